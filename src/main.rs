@@ -5,6 +5,9 @@ use cli::*;
 async fn main() -> anyhow::Result<()> {
     tracing_subscriber::fmt::init();
     let opts = Opts::parse();
-    opts.cmd.execute().await?;
-    Ok(())
+    match opts.cmd.execute().await {
+        // the reader of our stdout stopped early, which is how pipelines end
+        Err(err) if is_broken_pipe(&err) => Ok(()),
+        result => result,
+    }
 }
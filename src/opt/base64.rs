@@ -67,9 +67,9 @@ impl fmt::Display for Base64Format {
     }
 }
 
-///
-/// impl CmdExecutor
-///
+//
+// impl CmdExecutor
+//
 
 impl CmdExecutor for Base64EncodeOpts {
     async fn execute(self) -> anyhow::Result<()> {
@@ -1,74 +0,0 @@
-use super::verify_file;
-use crate::{process_csv, CmdExecutor};
-use clap::Parser;
-use std::fmt::Display;
-use std::str::FromStr;
-
-#[derive(Debug, Copy, Clone)]
-pub enum OutputFormat {
-    Json,
-    Yaml,
-    Xlsx,
-}
-
-#[derive(Debug, Parser)]
-pub struct CsvOpts {
-    #[arg(short, long, value_parser = verify_file)]
-    pub input: String,
-    #[arg(short, long)]
-    pub output: Option<String>,
-    #[arg(short, long, default_value_t = ',')]
-    pub delimiter: char,
-    #[arg(long, default_value_t = true)]
-    pub header: bool,
-    #[arg(long, default_value = "json", value_parser = parse_format)]
-    pub format: OutputFormat,
-}
-
-fn parse_format(format: &str) -> Result<OutputFormat, anyhow::Error> {
-    format.parse()
-}
-
-impl From<OutputFormat> for &'static str {
-    fn from(format: OutputFormat) -> Self {
-        match format {
-            OutputFormat::Json => "json",
-            OutputFormat::Yaml => "yaml",
-            OutputFormat::Xlsx => "xlsx",
-        }
-    }
-}
-
-impl FromStr for OutputFormat {
-    type Err = anyhow::Error;
-
-    fn from_str(value: &str) -> Result<Self, Self::Err> {
-        match value {
-            "json" => Ok(OutputFormat::Json),
-            "yaml" => Ok(OutputFormat::Yaml),
-            "xlsx" => Ok(OutputFormat::Xlsx),
-            _ => Err(anyhow::anyhow!("Invalid format")),
-        }
-    }
-}
-
-impl Display for OutputFormat {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", Into::<&str>::into(*self))
-    }
-}
-
-///
-/// impl CmdExecutor
-///
-
-impl CmdExecutor for CsvOpts {
-    async fn execute(self) -> anyhow::Result<()> {
-        let output = if let Some(output) = self.output {
-            output
-        } else {
-            format!("output.{}", self.format)
-        };
-        process_csv(&self.input, output, self.format)
-    }
-}
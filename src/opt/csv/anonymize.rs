@@ -0,0 +1,57 @@
+use super::{
+    output_format, output_path, parse_format, verify_file, CsvReaderArgs, CsvWriterArgs,
+    OutputFormat,
+};
+use crate::{process_csv_anonymize, CmdExecutor, ColumnRule, CsvReaderOptions};
+use clap::Parser;
+
+#[derive(Debug, Parser)]
+pub struct CsvAnonymizeOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+    /// hash, fake:name, fake:first_name, fake:last_name, fake:uuid, fake:regex:PATTERN,
+    /// truncate:N, redact or year, e.g. --rule Name=fake:name --rule DOB=year
+    #[arg(long = "rule", value_name = "COL=RULE", required = true, value_parser = parse_column_rule)]
+    pub rules: Vec<ColumnRule>,
+    /// blake3 key file for hash and fake, as made by `text generate`, so outputs match across
+    /// runs; a random key is used otherwise
+    #[arg(long, value_parser = verify_file)]
+    pub key: Option<String>,
+    #[arg(short, long)]
+    pub output: Option<String>,
+    /// defaults to json for CSV input and to csv for every other input
+    #[arg(long, value_parser = parse_format)]
+    pub format: Option<OutputFormat>,
+    #[command(flatten)]
+    pub reader: CsvReaderArgs,
+    #[command(flatten)]
+    pub writer: CsvWriterArgs,
+}
+
+fn parse_column_rule(rule: &str) -> Result<ColumnRule, anyhow::Error> {
+    rule.parse()
+}
+
+//
+// impl CmdExecutor
+//
+
+impl CmdExecutor for CsvAnonymizeOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let opts = CsvReaderOptions::from(&self.reader);
+        let writer_opts = self.writer.options(self.reader.delimiter);
+        let from = self.reader.input_format(&self.input);
+        let format = output_format(self.format, from);
+        let output = output_path(self.output, format);
+        process_csv_anonymize(
+            &self.input,
+            from,
+            &opts,
+            &self.rules,
+            self.key.as_deref(),
+            &output,
+            format,
+            &writer_opts,
+        )
+    }
+}
@@ -0,0 +1,210 @@
+use super::{Compression, InputFormat};
+use crate::{
+    CsvReaderOptions, CsvWriterOptions, DateColumn, DerivedColumn, Expr, SortKey, Transform,
+};
+use chrono::NaiveDate;
+use clap::{ArgAction, Args};
+use encoding_rs::Encoding;
+
+/// options shared by every csv command that reads records
+#[derive(Debug, Args)]
+pub struct CsvReaderArgs {
+    #[arg(short, long, default_value_t = ',', value_parser = parse_ascii_char)]
+    pub delimiter: char,
+    #[arg(long, default_value_t = '"', value_parser = parse_ascii_char)]
+    pub quote: char,
+    #[arg(long, default_value_t = true, action = ArgAction::Set)]
+    pub header: bool,
+    #[arg(long, value_parser = parse_ascii_char)]
+    pub comment: Option<char>,
+    #[arg(long)]
+    pub flexible: bool,
+    #[arg(long, default_value_t = 1000)]
+    pub infer_rows: usize,
+    /// input format, detected from the file extension by default
+    #[arg(long, value_parser = parse_input_format)]
+    pub from: Option<InputFormat>,
+    /// sheet name or zero-based index of xlsx/ods input
+    #[arg(long)]
+    pub sheet: Option<String>,
+    /// cell range of xlsx/ods input, e.g. A1:E20
+    #[arg(long)]
+    pub range: Option<String>,
+    /// encoding of CSV input, e.g. windows-1252 or utf-16le; a byte order mark takes precedence
+    /// and it is detected when omitted
+    #[arg(long, value_parser = parse_encoding)]
+    pub encoding: Option<&'static Encoding>,
+}
+
+/// options shared by every csv command that writes records
+#[derive(Debug, Args)]
+pub struct CsvWriterArgs {
+    /// delimiter of CSV output, defaults to --delimiter
+    #[arg(long, value_parser = parse_ascii_char)]
+    pub output_delimiter: Option<char>,
+    /// compression of parquet (default snappy) and arrow (default none) output
+    #[arg(long, value_parser = parse_compression)]
+    pub compression: Option<Compression>,
+    /// worksheet name of xlsx output
+    #[arg(long)]
+    pub sheet_name: Option<String>,
+    /// nest columns named like "address.city" or "tags[0]" in json, yaml and ndjson output
+    #[arg(long)]
+    pub unflatten: bool,
+}
+
+/// row and column trimming applied in this order: parse-date and derive, where, unique-by,
+/// sort-by, head, tail, then select/exclude
+#[derive(Debug, Args)]
+pub struct CsvTransformArgs {
+    /// columns to keep, in output order, e.g. "Name,Kit Number"
+    #[arg(long, value_delimiter = ',')]
+    pub select: Vec<String>,
+    /// columns to drop
+    #[arg(long, value_delimiter = ',')]
+    pub exclude: Vec<String>,
+    /// keep records matching an expression, e.g. "Position == Goalkeeper and `Kit Number` < 30"
+    #[arg(long = "where", value_name = "EXPR", value_parser = parse_expr)]
+    pub filter: Option<Expr>,
+    /// sort columns as COLUMN[:asc|:desc], e.g. "Nationality,Kit Number:desc"
+    #[arg(long, value_delimiter = ',', value_parser = parse_sort_key)]
+    pub sort_by: Vec<SortKey>,
+    /// keep only the first record for each distinct value of these columns
+    #[arg(long, value_delimiter = ',')]
+    pub unique_by: Vec<String>,
+    /// keep the first N records
+    #[arg(long)]
+    pub head: Option<usize>,
+    /// keep the last N records
+    #[arg(long)]
+    pub tail: Option<usize>,
+    /// normalize a date column to ISO 8601 as COLUMN[=FORMAT], e.g. "DOB=%b %d, %Y", the
+    /// format is detected when left out
+    #[arg(long, value_name = "COL=FORMAT", value_parser = parse_date_column)]
+    pub parse_date: Vec<DateColumn>,
+    /// add a column computed from a date column: age, year, month, day or weekday, e.g.
+    /// "Age=age(DOB)"
+    #[arg(long, value_name = "NAME=FUNC(COL)", value_parser = parse_derived_column)]
+    pub derive: Vec<DerivedColumn>,
+    /// reference date of age(), defaults to today
+    #[arg(long, value_name = "YYYY-MM-DD")]
+    pub as_of: Option<NaiveDate>,
+}
+
+fn parse_date_column(column: &str) -> Result<DateColumn, anyhow::Error> {
+    column.parse()
+}
+
+fn parse_derived_column(column: &str) -> Result<DerivedColumn, anyhow::Error> {
+    column.parse()
+}
+
+fn parse_input_format(format: &str) -> Result<InputFormat, anyhow::Error> {
+    format.parse()
+}
+
+fn parse_compression(compression: &str) -> Result<Compression, anyhow::Error> {
+    compression.parse()
+}
+
+fn parse_expr(expr: &str) -> Result<Expr, anyhow::Error> {
+    expr.parse()
+}
+
+fn parse_sort_key(key: &str) -> Result<SortKey, anyhow::Error> {
+    key.parse()
+}
+
+fn parse_encoding(label: &str) -> Result<&'static Encoding, &'static str> {
+    Encoding::for_label(label.as_bytes()).ok_or("Unknown encoding")
+}
+
+fn parse_ascii_char(value: &str) -> Result<char, &'static str> {
+    let c = match value {
+        "\\t" | "tab" => '\t',
+        _ => {
+            let mut chars = value.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => c,
+                _ => return Err("Must be a single character"),
+            }
+        }
+    };
+    if c.is_ascii() {
+        Ok(c)
+    } else {
+        Err("Must be an ASCII character")
+    }
+}
+
+impl From<&CsvReaderArgs> for CsvReaderOptions {
+    fn from(args: &CsvReaderArgs) -> Self {
+        Self {
+            delimiter: args.delimiter as u8,
+            quote: args.quote as u8,
+            has_headers: args.header,
+            comment: args.comment.map(|c| c as u8),
+            flexible: args.flexible,
+            infer_rows: args.infer_rows,
+            sheet: args.sheet.clone(),
+            range: args.range.clone(),
+            encoding: args.encoding,
+        }
+    }
+}
+
+impl From<&CsvTransformArgs> for Transform {
+    fn from(args: &CsvTransformArgs) -> Self {
+        Self {
+            select: args.select.clone(),
+            exclude: args.exclude.clone(),
+            filter: args.filter.clone(),
+            sort_by: args.sort_by.clone(),
+            unique_by: args.unique_by.clone(),
+            head: args.head,
+            tail: args.tail,
+            dates: args.parse_date.clone(),
+            derive: args.derive.clone(),
+            as_of: args.as_of,
+        }
+    }
+}
+
+impl CsvReaderArgs {
+    pub fn input_format(&self, input: &str) -> InputFormat {
+        self.from.unwrap_or_else(|| InputFormat::from_path(input))
+    }
+}
+
+impl CsvWriterArgs {
+    /// CSV output falls back to the input delimiter
+    pub fn options(&self, delimiter: char) -> CsvWriterOptions {
+        CsvWriterOptions {
+            delimiter: self.output_delimiter.unwrap_or(delimiter) as u8,
+            compression: self.compression,
+            sheet_name: self.sheet_name.clone(),
+            unflatten: self.unflatten,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_encoding() {
+        assert_eq!(parse_encoding("latin1"), Ok(encoding_rs::WINDOWS_1252));
+        assert_eq!(parse_encoding("UTF-16LE"), Ok(encoding_rs::UTF_16LE));
+        assert!(parse_encoding("klingon").is_err());
+    }
+
+    #[test]
+    fn test_parse_ascii_char() {
+        assert_eq!(parse_ascii_char(";"), Ok(';'));
+        assert_eq!(parse_ascii_char("\\t"), Ok('\t'));
+        assert_eq!(parse_ascii_char("tab"), Ok('\t'));
+        assert!(parse_ascii_char(";;").is_err());
+        assert!(parse_ascii_char("é").is_err());
+    }
+}
@@ -0,0 +1,98 @@
+use super::{verify_file, CsvReaderArgs};
+use crate::{process_csv_codegen, CmdExecutor, CsvReaderOptions};
+use clap::Parser;
+use std::fmt::Display;
+use std::fs;
+use std::str::FromStr;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CodegenLang {
+    Rust,
+    Typescript,
+    Jsonschema,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvCodegenOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+    #[arg(long, default_value = "rust", value_parser = parse_codegen_lang)]
+    pub lang: CodegenLang,
+    /// name of the struct, interface or schema title, defaults to the input file name
+    #[arg(long)]
+    pub name: Option<String>,
+    /// write the code to a file instead of stdout
+    #[arg(short, long)]
+    pub output: Option<String>,
+    #[command(flatten)]
+    pub reader: CsvReaderArgs,
+}
+
+fn parse_codegen_lang(lang: &str) -> Result<CodegenLang, anyhow::Error> {
+    lang.parse()
+}
+
+impl From<CodegenLang> for &'static str {
+    fn from(lang: CodegenLang) -> Self {
+        match lang {
+            CodegenLang::Rust => "rust",
+            CodegenLang::Typescript => "typescript",
+            CodegenLang::Jsonschema => "jsonschema",
+        }
+    }
+}
+
+impl FromStr for CodegenLang {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "rust" => Ok(CodegenLang::Rust),
+            "typescript" | "ts" => Ok(CodegenLang::Typescript),
+            "jsonschema" => Ok(CodegenLang::Jsonschema),
+            _ => Err(anyhow::anyhow!("Invalid language")),
+        }
+    }
+}
+
+impl Display for CodegenLang {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+//
+// impl CmdExecutor
+//
+
+impl CmdExecutor for CsvCodegenOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let opts = CsvReaderOptions::from(&self.reader);
+        let from = self.reader.input_format(&self.input);
+        let code = process_csv_codegen(&self.input, from, &opts, self.lang, self.name.as_deref())?;
+        match self.output {
+            Some(output) => fs::write(output, code)?,
+            None => print!("{}", code),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CsvOpts, CsvSubCommand};
+
+    #[test]
+    fn test_codegen_args() {
+        let opts = CsvOpts::try_parse_from(["csv", "codegen", "--lang", "ts"]).unwrap();
+        assert!(matches!(
+            opts.cmd,
+            Some(CsvSubCommand::Codegen(CsvCodegenOpts {
+                lang: CodegenLang::Typescript,
+                ref input,
+                ..
+            })) if input == "-"
+        ));
+    }
+}
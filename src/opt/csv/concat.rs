@@ -0,0 +1,40 @@
+use super::{
+    output_format, output_path, parse_format, verify_file, CsvReaderArgs, CsvWriterArgs,
+    OutputFormat,
+};
+use crate::{process_csv_concat, CmdExecutor, CsvReaderOptions};
+use clap::Parser;
+
+#[derive(Debug, Parser)]
+pub struct CsvConcatOpts {
+    #[arg(required = true, value_parser = verify_file)]
+    pub inputs: Vec<String>,
+    #[arg(short, long)]
+    pub output: Option<String>,
+    /// defaults to json for CSV input and to csv for every other input
+    #[arg(long, value_parser = parse_format)]
+    pub format: Option<OutputFormat>,
+    #[command(flatten)]
+    pub reader: CsvReaderArgs,
+    #[command(flatten)]
+    pub writer: CsvWriterArgs,
+}
+
+//
+// impl CmdExecutor
+//
+
+impl CmdExecutor for CsvConcatOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let opts = CsvReaderOptions::from(&self.reader);
+        let writer_opts = self.writer.options(self.reader.delimiter);
+        let inputs: Vec<_> = self
+            .inputs
+            .iter()
+            .map(|input| (input.as_str(), self.reader.input_format(input)))
+            .collect();
+        let format = output_format(self.format, inputs[0].1);
+        let output = output_path(self.output, format);
+        process_csv_concat(&inputs, &opts, &output, format, &writer_opts)
+    }
+}
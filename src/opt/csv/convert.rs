@@ -0,0 +1,157 @@
+use super::{is_glob, output_format, output_path, CsvOpts};
+use crate::{
+    batch_output, expand_glob, process_csv, process_csv_batch, BatchJob, BatchOptions, CmdExecutor,
+    CsvReaderOptions, Transform,
+};
+use std::fs;
+use std::io::{stdin, IsTerminal};
+
+impl CsvOpts {
+    /// convert every file matched by `input` into --output-dir and print a per-file summary
+    fn convert_batch(&self, input: &str) -> anyhow::Result<()> {
+        let dir = self.output_dir.as_deref().ok_or_else(|| {
+            anyhow::anyhow!("--output-dir is required when --input is a glob pattern")
+        })?;
+        let inputs = if is_glob(input) {
+            expand_glob(input)?
+        } else {
+            vec![input.to_string()]
+        };
+        fs::create_dir_all(dir)?;
+        let jobs: Vec<BatchJob> = inputs
+            .into_iter()
+            .map(|input| {
+                let from = self.reader.input_format(&input);
+                let format = output_format(self.format, from);
+                BatchJob {
+                    output: batch_output(dir, &input, format),
+                    input,
+                    from,
+                    format,
+                }
+            })
+            .collect();
+        let total = jobs.len();
+        let mut batch = BatchOptions {
+            keep_going: self.keep_going,
+            progress: std::io::stderr().is_terminal(),
+            ..Default::default()
+        };
+        if let Some(n) = self.jobs {
+            batch.jobs = n;
+        }
+        let outcomes = process_csv_batch(
+            jobs,
+            &CsvReaderOptions::from(&self.reader),
+            &self.writer.options(self.reader.delimiter),
+            &Transform::from(&self.transform),
+            &batch,
+        )?;
+        for outcome in &outcomes {
+            println!("{}", outcome);
+        }
+        let failed = outcomes.iter().filter(|o| o.result.is_err()).count();
+        let skipped = total - outcomes.len();
+        match (failed, skipped) {
+            (0, _) => {
+                println!("converted {} files into {}", total, dir);
+                Ok(())
+            }
+            (_, 0) => Err(anyhow::anyhow!("{} of {} files failed", failed, total)),
+            _ => Err(anyhow::anyhow!(
+                "{} of {} files failed, {} not started, use --keep-going to convert the rest",
+                failed,
+                total,
+                skipped
+            )),
+        }
+    }
+}
+
+//
+// impl CmdExecutor
+//
+
+impl CmdExecutor for CsvOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        if let Some(cmd) = self.cmd {
+            return cmd.execute().await;
+        }
+        let input = match self.input.clone() {
+            Some(input) => input,
+            None if stdin().is_terminal() => {
+                return Err(anyhow::anyhow!("no --input given and stdin is a terminal"))
+            }
+            None => "-".to_string(),
+        };
+        if is_glob(&input) || self.output_dir.is_some() {
+            return self.convert_batch(&input);
+        }
+        let opts = CsvReaderOptions::from(&self.reader);
+        let writer_opts = self.writer.options(self.reader.delimiter);
+        let from = self.reader.input_format(&input);
+        let format = output_format(self.format, from);
+        let output = output_path(self.output, format);
+        let transform = Transform::from(&self.transform);
+        process_csv(
+            &input,
+            output,
+            format,
+            from,
+            &opts,
+            &writer_opts,
+            &transform,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OutputFormat;
+    use clap::Parser;
+
+    #[test]
+    fn test_default_output_format() {
+        let opts = CsvOpts::try_parse_from(["csv", "-i", "-", "--from", "ndjson"]).unwrap();
+        let from = opts.reader.input_format("-");
+        assert!(matches!(
+            output_format(opts.format, from),
+            OutputFormat::Csv
+        ));
+        let opts = CsvOpts::try_parse_from(["csv", "-i", "-"]).unwrap();
+        let from = opts.reader.input_format("-");
+        assert!(matches!(
+            output_format(opts.format, from),
+            OutputFormat::Json
+        ));
+    }
+
+    #[test]
+    fn test_transform_args() {
+        let opts = CsvOpts::try_parse_from([
+            "csv",
+            "-i",
+            "-",
+            "--select",
+            "Name,Kit Number",
+            "--where",
+            "Position == Goalkeeper",
+            "--sort-by",
+            "Nationality,Kit Number:desc",
+            "--head",
+            "3",
+        ])
+        .unwrap();
+        let transform = Transform::from(&opts.transform);
+        assert_eq!(transform.select, vec!["Name", "Kit Number"]);
+        assert_eq!(transform.sort_by.len(), 2);
+        assert!(transform.sort_by[1].descending);
+        assert_eq!(transform.head, Some(3));
+
+        let err = CsvOpts::try_parse_from(["csv", "-i", "-", "--where", "Position =="])
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("expected a value at position 12"), "{}", err);
+    }
+}
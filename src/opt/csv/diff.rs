@@ -0,0 +1,122 @@
+use super::{verify_file, CsvReaderArgs};
+use crate::{process_csv_diff, render_diff, CmdExecutor, CsvReaderOptions};
+use clap::Parser;
+use std::fmt::Display;
+use std::fs;
+use std::io::IsTerminal;
+use std::str::FromStr;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DiffFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvDiffOpts {
+    /// a file, or `-` for stdin
+    #[arg(value_parser = verify_file)]
+    pub old: String,
+    /// a file, or `-` for stdin if OLD is a file
+    #[arg(value_parser = verify_file)]
+    pub new: String,
+    /// columns identifying a row in both files, e.g. "Name"
+    #[arg(short, long, required = true, value_delimiter = ',')]
+    pub key: Vec<String>,
+    /// write the diff to a file instead of stdout
+    #[arg(short, long)]
+    pub output: Option<String>,
+    #[arg(long, default_value = "text", value_parser = parse_diff_format)]
+    pub format: DiffFormat,
+    /// disable colors, which are only used when writing text to a terminal anyway
+    #[arg(long)]
+    pub no_color: bool,
+    #[command(flatten)]
+    pub reader: CsvReaderArgs,
+}
+
+fn parse_diff_format(format: &str) -> Result<DiffFormat, anyhow::Error> {
+    format.parse()
+}
+
+impl From<DiffFormat> for &'static str {
+    fn from(format: DiffFormat) -> Self {
+        match format {
+            DiffFormat::Text => "text",
+            DiffFormat::Json => "json",
+        }
+    }
+}
+
+impl FromStr for DiffFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "text" => Ok(DiffFormat::Text),
+            "json" => Ok(DiffFormat::Json),
+            _ => Err(anyhow::anyhow!("Invalid format")),
+        }
+    }
+}
+
+impl Display for DiffFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+//
+// impl CmdExecutor
+//
+
+impl CmdExecutor for CsvDiffOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        if self.old == "-" && self.new == "-" {
+            return Err(anyhow::anyhow!(
+                "only one of OLD and NEW can be read from stdin"
+            ));
+        }
+        let opts = CsvReaderOptions::from(&self.reader);
+        let old = (self.old.as_str(), self.reader.input_format(&self.old));
+        let new = (self.new.as_str(), self.reader.input_format(&self.new));
+        let diff = process_csv_diff(old, new, &opts, &self.key)?;
+        match self.output {
+            Some(output) => fs::write(output, render_diff(&diff, self.format, false)?)?,
+            None => {
+                let color = !self.no_color && std::io::stdout().is_terminal();
+                print!("{}", render_diff(&diff, self.format, color)?)
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CsvOpts, CsvSubCommand};
+
+    #[test]
+    fn test_diff_args() {
+        let opts =
+            CsvOpts::try_parse_from(["csv", "diff", "--key", "Name", "-", "assets/juventus.csv"])
+                .unwrap();
+        assert!(matches!(
+            opts.cmd,
+            Some(CsvSubCommand::Diff(CsvDiffOpts {
+                format: DiffFormat::Text,
+                ref old,
+                ref key,
+                ..
+            })) if old == "-" && key == &["Name"]
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_diff_reads_stdin_once() {
+        let opts = CsvDiffOpts::try_parse_from(["diff", "--key", "Name", "-", "-"]).unwrap();
+        let err = opts.execute().await.unwrap_err().to_string();
+        assert_eq!(err, "only one of OLD and NEW can be read from stdin");
+    }
+}
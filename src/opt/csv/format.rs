@@ -0,0 +1,205 @@
+use crate::utils::strip_compression_ext;
+use std::fmt::Display;
+use std::str::FromStr;
+
+#[derive(Debug, Copy, Clone)]
+pub enum OutputFormat {
+    Json,
+    Yaml,
+    Xlsx,
+    Ndjson,
+    Toml,
+    Markdown,
+    Html,
+    Csv,
+    Parquet,
+    Arrow,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Snappy,
+    Gzip,
+    Zstd,
+    Lz4,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum InputFormat {
+    Csv,
+    Json,
+    Yaml,
+    Ndjson,
+    Xlsx,
+    Ods,
+}
+
+impl InputFormat {
+    /// guess the format from the file extension, defaulting to csv
+    ///
+    /// A compression extension is skipped, so `data.json.gz` is json.
+    pub fn from_path(path: &str) -> Self {
+        let ext = std::path::Path::new(strip_compression_ext(path))
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+        match ext.as_deref() {
+            Some("json") => InputFormat::Json,
+            Some("yaml" | "yml") => InputFormat::Yaml,
+            Some("ndjson" | "jsonl") => InputFormat::Ndjson,
+            Some("xlsx" | "xlsm" | "xlsb" | "xls") => InputFormat::Xlsx,
+            Some("ods") => InputFormat::Ods,
+            _ => InputFormat::Csv,
+        }
+    }
+}
+
+impl OutputFormat {
+    /// formats written as a whole file rather than as text, so they can't be compressed by
+    /// extension or shown on a terminal
+    pub fn is_binary(self) -> bool {
+        matches!(
+            self,
+            OutputFormat::Xlsx | OutputFormat::Parquet | OutputFormat::Arrow
+        )
+    }
+}
+
+impl From<OutputFormat> for &'static str {
+    fn from(format: OutputFormat) -> Self {
+        match format {
+            OutputFormat::Json => "json",
+            OutputFormat::Yaml => "yaml",
+            OutputFormat::Xlsx => "xlsx",
+            OutputFormat::Ndjson => "ndjson",
+            OutputFormat::Toml => "toml",
+            OutputFormat::Markdown => "markdown",
+            OutputFormat::Html => "html",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Parquet => "parquet",
+            OutputFormat::Arrow => "arrow",
+        }
+    }
+}
+
+impl From<Compression> for &'static str {
+    fn from(compression: Compression) -> Self {
+        match compression {
+            Compression::None => "none",
+            Compression::Snappy => "snappy",
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+            Compression::Lz4 => "lz4",
+        }
+    }
+}
+
+impl From<InputFormat> for &'static str {
+    fn from(format: InputFormat) -> Self {
+        match format {
+            InputFormat::Csv => "csv",
+            InputFormat::Json => "json",
+            InputFormat::Yaml => "yaml",
+            InputFormat::Ndjson => "ndjson",
+            InputFormat::Xlsx => "xlsx",
+            InputFormat::Ods => "ods",
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "json" => Ok(OutputFormat::Json),
+            "yaml" => Ok(OutputFormat::Yaml),
+            "xlsx" => Ok(OutputFormat::Xlsx),
+            "ndjson" => Ok(OutputFormat::Ndjson),
+            "toml" => Ok(OutputFormat::Toml),
+            "markdown" => Ok(OutputFormat::Markdown),
+            "html" => Ok(OutputFormat::Html),
+            "csv" => Ok(OutputFormat::Csv),
+            "parquet" => Ok(OutputFormat::Parquet),
+            "arrow" => Ok(OutputFormat::Arrow),
+            _ => Err(anyhow::anyhow!("Invalid format")),
+        }
+    }
+}
+
+impl FromStr for Compression {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "none" => Ok(Compression::None),
+            "snappy" => Ok(Compression::Snappy),
+            "gzip" => Ok(Compression::Gzip),
+            "zstd" => Ok(Compression::Zstd),
+            "lz4" => Ok(Compression::Lz4),
+            _ => Err(anyhow::anyhow!("Invalid compression")),
+        }
+    }
+}
+
+impl FromStr for InputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "csv" => Ok(InputFormat::Csv),
+            "json" => Ok(InputFormat::Json),
+            "yaml" => Ok(InputFormat::Yaml),
+            "ndjson" => Ok(InputFormat::Ndjson),
+            "xlsx" => Ok(InputFormat::Xlsx),
+            "ods" => Ok(InputFormat::Ods),
+            _ => Err(anyhow::anyhow!("Invalid format")),
+        }
+    }
+}
+
+impl Display for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl Display for Compression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl Display for InputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_format_round_trip() {
+        for name in [
+            "json", "yaml", "xlsx", "ndjson", "toml", "markdown", "html", "csv", "parquet", "arrow",
+        ] {
+            let format: OutputFormat = name.parse().unwrap();
+            assert_eq!(format.to_string(), name);
+        }
+        assert!("csv2".parse::<OutputFormat>().is_err());
+    }
+
+    #[test]
+    fn test_input_format_from_path() {
+        assert_eq!(InputFormat::from_path("a.csv"), InputFormat::Csv);
+        assert_eq!(InputFormat::from_path("a.JSONL"), InputFormat::Ndjson);
+        assert_eq!(InputFormat::from_path("a.yml"), InputFormat::Yaml);
+        assert_eq!(InputFormat::from_path("a.xlsx"), InputFormat::Xlsx);
+        assert_eq!(InputFormat::from_path("a.ods"), InputFormat::Ods);
+        assert_eq!(InputFormat::from_path("-"), InputFormat::Csv);
+        assert_eq!(InputFormat::from_path("a.json.gz"), InputFormat::Json);
+        assert_eq!(InputFormat::from_path("a.csv.zst"), InputFormat::Csv);
+    }
+}
@@ -0,0 +1,42 @@
+use super::{output_path, parse_format, verify_file, CsvWriterArgs, OutputFormat};
+use crate::{process_csv_generate, CmdExecutor, GenerateSchema};
+use clap::Parser;
+
+#[derive(Debug, Parser)]
+pub struct CsvGenerateOpts {
+    /// schema file, JSON if it ends in .json and YAML otherwise
+    #[arg(short, long, value_parser = verify_file)]
+    pub schema: String,
+    #[arg(short, long, default_value_t = 100)]
+    pub rows: usize,
+    /// seed for reproducible output, overrides the schema's seed
+    #[arg(long)]
+    pub seed: Option<u64>,
+    #[arg(short, long)]
+    pub output: Option<String>,
+    #[arg(long, default_value = "csv", value_parser = parse_format)]
+    pub format: OutputFormat,
+    #[command(flatten)]
+    pub writer: CsvWriterArgs,
+}
+
+//
+// impl CmdExecutor
+//
+
+impl CmdExecutor for CsvGenerateOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let schema = GenerateSchema::load(&self.schema)?;
+        let writer_opts = self.writer.options(',');
+        let output = output_path(self.output, self.format);
+        process_csv_generate(
+            &schema,
+            self.rows,
+            self.seed,
+            &output,
+            self.format,
+            &writer_opts,
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,125 @@
+use super::{
+    output_format, output_path, parse_format, verify_file, CsvReaderArgs, CsvWriterArgs,
+    OutputFormat,
+};
+use crate::{process_csv_join, CmdExecutor, CsvReaderOptions, JoinOptions};
+use clap::Parser;
+use std::fmt::Display;
+use std::str::FromStr;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum JoinKind {
+    Inner,
+    Left,
+    Right,
+    Full,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvJoinOpts {
+    #[arg(long, value_parser = verify_file)]
+    pub left: String,
+    #[arg(long, value_parser = verify_file)]
+    pub right: String,
+    /// key columns, either a shared name or LEFT=RIGHT, e.g. "Nationality=Country"
+    #[arg(long, required = true, value_delimiter = ',', value_parser = parse_join_on)]
+    pub on: Vec<(String, String)>,
+    /// inner, left, right or full
+    #[arg(long, default_value = "inner", value_parser = parse_join_kind)]
+    pub how: JoinKind,
+    #[arg(short, long)]
+    pub output: Option<String>,
+    /// defaults to json for CSV input and to csv for every other input
+    #[arg(long, value_parser = parse_format)]
+    pub format: Option<OutputFormat>,
+    #[command(flatten)]
+    pub reader: CsvReaderArgs,
+    #[command(flatten)]
+    pub writer: CsvWriterArgs,
+}
+
+fn parse_join_kind(kind: &str) -> Result<JoinKind, anyhow::Error> {
+    kind.parse()
+}
+
+fn parse_join_on(on: &str) -> Result<(String, String), &'static str> {
+    let (left, right) = on.split_once('=').unwrap_or((on, on));
+    if left.is_empty() || right.is_empty() {
+        return Err("Must be COLUMN or LEFT=RIGHT");
+    }
+    Ok((left.to_string(), right.to_string()))
+}
+
+impl From<JoinKind> for &'static str {
+    fn from(kind: JoinKind) -> Self {
+        match kind {
+            JoinKind::Inner => "inner",
+            JoinKind::Left => "left",
+            JoinKind::Right => "right",
+            JoinKind::Full => "full",
+        }
+    }
+}
+
+impl FromStr for JoinKind {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "inner" => Ok(JoinKind::Inner),
+            "left" => Ok(JoinKind::Left),
+            "right" => Ok(JoinKind::Right),
+            "full" => Ok(JoinKind::Full),
+            _ => Err(anyhow::anyhow!("Invalid join")),
+        }
+    }
+}
+
+impl Display for JoinKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+//
+// impl CmdExecutor
+//
+
+impl CmdExecutor for CsvJoinOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let opts = CsvReaderOptions::from(&self.reader);
+        let writer_opts = self.writer.options(self.reader.delimiter);
+        let left = (self.left.as_str(), self.reader.input_format(&self.left));
+        let right = (self.right.as_str(), self.reader.input_format(&self.right));
+        let format = output_format(self.format, left.1);
+        let output = output_path(self.output, format);
+        let join_opts = JoinOptions {
+            on: self.on,
+            kind: self.how,
+        };
+        process_csv_join(
+            left,
+            right,
+            &opts,
+            &join_opts,
+            &output,
+            format,
+            &writer_opts,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_join_on() {
+        assert_eq!(parse_join_on("Name"), Ok(("Name".into(), "Name".into())));
+        assert_eq!(
+            parse_join_on("Nationality=Country"),
+            Ok(("Nationality".into(), "Country".into()))
+        );
+        assert!(parse_join_on("=Country").is_err());
+    }
+}
@@ -0,0 +1,62 @@
+use super::{
+    output_format, output_path, parse_format, verify_file, CsvReaderArgs, CsvWriterArgs,
+    OutputFormat,
+};
+use crate::{process_csv_melt, CmdExecutor, CsvReaderOptions, MeltOptions};
+use clap::Parser;
+
+#[derive(Debug, Parser)]
+pub struct CsvMeltOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+    /// columns kept on every output row, e.g. "Name"
+    #[arg(long, value_delimiter = ',')]
+    pub id: Vec<String>,
+    /// columns turned into rows, defaults to every column that isn't an id
+    #[arg(long, value_delimiter = ',')]
+    pub value_columns: Vec<String>,
+    /// name of the output column holding the melted column names
+    #[arg(long, default_value = "variable")]
+    pub var_name: String,
+    /// name of the output column holding their values
+    #[arg(long, default_value = "value")]
+    pub value_name: String,
+    #[arg(short, long)]
+    pub output: Option<String>,
+    /// defaults to json for CSV input and to csv for every other input
+    #[arg(long, value_parser = parse_format)]
+    pub format: Option<OutputFormat>,
+    #[command(flatten)]
+    pub reader: CsvReaderArgs,
+    #[command(flatten)]
+    pub writer: CsvWriterArgs,
+}
+
+//
+// impl CmdExecutor
+//
+
+impl CmdExecutor for CsvMeltOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let opts = CsvReaderOptions::from(&self.reader);
+        let writer_opts = self.writer.options(self.reader.delimiter);
+        let from = self.reader.input_format(&self.input);
+        let format = output_format(self.format, from);
+        let output = output_path(self.output, format);
+        let melt_opts = MeltOptions {
+            id: self.id,
+            value_columns: self.value_columns,
+            var_name: self.var_name,
+            value_name: self.value_name,
+        };
+        process_csv_melt(
+            &self.input,
+            from,
+            &opts,
+            &melt_opts,
+            &output,
+            format,
+            &writer_opts,
+        )
+    }
+}
@@ -0,0 +1,151 @@
+mod anonymize;
+mod args;
+mod codegen;
+mod concat;
+mod convert;
+mod diff;
+mod format;
+mod generate;
+mod join;
+mod melt;
+mod pivot;
+mod query;
+mod split;
+mod stats;
+mod validate;
+mod view;
+
+use super::verify_file;
+use clap::Parser;
+use enum_dispatch::enum_dispatch;
+
+pub use self::{
+    anonymize::*, args::*, codegen::*, concat::*, diff::*, format::*, generate::*, join::*,
+    melt::*, pivot::*, query::*, split::*, stats::*, validate::*, view::*,
+};
+
+#[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+pub struct CsvOpts {
+    #[command(subcommand)]
+    pub cmd: Option<CsvSubCommand>,
+    /// a file, or a quoted glob pattern such as 'data/*.csv' together with --output-dir,
+    /// defaults to stdin
+    #[arg(short, long, value_parser = verify_input)]
+    pub input: Option<String>,
+    /// defaults to output.<format>, `-` writes to stdout
+    #[arg(short, long)]
+    pub output: Option<String>,
+    /// defaults to json for CSV input and to csv for every other input
+    #[arg(long, value_parser = parse_format)]
+    pub format: Option<OutputFormat>,
+    /// convert every input into this directory, named after the input file
+    #[arg(long, conflicts_with = "output")]
+    pub output_dir: Option<String>,
+    /// number of files converted in parallel, defaults to the number of CPUs
+    #[arg(short, long)]
+    pub jobs: Option<usize>,
+    /// keep converting after a file fails, the failures are reported at the end
+    #[arg(long)]
+    pub keep_going: bool,
+    #[command(flatten)]
+    pub reader: CsvReaderArgs,
+    #[command(flatten)]
+    pub writer: CsvWriterArgs,
+    #[command(flatten)]
+    pub transform: CsvTransformArgs,
+}
+
+// parsed once at startup, like SubCommand
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExecutor)]
+pub enum CsvSubCommand {
+    #[command(about = "Run a SQL-like query over a CSV file")]
+    Query(CsvQueryOpts),
+
+    #[command(about = "Profile every column of a CSV file")]
+    Stats(CsvStatsOpts),
+
+    #[command(about = "Validate a CSV file against a JSON or YAML schema")]
+    Validate(CsvValidateOpts),
+
+    #[command(about = "Join two files on key columns")]
+    Join(CsvJoinOpts),
+
+    #[command(about = "Concatenate files, taking the union of their headers")]
+    Concat(CsvConcatOpts),
+
+    #[command(about = "Show added, removed and modified rows between two files")]
+    Diff(CsvDiffOpts),
+
+    #[command(about = "Generate fake records from a schema of column generators")]
+    Generate(CsvGenerateOpts),
+
+    #[command(about = "Show a file as a table in the terminal, optionally in a pager")]
+    View(CsvViewOpts),
+
+    #[command(about = "Generate a Rust struct, TypeScript interface or JSON Schema for a file")]
+    Codegen(CsvCodegenOpts),
+
+    #[command(about = "Reshape long to wide, aggregating the values of each cell")]
+    Pivot(CsvPivotOpts),
+
+    #[command(about = "Reshape wide to long, one row per value column of each record")]
+    Melt(CsvMeltOpts),
+
+    #[command(about = "Hash, fake, truncate or redact columns before sharing a file")]
+    Anonymize(CsvAnonymizeOpts),
+
+    #[command(about = "Split a file into pieces by row count or by the value of a column")]
+    Split(CsvSplitOpts),
+}
+
+/// a glob pattern is checked for syntax only, anything else must be an existing file or "-"
+fn verify_input(input: &str) -> Result<String, &'static str> {
+    if is_glob(input) {
+        glob::Pattern::new(input)
+            .map(|_| input.to_string())
+            .map_err(|_| "Invalid glob pattern")
+    } else {
+        verify_file(input)
+    }
+}
+
+/// an existing file whose name happens to contain `[` is still a file
+fn is_glob(input: &str) -> bool {
+    input.contains(['*', '?', '[']) && !std::path::Path::new(input).exists()
+}
+
+fn parse_format(format: &str) -> Result<OutputFormat, anyhow::Error> {
+    format.parse()
+}
+
+/// tabular inputs default to json, structured inputs to csv
+fn output_format(format: Option<OutputFormat>, from: InputFormat) -> OutputFormat {
+    match (format, from) {
+        (Some(format), _) => format,
+        (None, InputFormat::Csv | InputFormat::Xlsx | InputFormat::Ods) => OutputFormat::Json,
+        (None, _) => OutputFormat::Csv,
+    }
+}
+
+fn output_path(output: Option<String>, format: OutputFormat) -> String {
+    output.unwrap_or_else(|| format!("output.{}", format))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_subcommands() {
+        // without a subcommand or --input the conversion reads stdin
+        let opts = CsvOpts::try_parse_from(["csv"]).unwrap();
+        assert!(opts.cmd.is_none() && opts.input.is_none());
+        // a subcommand's --input is its own
+        let opts = CsvOpts::try_parse_from(["csv", "stats", "-i", "assets/juventus.csv"]).unwrap();
+        assert!(opts.input.is_none());
+        assert!(matches!(opts.cmd, Some(CsvSubCommand::Stats(_))));
+    }
+}
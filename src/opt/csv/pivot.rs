@@ -0,0 +1,66 @@
+use super::{
+    output_format, output_path, parse_format, verify_file, CsvReaderArgs, CsvWriterArgs,
+    OutputFormat,
+};
+use crate::{process_csv_pivot, Aggregate, CmdExecutor, CsvReaderOptions, PivotOptions};
+use clap::Parser;
+
+#[derive(Debug, Parser)]
+pub struct CsvPivotOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+    /// columns identifying an output row, e.g. "Nationality"
+    #[arg(long, required = true, value_delimiter = ',')]
+    pub index: Vec<String>,
+    /// column whose distinct values become the output columns, e.g. "Position"
+    #[arg(long)]
+    pub columns: String,
+    /// column to aggregate, optional for count which then counts rows
+    #[arg(long)]
+    pub values: Option<String>,
+    /// count, sum, avg, min or max
+    #[arg(long, default_value = "count", value_parser = parse_aggregate)]
+    pub agg: Aggregate,
+    #[arg(short, long)]
+    pub output: Option<String>,
+    /// defaults to json for CSV input and to csv for every other input
+    #[arg(long, value_parser = parse_format)]
+    pub format: Option<OutputFormat>,
+    #[command(flatten)]
+    pub reader: CsvReaderArgs,
+    #[command(flatten)]
+    pub writer: CsvWriterArgs,
+}
+
+fn parse_aggregate(agg: &str) -> Result<Aggregate, anyhow::Error> {
+    agg.parse()
+}
+
+//
+// impl CmdExecutor
+//
+
+impl CmdExecutor for CsvPivotOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let opts = CsvReaderOptions::from(&self.reader);
+        let writer_opts = self.writer.options(self.reader.delimiter);
+        let from = self.reader.input_format(&self.input);
+        let format = output_format(self.format, from);
+        let output = output_path(self.output, format);
+        let pivot_opts = PivotOptions {
+            index: self.index,
+            columns: self.columns,
+            values: self.values,
+            agg: self.agg,
+        };
+        process_csv_pivot(
+            &self.input,
+            from,
+            &opts,
+            &pivot_opts,
+            &output,
+            format,
+            &writer_opts,
+        )
+    }
+}
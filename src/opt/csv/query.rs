@@ -0,0 +1,72 @@
+use super::{
+    output_format, output_path, parse_format, verify_file, CsvReaderArgs, CsvWriterArgs,
+    OutputFormat,
+};
+use crate::{process_csv_query, CmdExecutor, CsvReaderOptions};
+use clap::Parser;
+
+#[derive(Debug, Parser)]
+pub struct CsvQueryOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+    /// e.g. "SELECT Nationality, count(*) AS players GROUP BY Nationality ORDER BY players DESC"
+    pub query: String,
+    #[arg(short, long)]
+    pub output: Option<String>,
+    /// defaults to json for CSV input and to csv for every other input
+    #[arg(long, value_parser = parse_format)]
+    pub format: Option<OutputFormat>,
+    #[command(flatten)]
+    pub reader: CsvReaderArgs,
+    #[command(flatten)]
+    pub writer: CsvWriterArgs,
+}
+
+//
+// impl CmdExecutor
+//
+
+impl CmdExecutor for CsvQueryOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let opts = CsvReaderOptions::from(&self.reader);
+        let writer_opts = self.writer.options(self.reader.delimiter);
+        let from = self.reader.input_format(&self.input);
+        let format = output_format(self.format, from);
+        let output = output_path(self.output, format);
+        process_csv_query(
+            &self.input,
+            from,
+            &opts,
+            &self.query,
+            &output,
+            format,
+            &writer_opts,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CsvOpts, CsvSubCommand};
+
+    #[test]
+    fn test_query_args() {
+        let opts = CsvOpts::try_parse_from([
+            "csv",
+            "query",
+            "SELECT Nationality, count(*) GROUP BY Nationality",
+            "--format",
+            "markdown",
+        ])
+        .unwrap();
+        assert!(matches!(
+            opts.cmd,
+            Some(CsvSubCommand::Query(CsvQueryOpts {
+                format: Some(OutputFormat::Markdown),
+                ref input,
+                ..
+            })) if input == "-"
+        ));
+    }
+}
@@ -0,0 +1,72 @@
+use super::{parse_format, verify_file, CsvReaderArgs, CsvWriterArgs, InputFormat, OutputFormat};
+use crate::{
+    process_csv_split, split_prefix, CmdExecutor, CsvReaderOptions, SplitMode, SplitOptions,
+};
+use clap::Parser;
+
+#[derive(Debug, Parser)]
+pub struct CsvSplitOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+    /// records per piece
+    #[arg(long, conflicts_with = "by", required_unless_present = "by")]
+    pub rows: Option<usize>,
+    /// column whose values each get a piece, e.g. Nationality
+    #[arg(long)]
+    pub by: Option<String>,
+    #[arg(long, default_value = ".")]
+    pub output_dir: String,
+    /// start of the piece names, defaults to the input file name or `split` for stdin
+    #[arg(long)]
+    pub prefix: Option<String>,
+    /// format of the pieces, defaults to the input's format
+    #[arg(long, value_parser = parse_format)]
+    pub format: Option<OutputFormat>,
+    /// most files `--by` keeps open at once
+    #[arg(long, default_value_t = 64)]
+    pub max_open: usize,
+    #[command(flatten)]
+    pub reader: CsvReaderArgs,
+    #[command(flatten)]
+    pub writer: CsvWriterArgs,
+}
+
+/// pieces of a split keep the input's format unless asked otherwise
+fn split_format(format: Option<OutputFormat>, from: InputFormat) -> OutputFormat {
+    match (format, from) {
+        (Some(format), _) => format,
+        (None, InputFormat::Json) => OutputFormat::Json,
+        (None, InputFormat::Yaml) => OutputFormat::Yaml,
+        (None, InputFormat::Ndjson) => OutputFormat::Ndjson,
+        (None, InputFormat::Xlsx) => OutputFormat::Xlsx,
+        (None, InputFormat::Csv | InputFormat::Ods) => OutputFormat::Csv,
+    }
+}
+
+//
+// impl CmdExecutor
+//
+
+impl CmdExecutor for CsvSplitOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let opts = CsvReaderOptions::from(&self.reader);
+        let writer_opts = self.writer.options(self.reader.delimiter);
+        let from = self.reader.input_format(&self.input);
+        let mode = match (self.rows, self.by) {
+            (Some(rows), _) => SplitMode::Rows(rows),
+            (None, Some(by)) => SplitMode::By(by),
+            (None, None) => unreachable!("clap requires --rows or --by"),
+        };
+        let split_opts = SplitOptions {
+            mode,
+            dir: self.output_dir,
+            prefix: self.prefix.unwrap_or_else(|| split_prefix(&self.input)),
+            format: split_format(self.format, from),
+            max_open: self.max_open,
+        };
+        for piece in process_csv_split(&self.input, from, &opts, &split_opts, &writer_opts)? {
+            println!("{}", piece);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,84 @@
+use super::{verify_file, CsvReaderArgs};
+use crate::{process_csv_stats, render_stats, CmdExecutor, CsvReaderOptions, StatsOptions};
+use clap::Parser;
+use std::fmt::Display;
+use std::fs;
+use std::str::FromStr;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StatsFormat {
+    Table,
+    Json,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvStatsOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+    /// write the profile to a file instead of stdout
+    #[arg(short, long)]
+    pub output: Option<String>,
+    #[arg(long, default_value = "table", value_parser = parse_stats_format)]
+    pub format: StatsFormat,
+    /// number of most frequent values shown per column
+    #[arg(long, default_value_t = 5)]
+    pub top: usize,
+    /// estimate distinct and top values in bounded memory
+    #[arg(long)]
+    pub approx: bool,
+    #[command(flatten)]
+    pub reader: CsvReaderArgs,
+}
+
+fn parse_stats_format(format: &str) -> Result<StatsFormat, anyhow::Error> {
+    format.parse()
+}
+
+impl From<StatsFormat> for &'static str {
+    fn from(format: StatsFormat) -> Self {
+        match format {
+            StatsFormat::Table => "table",
+            StatsFormat::Json => "json",
+        }
+    }
+}
+
+impl FromStr for StatsFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "table" => Ok(StatsFormat::Table),
+            "json" => Ok(StatsFormat::Json),
+            _ => Err(anyhow::anyhow!("Invalid format")),
+        }
+    }
+}
+
+impl Display for StatsFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+//
+// impl CmdExecutor
+//
+
+impl CmdExecutor for CsvStatsOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let opts = CsvReaderOptions::from(&self.reader);
+        let from = self.reader.input_format(&self.input);
+        let stats_opts = StatsOptions {
+            top: self.top,
+            approximate: self.approx,
+        };
+        let stats = process_csv_stats(&self.input, from, &opts, &stats_opts)?;
+        let content = render_stats(&stats, self.format)?;
+        match self.output {
+            Some(output) => fs::write(output, content)?,
+            None => print!("{}", content),
+        }
+        Ok(())
+    }
+}
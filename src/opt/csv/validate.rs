@@ -0,0 +1,54 @@
+use super::{verify_file, CsvReaderArgs};
+use crate::{process_csv_validate, CmdExecutor, CsvReaderOptions, Schema};
+use clap::Parser;
+
+#[derive(Debug, Parser)]
+pub struct CsvValidateOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+    /// schema file, JSON if it ends in .json and YAML otherwise
+    #[arg(short, long, value_parser = verify_file)]
+    pub schema: String,
+    #[command(flatten)]
+    pub reader: CsvReaderArgs,
+}
+
+//
+// impl CmdExecutor
+//
+
+impl CmdExecutor for CsvValidateOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let opts = CsvReaderOptions::from(&self.reader);
+        let from = self.reader.input_format(&self.input);
+        let schema = Schema::load(&self.schema)?;
+        let errors = process_csv_validate(&self.input, from, &opts, &schema)?;
+        if errors.is_empty() {
+            println!("{} is valid", self.input);
+            return Ok(());
+        }
+        for error in &errors {
+            println!("{}", error);
+        }
+        Err(anyhow::anyhow!(
+            "{} validation error(s) in {}",
+            errors.len(),
+            self.input
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CsvOpts, CsvSubCommand};
+
+    #[test]
+    fn test_validate_reads_stdin_by_default() {
+        let opts = CsvOpts::try_parse_from(["csv", "validate", "--schema", "Cargo.toml"]).unwrap();
+        assert!(matches!(
+            opts.cmd,
+            Some(CsvSubCommand::Validate(CsvValidateOpts { ref input, .. })) if input == "-"
+        ));
+    }
+}
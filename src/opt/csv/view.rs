@@ -0,0 +1,68 @@
+use super::{verify_file, CsvReaderArgs, CsvTransformArgs};
+use crate::{
+    process_csv_view, run_pager, CmdExecutor, CsvReaderOptions, Pager, RowRange, Transform,
+};
+use clap::Parser;
+use std::io::IsTerminal;
+
+#[derive(Debug, Parser)]
+pub struct CsvViewOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+    /// records to show, 1-based and inclusive, e.g. "10..20", "10.." or "..20"
+    #[arg(long, default_value = "1..", value_parser = parse_row_range)]
+    pub rows: RowRange,
+    /// longest a column gets before its cells are cut off with "…"
+    #[arg(long, default_value_t = 30)]
+    pub max_width: usize,
+    /// browse the table: arrows scroll, s sorts by the leftmost column, / searches, q quits
+    #[arg(short, long)]
+    pub pager: bool,
+    #[command(flatten)]
+    pub reader: CsvReaderArgs,
+    #[command(flatten)]
+    pub transform: CsvTransformArgs,
+}
+
+fn parse_row_range(range: &str) -> Result<RowRange, anyhow::Error> {
+    range.parse()
+}
+
+//
+// impl CmdExecutor
+//
+
+impl CmdExecutor for CsvViewOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let opts = CsvReaderOptions::from(&self.reader);
+        let from = self.reader.input_format(&self.input);
+        let transform = Transform::from(&self.transform);
+        let table = process_csv_view(&self.input, from, &opts, &transform, self.rows)?;
+        if self.pager && std::io::stdout().is_terminal() {
+            run_pager(&mut Pager::new(table, self.max_width))
+        } else {
+            print!("{}", table.render(self.max_width));
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CsvOpts, CsvSubCommand};
+
+    #[test]
+    fn test_view_args() {
+        let opts =
+            CsvOpts::try_parse_from(["csv", "view", "--rows", "10..20", "--head", "5"]).unwrap();
+        assert!(matches!(
+            opts.cmd,
+            Some(CsvSubCommand::View(CsvViewOpts {
+                ref input,
+                ref transform,
+                ..
+            })) if input == "-" && transform.head == Some(5)
+        ));
+    }
+}
@@ -19,9 +19,9 @@ pub struct GenPassOpts {
     pub symbol: bool,
 }
 
-///
-/// impl CmdExecutor
-///
+//
+// impl CmdExecutor
+//
 
 impl CmdExecutor for GenPassOpts {
     async fn execute(self) -> anyhow::Result<()> {
@@ -20,9 +20,9 @@ pub struct HttpServeOpts {
     pub port: u16,
 }
 
-///
-/// impl CmdExecutor
-///
+//
+// impl CmdExecutor
+//
 
 impl CmdExecutor for HttpServeOpts {
     async fn execute(self) -> anyhow::Result<()> {
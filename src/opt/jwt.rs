@@ -37,9 +37,9 @@ pub struct JwtVerifyOpts {
     pub key: String,
 }
 
-///
-/// impl CmdExecutor
-///
+//
+// impl CmdExecutor
+//
 
 impl CmdExecutor for JwtSignOpts {
     async fn execute(self) -> anyhow::Result<()> {
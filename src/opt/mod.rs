@@ -18,10 +18,12 @@ pub struct Opts {
     pub cmd: SubCommand,
 }
 
+// parsed once at startup, so boxing the larger variants buys nothing
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Parser)]
 #[enum_dispatch(CmdExecutor)]
 pub enum SubCommand {
-    #[command(name = "csv", about = "Convert CSV to other formats")]
+    #[command(name = "csv", about = "Convert, query and combine CSV files")]
     Csv(CsvOpts),
 
     #[command(name = "genpass", about = "Generate a password")]
@@ -119,9 +119,9 @@ impl fmt::Display for TextSignFormat {
     }
 }
 
-///
-/// impl CmdExecutor
-///
+//
+// impl CmdExecutor
+//
 
 impl CmdExecutor for TextSignOpts {
     async fn execute(self) -> anyhow::Result<()> {
@@ -0,0 +1,390 @@
+use crate::opt::{InputFormat, OutputFormat};
+use crate::process::csv_convert::open_input;
+use crate::process::csv_dates::DateValue;
+use crate::process::csv_generate::{Generator, ValueGenerator};
+use crate::process::csv_reader::{ColumnType, CsvReaderOptions, RecordStream};
+use crate::process::csv_writer::{write_records, CsvWriterOptions};
+use crate::process::text::{Blake3, KeyLoader};
+use anyhow::{anyhow, Result};
+use chrono::Datelike;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+/// how often a fake value is redrawn when it is already taken by another input value
+const FAKE_ATTEMPTS: usize = 8;
+
+/// `COLUMN=RULE`, e.g. `Name=fake:name` or `DOB=year`
+#[derive(Debug, Clone)]
+pub struct ColumnRule {
+    pub column: String,
+    pub rule: Rule,
+}
+
+#[derive(Debug, Clone)]
+pub enum Rule {
+    /// hex of the keyed BLAKE3 hash, 32 characters
+    Hash,
+    /// a fake value from a generator of `csv generate`: name, first_name, last_name, uuid or
+    /// regex:PATTERN
+    Fake(Generator),
+    /// the first `n` characters
+    Truncate(usize),
+    /// the fixed text `REDACTED`
+    Redact,
+    /// the year of a date
+    Year,
+}
+
+/// a rule ready to apply, with its fake values remembered
+enum Masker {
+    Hash,
+    Fake {
+        generator: ValueGenerator,
+        /// input value to fake value, so repeated values map to the same fake
+        mapping: HashMap<String, Value>,
+        taken: HashSet<String>,
+    },
+    Truncate(usize),
+    Redact,
+    Year,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn process_csv_anonymize(
+    input: &str,
+    from: InputFormat,
+    opts: &CsvReaderOptions,
+    rules: &[ColumnRule],
+    key: Option<&str>,
+    output: &str,
+    format: OutputFormat,
+    writer_opts: &CsvWriterOptions,
+) -> Result<()> {
+    let key = match key {
+        Some(path) => Blake3::load(path)?,
+        None => Blake3::new(rand::random()),
+    };
+    let stream = anonymize(open_input(input, from, opts)?, rules, key)?;
+    write_records(stream, format, output, writer_opts)?;
+    Ok(())
+}
+
+/// mask the columns named by `rules`, streaming
+///
+/// Hashes and fake values are derived from the keyed hash of the value, so the same value
+/// always maps to the same output within a run, and across runs with the same key. Fake values
+/// are also kept unique per column while the generator allows it, which takes memory for every
+/// distinct value of those columns. Empty values stay empty.
+pub fn anonymize(stream: RecordStream, rules: &[ColumnRule], key: Blake3) -> Result<RecordStream> {
+    let RecordStream { mut columns, rows } = stream;
+    let mut maskers: Vec<(String, Masker)> = Vec::new();
+    for ColumnRule { column, rule } in rules {
+        let target = columns
+            .iter_mut()
+            .find(|c| &c.name == column)
+            .ok_or_else(|| anyhow!("unknown column {:?}", column))?;
+        if maskers.iter().any(|(name, _)| name == column) {
+            return Err(anyhow!("column {:?} has more than one rule", column));
+        }
+        let masker = Masker::new(rule).map_err(|e| anyhow!("column {:?}: {}", column, e))?;
+        target.ty = masker.ty(target.ty);
+        maskers.push((column.clone(), masker));
+    }
+
+    let rows = rows.map(move |record| {
+        let mut record = record?;
+        for (column, masker) in maskers.iter_mut() {
+            if let Some(value) = record.get_mut(column.as_str()) {
+                *value = masker
+                    .mask(value, &key)
+                    .map_err(|e| anyhow!("column {:?}: {}", column, e))?;
+            }
+        }
+        Ok(record)
+    });
+    Ok(RecordStream {
+        columns,
+        rows: Box::new(rows),
+    })
+}
+
+impl Masker {
+    fn new(rule: &Rule) -> Result<Self> {
+        let masker = match rule {
+            Rule::Hash => Masker::Hash,
+            Rule::Fake(generator) => Masker::Fake {
+                generator: ValueGenerator::new(generator)?,
+                mapping: HashMap::new(),
+                taken: HashSet::new(),
+            },
+            Rule::Truncate(n) => Masker::Truncate(*n),
+            Rule::Redact => Masker::Redact,
+            Rule::Year => Masker::Year,
+        };
+        Ok(masker)
+    }
+
+    fn ty(&self, input: ColumnType) -> ColumnType {
+        match self {
+            Masker::Hash | Masker::Truncate(_) | Masker::Redact => ColumnType::String,
+            Masker::Fake { generator, .. } => generator.ty(),
+            Masker::Year if input == ColumnType::Null => ColumnType::Null,
+            Masker::Year => ColumnType::Integer,
+        }
+    }
+
+    fn mask(&mut self, value: &Value, key: &Blake3) -> Result<Value> {
+        let text = match value {
+            Value::Null => return Ok(Value::Null),
+            Value::String(s) if s.is_empty() => return Ok(Value::Null),
+            Value::String(s) => s.clone(),
+            v => v.to_string(),
+        };
+        let masked = match self {
+            Masker::Hash => Value::String(key.hash(text.as_bytes()).to_hex()[..32].to_string()),
+            Masker::Fake {
+                generator,
+                mapping,
+                taken,
+            } => {
+                if let Some(fake) = mapping.get(&text) {
+                    return Ok(fake.clone());
+                }
+                let mut fake = Value::Null;
+                for attempt in 0..FAKE_ATTEMPTS {
+                    let mut data = text.clone().into_bytes();
+                    data.extend(attempt.to_le_bytes());
+                    let mut rng = StdRng::from_seed(*key.hash(&data).as_bytes());
+                    fake = generator.value(&mut rng, 0);
+                    if taken.insert(fake.to_string()) {
+                        break;
+                    }
+                }
+                mapping.insert(text, fake.clone());
+                fake
+            }
+            Masker::Truncate(n) => Value::String(text.chars().take(*n).collect()),
+            Masker::Redact => Value::String("REDACTED".to_string()),
+            Masker::Year => {
+                let date = DateValue::parse(&text, None)
+                    .ok_or_else(|| anyhow!("{:?} isn't a date in a known format", text))?;
+                Value::from(date.date().year())
+            }
+        };
+        Ok(masked)
+    }
+}
+
+impl FromStr for ColumnRule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (column, rule) = s
+            .split_once('=')
+            .filter(|(column, _)| !column.is_empty())
+            .ok_or_else(|| anyhow!("expected COLUMN=RULE"))?;
+        Ok(Self {
+            column: column.to_string(),
+            rule: rule.parse()?,
+        })
+    }
+}
+
+impl FromStr for Rule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, arg) = match s.split_once(':') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (s, None),
+        };
+        match (name, arg) {
+            ("hash", None) => Ok(Rule::Hash),
+            ("redact", None) => Ok(Rule::Redact),
+            ("year", None) => Ok(Rule::Year),
+            ("truncate", n) => n
+                .and_then(|n| n.parse().ok())
+                .map(Rule::Truncate)
+                .ok_or_else(|| anyhow!("truncate needs a length, e.g. truncate:3")),
+            ("fake", Some("name")) => Ok(Rule::Fake(Generator::Name)),
+            ("fake", Some("first_name")) => Ok(Rule::Fake(Generator::FirstName)),
+            ("fake", Some("last_name")) => Ok(Rule::Fake(Generator::LastName)),
+            ("fake", Some("uuid")) => Ok(Rule::Fake(Generator::Uuid)),
+            ("fake", Some(arg)) if arg.starts_with("regex:") => Ok(Rule::Fake(Generator::Regex {
+                pattern: arg["regex:".len()..].to_string(),
+            })),
+            ("fake", _) => Err(anyhow!(
+                "expected fake:name, fake:first_name, fake:last_name, fake:uuid or fake:regex:PATTERN"
+            )),
+            _ => Err(anyhow!(
+                "unknown rule {:?}, expected hash, fake:KIND, truncate:N, redact or year",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rule::Hash => write!(f, "hash"),
+            Rule::Fake(Generator::Regex { pattern }) => write!(f, "fake:regex:{}", pattern),
+            Rule::Fake(Generator::FirstName) => write!(f, "fake:first_name"),
+            Rule::Fake(Generator::LastName) => write!(f, "fake:last_name"),
+            Rule::Fake(Generator::Uuid) => write!(f, "fake:uuid"),
+            Rule::Fake(_) => write!(f, "fake:name"),
+            Rule::Truncate(n) => write!(f, "truncate:{}", n),
+            Rule::Redact => write!(f, "redact"),
+            Rule::Year => write!(f, "year"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::csv_reader::{open_csv, Record};
+    use crate::utils::write_temp;
+    use serde_json::json;
+
+    fn key() -> Result<Blake3> {
+        Blake3::load("fixtures/blake3.txt")
+    }
+
+    fn rules(rules: &[&str]) -> Result<Vec<ColumnRule>> {
+        rules.iter().map(|r| r.parse()).collect()
+    }
+
+    fn run(name: &str, content: &str, rules: &[ColumnRule]) -> Result<(RecordStream, Vec<Record>)> {
+        let path = write_temp(name, content)?;
+        let stream = open_csv(&path, &CsvReaderOptions::default())?;
+        let mut stream = anonymize(stream, rules, key()?)?;
+        let rows = std::mem::replace(&mut stream.rows, Box::new(std::iter::empty()));
+        Ok((stream, rows.collect::<Result<_>>()?))
+    }
+
+    const PEOPLE: &str = "id,name,email,phone,dob,team\n\
+        1,Alice Smith,alice@example.com,555-0101,1990-04-18,a\n\
+        2,Bob Jones,bob@example.com,555-0102,1985-11-02,b\n\
+        3,Alice Smith,alice@example.com,,1990-04-18,a\n";
+
+    #[test]
+    fn test_anonymize() -> Result<()> {
+        let rules = rules(&[
+            "name=fake:name",
+            "email=hash",
+            "phone=truncate:3",
+            "dob=year",
+            "team=redact",
+        ])?;
+        let (stream, rows) = run("cli_anonymize.csv", PEOPLE, &rules)?;
+        let types: Vec<ColumnType> = stream.columns.iter().map(|c| c.ty).collect();
+        assert_eq!(
+            types,
+            vec![
+                ColumnType::Integer,
+                ColumnType::String,
+                ColumnType::String,
+                ColumnType::String,
+                ColumnType::Integer,
+                ColumnType::String,
+            ]
+        );
+
+        assert_eq!(rows[0]["id"], 1);
+        assert_ne!(rows[0]["name"], "Alice Smith");
+        assert_ne!(rows[0]["name"], rows[1]["name"]);
+        let hash = rows[0]["email"].as_str().unwrap();
+        assert_eq!(hash.len(), 32);
+        assert!(hash.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(rows[0]["phone"], "555");
+        assert_eq!(rows[2]["phone"], Value::Null);
+        assert_eq!(rows[0]["dob"], 1990);
+        assert_eq!(rows[1]["dob"], 1985);
+        assert_eq!(rows[0]["team"], "REDACTED");
+        // a repeated value maps to the same output, so joins still line up
+        assert_eq!(rows[2]["name"], rows[0]["name"]);
+        assert_eq!(rows[2]["email"], rows[0]["email"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_same_key_same_output() -> Result<()> {
+        let rules = rules(&["name=fake:regex:[A-Z]{2}[0-9]{4}", "email=hash"])?;
+        let (_, first) = run("cli_anonymize_a.csv", PEOPLE, &rules)?;
+        let (_, second) = run("cli_anonymize_b.csv", PEOPLE, &rules)?;
+        assert_eq!(first, second);
+        let id = first[0]["name"].as_str().unwrap();
+        assert!(
+            regex::Regex::new("^[A-Z]{2}[0-9]{4}$")?.is_match(id),
+            "{}",
+            id
+        );
+
+        let expected = key()?.hash(b"alice@example.com").to_hex()[..32].to_string();
+        assert_eq!(first[0]["email"], json!(expected));
+        Ok(())
+    }
+
+    #[test]
+    fn test_fake_values_stay_distinct() -> Result<()> {
+        let content: String = std::iter::once("name\n".to_string())
+            .chain((0..200).map(|i| format!("player {}\n", i)))
+            .collect();
+        let (_, rows) = run(
+            "cli_anonymize_many.csv",
+            &content,
+            &rules(&["name=fake:name"])?,
+        )?;
+        let distinct: HashSet<String> = rows.iter().map(|r| r["name"].to_string()).collect();
+        // 900 possible names, so a few collisions survive the redraws at most
+        assert!(distinct.len() >= 195, "{}", distinct.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rule_parsing() -> Result<()> {
+        for rule in [
+            "hash",
+            "fake:first_name",
+            "fake:regex:\\d{3}",
+            "truncate:3",
+            "year",
+        ] {
+            assert_eq!(rule.parse::<Rule>()?.to_string(), rule);
+        }
+        let err = |s: &str| s.parse::<ColumnRule>().unwrap_err().to_string();
+        assert_eq!(err("name"), "expected COLUMN=RULE");
+        assert_eq!(
+            err("name=truncate"),
+            "truncate needs a length, e.g. truncate:3"
+        );
+        assert!(err("name=fake:email").starts_with("expected fake:name"));
+        assert!(err("name=mask").starts_with("unknown rule \"mask\""));
+        Ok(())
+    }
+
+    #[test]
+    fn test_anonymize_errors() -> Result<()> {
+        let err = |rules: &[ColumnRule]| -> String {
+            match run("cli_anonymize_err.csv", PEOPLE, rules) {
+                Ok(_) => String::new(),
+                Err(e) => e.to_string(),
+            }
+        };
+        assert_eq!(err(&rules(&["age=hash"])?), "unknown column \"age\"");
+        assert_eq!(
+            err(&rules(&["name=hash", "name=redact"])?),
+            "column \"name\" has more than one rule"
+        );
+        assert_eq!(
+            err(&rules(&["name=year"])?),
+            "column \"name\": \"Alice Smith\" isn't a date in a known format"
+        );
+        Ok(())
+    }
+}
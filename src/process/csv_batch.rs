@@ -0,0 +1,290 @@
+use crate::opt::{InputFormat, OutputFormat};
+use crate::process::csv_convert::open_input;
+use crate::process::csv_reader::CsvReaderOptions;
+use crate::process::csv_transform::Transform;
+use crate::process::csv_writer::{write_records, CsvWriterOptions};
+use crate::utils::strip_compression_ext;
+use anyhow::{anyhow, Result};
+use indicatif::{ProgressBar, ProgressStyle};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// one file of a batch conversion
+#[derive(Debug, Clone)]
+pub struct BatchJob {
+    pub input: String,
+    pub from: InputFormat,
+    pub output: String,
+    pub format: OutputFormat,
+}
+
+#[derive(Debug, Clone)]
+pub struct BatchOptions {
+    /// number of worker threads
+    pub jobs: usize,
+    /// convert every file even after one fails
+    pub keep_going: bool,
+    /// draw a progress bar on stderr
+    pub progress: bool,
+}
+
+/// what happened to one file, `result` holds the record count or the error message
+#[derive(Debug)]
+pub struct BatchOutcome {
+    pub job: BatchJob,
+    pub result: Result<usize, String>,
+    pub elapsed: Duration,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self {
+            jobs: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            keep_going: false,
+            progress: false,
+        }
+    }
+}
+
+impl fmt::Display for BatchOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.result {
+            Ok(count) => write!(
+                f,
+                "ok      {} -> {} ({} records, {:.2?})",
+                self.job.input, self.job.output, count, self.elapsed
+            ),
+            Err(e) => write!(f, "failed  {}: {}", self.job.input, e),
+        }
+    }
+}
+
+/// files matching a glob pattern, in path order
+pub fn expand_glob(pattern: &str) -> Result<Vec<String>> {
+    let mut inputs = Vec::new();
+    for entry in glob::glob(pattern)? {
+        let path = entry?;
+        if path.is_file() {
+            inputs.push(path.to_string_lossy().into_owned());
+        }
+    }
+    if inputs.is_empty() {
+        return Err(anyhow!("no files match {}", pattern));
+    }
+    inputs.sort();
+    Ok(inputs)
+}
+
+/// the output of `input` in `dir`: its file name with the extension of `format`
+///
+/// A compression extension is dropped along with the format one, so `a.csv.gz` becomes `a.json`.
+pub fn batch_output(dir: &str, input: &str, format: OutputFormat) -> String {
+    let input = strip_compression_ext(input);
+    let stem = Path::new(input)
+        .file_stem()
+        .map_or_else(|| "output".into(), |stem| stem.to_string_lossy());
+    Path::new(dir)
+        .join(format!("{}.{}", stem, format))
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// convert every job like `process_csv` on a pool of worker threads
+///
+/// Outcomes come back in job order. Unless `keep_going` is set, no new file is started once one
+/// has failed, and skipped files have no outcome.
+pub fn process_csv_batch(
+    jobs: Vec<BatchJob>,
+    opts: &CsvReaderOptions,
+    writer_opts: &CsvWriterOptions,
+    transform: &Transform,
+    batch: &BatchOptions,
+) -> Result<Vec<BatchOutcome>> {
+    let mut seen = HashMap::new();
+    for job in &jobs {
+        if let Some(other) = seen.insert(job.output.as_str(), job.input.as_str()) {
+            return Err(anyhow!(
+                "{} and {} would both be written to {}",
+                other,
+                job.input,
+                job.output
+            ));
+        }
+    }
+
+    let progress = if batch.progress {
+        let bar = ProgressBar::new(jobs.len() as u64);
+        bar.set_style(ProgressStyle::with_template(
+            "{bar:40} {pos}/{len} files, {elapsed} {msg}",
+        )?);
+        bar
+    } else {
+        ProgressBar::hidden()
+    };
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let outcomes = Mutex::new(Vec::with_capacity(jobs.len()));
+    std::thread::scope(|scope| {
+        for _ in 0..batch.jobs.clamp(1, jobs.len().max(1)) {
+            scope.spawn(|| loop {
+                if failed.load(Ordering::SeqCst) && !batch.keep_going {
+                    break;
+                }
+                let i = next.fetch_add(1, Ordering::SeqCst);
+                let Some(job) = jobs.get(i) else {
+                    break;
+                };
+                let start = Instant::now();
+                let result = open_input(&job.input, job.from, opts)
+                    .and_then(|stream| transform.apply(stream))
+                    .and_then(|stream| write_records(stream, job.format, &job.output, writer_opts))
+                    .map_err(|e| e.to_string());
+                if result.is_err() {
+                    failed.store(true, Ordering::SeqCst);
+                }
+                progress.set_message(job.input.clone());
+                progress.inc(1);
+                let outcome = BatchOutcome {
+                    job: job.clone(),
+                    result,
+                    elapsed: start.elapsed(),
+                };
+                outcomes
+                    .lock()
+                    .expect("a worker panicked")
+                    .push((i, outcome));
+            });
+        }
+    });
+    progress.finish_and_clear();
+
+    let mut outcomes = outcomes.into_inner().expect("a worker panicked");
+    outcomes.sort_by_key(|(i, _)| *i);
+    Ok(outcomes.into_iter().map(|(_, outcome)| outcome).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::temp_path;
+    use std::fs;
+
+    fn batch_dir(name: &str) -> Result<String> {
+        let dir = temp_path(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("out"))?;
+        fs::copy("assets/juventus.csv", dir.join("a.csv"))?;
+        fs::write(dir.join("b.csv"), "Name,Kit Number\nalice,7\n")?;
+        // a row longer than the header fails unless flexible
+        fs::write(dir.join("c.csv"), "Name\nalice,7\n")?;
+        Ok(dir.to_string_lossy().into_owned())
+    }
+
+    fn jobs(dir: &str) -> Result<Vec<BatchJob>> {
+        let out = format!("{}/out", dir);
+        Ok(expand_glob(&format!("{}/*.csv", dir))?
+            .into_iter()
+            .map(|input| BatchJob {
+                output: batch_output(&out, &input, OutputFormat::Json),
+                input,
+                from: InputFormat::Csv,
+                format: OutputFormat::Json,
+            })
+            .collect())
+    }
+
+    #[test]
+    fn test_batch_output() {
+        assert_eq!(
+            batch_output("out", "data/players.csv.gz", OutputFormat::Yaml),
+            Path::new("out").join("players.yaml").to_string_lossy()
+        );
+        assert_eq!(
+            batch_output("out", "players", OutputFormat::Csv),
+            Path::new("out").join("players.csv").to_string_lossy()
+        );
+    }
+
+    #[test]
+    fn test_expand_glob() -> Result<()> {
+        let dir = batch_dir("cli_batch_glob")?;
+        let inputs = expand_glob(&format!("{}/*.csv", dir))?;
+        let names: Vec<_> = inputs
+            .iter()
+            .map(|input| Path::new(input).file_name().unwrap().to_string_lossy())
+            .collect();
+        assert_eq!(names, vec!["a.csv", "b.csv", "c.csv"]);
+        assert!(expand_glob(&format!("{}/*.nothing", dir)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_batch_keep_going() -> Result<()> {
+        let dir = batch_dir("cli_batch_keep_going")?;
+        let batch = BatchOptions {
+            jobs: 2,
+            keep_going: true,
+            progress: false,
+        };
+        let outcomes = process_csv_batch(
+            jobs(&dir)?,
+            &CsvReaderOptions::default(),
+            &CsvWriterOptions::default(),
+            &Transform::default(),
+            &batch,
+        )?;
+        let results: Vec<_> = outcomes.iter().map(|o| o.result.clone().ok()).collect();
+        assert_eq!(results, vec![Some(27), Some(1), None]);
+        assert!(outcomes[2].to_string().starts_with("failed  "));
+        assert!(Path::new(&format!("{}/out/a.json", dir)).exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_batch_stops_after_failure() -> Result<()> {
+        let dir = batch_dir("cli_batch_fail_fast")?;
+        let mut jobs = jobs(&dir)?;
+        jobs.rotate_right(1);
+        let batch = BatchOptions {
+            jobs: 1,
+            keep_going: false,
+            progress: false,
+        };
+        let outcomes = process_csv_batch(
+            jobs,
+            &CsvReaderOptions::default(),
+            &CsvWriterOptions::default(),
+            &Transform::default(),
+            &batch,
+        )?;
+        assert_eq!(outcomes.len(), 1);
+        assert!(outcomes[0].result.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_batch_rejects_clashing_outputs() {
+        let job = BatchJob {
+            input: "a/x.csv".into(),
+            from: InputFormat::Csv,
+            output: "out/x.json".into(),
+            format: OutputFormat::Json,
+        };
+        let other = BatchJob {
+            input: "b/x.csv".into(),
+            ..job.clone()
+        };
+        let result = process_csv_batch(
+            vec![job, other],
+            &CsvReaderOptions::default(),
+            &CsvWriterOptions::default(),
+            &Transform::default(),
+            &BatchOptions::default(),
+        );
+        assert!(result.is_err());
+    }
+}
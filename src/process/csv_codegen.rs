@@ -0,0 +1,421 @@
+use crate::opt::{CodegenLang, InputFormat};
+use crate::process::csv_convert::open_input;
+use crate::process::csv_reader::{ColumnType, CsvReaderOptions, RecordStream};
+use anyhow::Result;
+use serde_json::{json, Map, Value};
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::path::Path;
+
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "dyn", "else", "enum", "extern", "false",
+    "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+    "return", "static", "struct", "trait", "true", "type", "unsafe", "use", "where", "while",
+    "abstract", "become", "box", "do", "final", "gen", "macro", "override", "priv", "try",
+    "typeof", "unsized", "virtual", "yield",
+];
+
+/// keywords that can't be raw identifiers either, these get a trailing `_` instead
+const RUST_RESERVED: &[&str] = &["crate", "self", "super", "Self"];
+
+/// a column with the type and nullability seen across the whole input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    pub name: String,
+    pub ty: ColumnType,
+    /// at least one value is empty
+    pub nullable: bool,
+}
+
+pub fn process_csv_codegen(
+    input: &str,
+    from: InputFormat,
+    opts: &CsvReaderOptions,
+    lang: CodegenLang,
+    name: Option<&str>,
+) -> Result<String> {
+    let fields = infer_fields(open_input(input, from, opts)?)?;
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => type_name(input),
+    };
+    codegen(&fields, lang, &name)
+}
+
+/// scan every record, since the reader only infers types from the first rows
+pub fn infer_fields(stream: RecordStream) -> Result<Vec<Field>> {
+    let mut fields: Vec<Field> = stream
+        .columns
+        .iter()
+        .map(|column| Field {
+            name: column.name.clone(),
+            ty: ColumnType::Null,
+            nullable: false,
+        })
+        .collect();
+    for record in stream.rows {
+        let record = record?;
+        for (field, column) in fields.iter_mut().zip(&stream.columns) {
+            let ty = match record.get(&field.name) {
+                None | Some(Value::Null) => ColumnType::Null,
+                // dates are kept as strings, as are values past the sample that didn't fit the
+                // sampled type, so their text decides
+                Some(Value::String(s)) if column.ty != ColumnType::String => ColumnType::of(s),
+                Some(value) => ColumnType::of_value(value),
+            };
+            field.nullable |= ty == ColumnType::Null;
+            field.ty = field.ty.merge(ty);
+        }
+    }
+    Ok(fields)
+}
+
+pub fn codegen(fields: &[Field], lang: CodegenLang, name: &str) -> Result<String> {
+    match lang {
+        CodegenLang::Rust => Ok(render_rust(fields, name)),
+        CodegenLang::Typescript => Ok(render_typescript(fields, name)),
+        CodegenLang::Jsonschema => render_json_schema(fields, name),
+    }
+}
+
+/// a serde struct, renaming every field whose identifier differs from its header
+fn render_rust(fields: &[Field], name: &str) -> String {
+    let mut out = String::new();
+    if fields.iter().any(|f| f.ty == ColumnType::Date) {
+        out.push_str("use chrono::NaiveDate;\n");
+    }
+    out.push_str("use serde::{Deserialize, Serialize};\n\n");
+    let _ = writeln!(
+        out,
+        "#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]"
+    );
+    let _ = writeln!(out, "pub struct {} {{", pascal_case(name));
+    let mut taken = HashSet::new();
+    for field in fields {
+        let ident = unique(rust_ident(&field.name), &mut taken);
+        if ident != field.name {
+            let _ = writeln!(out, "    #[serde(rename = {:?})]", field.name);
+        }
+        let ty = match field.ty {
+            ColumnType::Bool => "bool",
+            ColumnType::Integer => "i64",
+            ColumnType::Float => "f64",
+            ColumnType::Date => "NaiveDate",
+            ColumnType::Null | ColumnType::String => "String",
+        };
+        let ty = match field.nullable || field.ty == ColumnType::Null {
+            true => format!("Option<{}>", ty),
+            false => ty.to_string(),
+        };
+        let ident = match RUST_KEYWORDS.contains(&ident.as_str()) {
+            true => format!("r#{}", ident),
+            false => ident,
+        };
+        let _ = writeln!(out, "    pub {}: {},", ident, ty);
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn render_typescript(fields: &[Field], name: &str) -> String {
+    let mut out = format!("export interface {} {{\n", pascal_case(name));
+    for field in fields {
+        let ty = match field.ty {
+            ColumnType::Bool => "boolean",
+            ColumnType::Integer | ColumnType::Float => "number",
+            ColumnType::Date | ColumnType::Null | ColumnType::String => "string",
+        };
+        let key = match is_js_ident(&field.name) {
+            true => field.name.clone(),
+            false => format!("{:?}", field.name),
+        };
+        let null = match field.nullable || field.ty == ColumnType::Null {
+            true => " | null",
+            false => "",
+        };
+        let _ = writeln!(out, "  {}: {}{};", key, ty, null);
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// a JSON Schema (draft 2020-12) for one record, as written by json output
+fn render_json_schema(fields: &[Field], name: &str) -> Result<String> {
+    let mut properties = Map::new();
+    for field in fields {
+        let ty = match field.ty {
+            ColumnType::Bool => "boolean",
+            ColumnType::Integer => "integer",
+            ColumnType::Float => "number",
+            ColumnType::Date | ColumnType::Null | ColumnType::String => "string",
+        };
+        let mut property = match field.nullable || field.ty == ColumnType::Null {
+            true => json!({ "type": [ty, "null"] }),
+            false => json!({ "type": ty }),
+        };
+        if field.ty == ColumnType::Date {
+            property["format"] = json!("date");
+        }
+        properties.insert(field.name.clone(), property);
+    }
+    let schema = json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": pascal_case(name),
+        "type": "object",
+        "properties": properties,
+        "required": fields.iter().map(|f| f.name.as_str()).collect::<Vec<_>>(),
+        "additionalProperties": false,
+    });
+    Ok(serde_json::to_string_pretty(&schema)? + "\n")
+}
+
+/// `assets/juventus.csv` becomes `Juventus`, stdin `Record`
+fn type_name(input: &str) -> String {
+    let stem = Path::new(input)
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .and_then(|name| name.split('.').next().map(str::to_string))
+        .filter(|_| input != "-")
+        .unwrap_or_default();
+    pascal_case(&stem)
+}
+
+/// split a header into lowercase words at separators and lower-to-upper case changes
+fn words(name: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut prev_lower = false;
+    for c in name.chars() {
+        if !c.is_alphanumeric() {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            prev_lower = false;
+            continue;
+        }
+        if c.is_uppercase() && prev_lower {
+            words.push(std::mem::take(&mut word));
+        }
+        prev_lower = c.is_lowercase() || c.is_numeric();
+        word.extend(c.to_lowercase());
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+fn pascal_case(name: &str) -> String {
+    let name: String = words(name)
+        .iter()
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect();
+    match name.chars().next() {
+        None => "Record".to_string(),
+        Some(c) if c.is_numeric() => format!("Record{}", name),
+        Some(_) => name,
+    }
+}
+
+/// a snake_case field name, escaped with `r#` later if it is a keyword
+fn rust_ident(name: &str) -> String {
+    let ident = words(name).join("_");
+    match ident.chars().next() {
+        None => "field".to_string(),
+        Some(c) if c.is_numeric() => format!("field_{}", ident),
+        Some(_) if RUST_RESERVED.contains(&ident.as_str()) => format!("{}_", ident),
+        Some(_) => ident,
+    }
+}
+
+/// `ident`, or `ident_2`, `ident_3`… if an earlier header already maps to it
+fn unique(ident: String, taken: &mut HashSet<String>) -> String {
+    let mut candidate = ident.clone();
+    let mut n = 2;
+    while !taken.insert(candidate.clone()) {
+        // `self_` becomes `self_2`, a double underscore isn't snake case
+        candidate = format!("{}_{}", ident.trim_end_matches('_'), n);
+        n += 1;
+    }
+    candidate
+}
+
+fn is_js_ident(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::csv_reader::open_csv;
+    use crate::utils::write_temp;
+
+    fn fields(name: &str, content: &str) -> Result<Vec<Field>> {
+        let path = write_temp(name, content)?;
+        let opts = CsvReaderOptions {
+            infer_rows: 2,
+            ..Default::default()
+        };
+        infer_fields(open_csv(&path, &opts)?)
+    }
+
+    fn juventus(lang: CodegenLang) -> Result<String> {
+        let opts = CsvReaderOptions::default();
+        process_csv_codegen("assets/juventus.csv", InputFormat::Csv, &opts, lang, None)
+    }
+
+    #[test]
+    fn test_infer_fields_scans_every_row() -> Result<()> {
+        // the first two rows alone would make score an integer and nothing nullable
+        let fields = fields(
+            "cli_codegen_infer.csv",
+            "id,score,joined,note\n1,7,2019-11-01,a\n2,8,2020-01-31,b\n3,7.5,,\n",
+        )?;
+        let summary: Vec<(&str, ColumnType, bool)> = fields
+            .iter()
+            .map(|f| (f.name.as_str(), f.ty, f.nullable))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("id", ColumnType::Integer, false),
+                ("score", ColumnType::Float, false),
+                ("joined", ColumnType::Date, true),
+                ("note", ColumnType::String, true),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_rust_golden() -> Result<()> {
+        assert_eq!(
+            juventus(CodegenLang::Rust)?,
+            "use serde::{Deserialize, Serialize};\n\
+             \n\
+             #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]\n\
+             pub struct Juventus {\n    \
+             #[serde(rename = \"Name\")]\n    \
+             pub name: String,\n    \
+             #[serde(rename = \"Position\")]\n    \
+             pub position: String,\n    \
+             #[serde(rename = \"DOB\")]\n    \
+             pub dob: String,\n    \
+             #[serde(rename = \"Nationality\")]\n    \
+             pub nationality: String,\n    \
+             #[serde(rename = \"Kit Number\")]\n    \
+             pub kit_number: i64,\n\
+             }\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_typescript_golden() -> Result<()> {
+        assert_eq!(
+            juventus(CodegenLang::Typescript)?,
+            "export interface Juventus {\n  \
+             Name: string;\n  \
+             Position: string;\n  \
+             DOB: string;\n  \
+             Nationality: string;\n  \
+             \"Kit Number\": number;\n\
+             }\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_schema() -> Result<()> {
+        let fields = fields(
+            "cli_codegen_schema.csv",
+            "id,joined,active\n1,2019-11-01,true\n2,,false\n",
+        )?;
+        let schema: Value =
+            serde_json::from_str(&codegen(&fields, CodegenLang::Jsonschema, "members")?)?;
+        assert_eq!(schema["title"], "Members");
+        assert_eq!(
+            schema["properties"],
+            json!({
+                "id": {"type": "integer"},
+                "joined": {"type": ["string", "null"], "format": "date"},
+                "active": {"type": "boolean"},
+            })
+        );
+        assert_eq!(schema["required"], json!(["id", "joined", "active"]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_nullable_and_dates_in_rust() -> Result<()> {
+        let fields = fields(
+            "cli_codegen_nullable.csv",
+            "type,joined,2nd place,Type\n,2019-11-01,x,1\na,,y,2\n",
+        )?;
+        let code = codegen(&fields, CodegenLang::Rust, "-")?;
+        assert!(code.starts_with("use chrono::NaiveDate;\n"));
+        assert!(code.contains("pub struct Record {\n"));
+        assert!(code.contains("    pub r#type: Option<String>,\n"));
+        assert!(code.contains("    pub joined: Option<NaiveDate>,\n"));
+        assert!(code.contains("#[serde(rename = \"2nd place\")]\n    pub field_2nd_place: String,"));
+        assert!(code.contains("#[serde(rename = \"Type\")]\n    pub type_2: i64,"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_reserved_rust_names() -> Result<()> {
+        let fields: Vec<Field> = ["crate", "self", "super", "Self", "type"]
+            .iter()
+            .map(|name| Field {
+                name: name.to_string(),
+                ty: ColumnType::String,
+                nullable: false,
+            })
+            .collect();
+        // none of these can be written as `r#crate` and so on
+        assert_eq!(
+            codegen(&fields, CodegenLang::Rust, "Row")?,
+            "use serde::{Deserialize, Serialize};\n\
+             \n\
+             #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]\n\
+             pub struct Row {\n    \
+             #[serde(rename = \"crate\")]\n    \
+             pub crate_: String,\n    \
+             #[serde(rename = \"self\")]\n    \
+             pub self_: String,\n    \
+             #[serde(rename = \"super\")]\n    \
+             pub super_: String,\n    \
+             #[serde(rename = \"Self\")]\n    \
+             pub self_2: String,\n    \
+             pub r#type: String,\n\
+             }\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_identifiers() {
+        assert_eq!(rust_ident("Kit Number"), "kit_number");
+        assert_eq!(rust_ident("firstName"), "first_name");
+        assert_eq!(rust_ident("DOB"), "dob");
+        assert_eq!(rust_ident("address.city"), "address_city");
+        assert_eq!(rust_ident("%"), "field");
+        assert_eq!(rust_ident("crate"), "crate_");
+        assert_eq!(rust_ident("Self"), "self_");
+        assert_eq!(pascal_case("player_stats"), "PlayerStats");
+        assert_eq!(type_name("assets/juventus.csv.gz"), "Juventus");
+        assert_eq!(type_name("-"), "Record");
+        assert!(is_js_ident("$id"));
+        assert!(!is_js_ident("Kit Number"));
+    }
+}
@@ -0,0 +1,425 @@
+use crate::opt::Compression;
+use crate::process::csv_reader::{Column, ColumnType, Record};
+use crate::process::csv_writer::RecordWriter;
+use crate::utils::get_plain_writer;
+use anyhow::{anyhow, Result};
+use arrow_array::builder::{
+    BooleanBuilder, Date32Builder, Float64Builder, Int64Builder, StringBuilder,
+};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_ipc::writer::{FileWriter, IpcWriteOptions};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use chrono::NaiveDate;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{GzipLevel, ZstdLevel};
+use parquet::file::properties::WriterProperties;
+use serde_json::Value;
+use std::ffi::OsString;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// rows buffered before they are flushed as one record batch
+const BATCH_SIZE: usize = 8192;
+
+pub struct ParquetWriter {
+    writer: ArrowWriter<Box<dyn Write + Send>>,
+    batch: BatchBuilder,
+    output: StagedOutput,
+}
+
+pub struct ArrowIpcWriter {
+    writer: FileWriter<Box<dyn Write + Send>>,
+    batch: BatchBuilder,
+    output: StagedOutput,
+}
+
+/// a file written under a temporary name and only moved into place once it's complete, so a
+/// value that doesn't fit the schema halfway through doesn't leave a truncated file behind
+struct StagedOutput {
+    path: PathBuf,
+    temp: Option<PathBuf>,
+}
+
+/// collects records into typed arrow columns, using the types inferred by the reader
+struct BatchBuilder {
+    schema: SchemaRef,
+    columns: Vec<Column>,
+    builders: Vec<ColumnBuilder>,
+    rows: usize,
+}
+
+enum ColumnBuilder {
+    Integer(Int64Builder),
+    Float(Float64Builder),
+    Bool(BooleanBuilder),
+    Date(Date32Builder),
+    String(StringBuilder),
+}
+
+impl ParquetWriter {
+    pub fn try_new(output: &str, columns: &[Column], compression: Compression) -> Result<Self> {
+        let batch = BatchBuilder::new(columns);
+        let compression = match compression {
+            Compression::None => parquet::basic::Compression::UNCOMPRESSED,
+            Compression::Snappy => parquet::basic::Compression::SNAPPY,
+            Compression::Gzip => parquet::basic::Compression::GZIP(GzipLevel::default()),
+            Compression::Zstd => parquet::basic::Compression::ZSTD(ZstdLevel::default()),
+            Compression::Lz4 => parquet::basic::Compression::LZ4_RAW,
+        };
+        let props = WriterProperties::builder()
+            .set_compression(compression)
+            .build();
+        let (output, file) = StagedOutput::create(output)?;
+        let writer = ArrowWriter::try_new(file, batch.schema.clone(), Some(props))?;
+        Ok(Self {
+            writer,
+            batch,
+            output,
+        })
+    }
+}
+
+impl RecordWriter for ParquetWriter {
+    fn write_record(&mut self, record: &Record) -> Result<()> {
+        self.batch.append(record)?;
+        if self.batch.rows >= BATCH_SIZE {
+            self.writer.write(&self.batch.flush()?)?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        if self.batch.rows > 0 {
+            self.writer.write(&self.batch.flush()?)?;
+        }
+        self.writer.finish()?;
+        self.writer.inner_mut().flush()?;
+        self.output.commit()
+    }
+}
+
+impl ArrowIpcWriter {
+    pub fn try_new(output: &str, columns: &[Column], compression: Compression) -> Result<Self> {
+        let batch = BatchBuilder::new(columns);
+        let compression = match compression {
+            Compression::None => None,
+            Compression::Lz4 => Some(arrow_ipc::CompressionType::LZ4_FRAME),
+            Compression::Zstd => Some(arrow_ipc::CompressionType::ZSTD),
+            Compression::Snappy | Compression::Gzip => {
+                return Err(anyhow!(
+                    "arrow only supports lz4 and zstd compression, not {}",
+                    compression
+                ))
+            }
+        };
+        let opts = IpcWriteOptions::default().try_with_compression(compression)?;
+        let (output, file) = StagedOutput::create(output)?;
+        let writer = FileWriter::try_new_with_options(file, &batch.schema, opts)?;
+        Ok(Self {
+            writer,
+            batch,
+            output,
+        })
+    }
+}
+
+impl RecordWriter for ArrowIpcWriter {
+    fn write_record(&mut self, record: &Record) -> Result<()> {
+        self.batch.append(record)?;
+        if self.batch.rows >= BATCH_SIZE {
+            self.writer.write(&self.batch.flush()?)?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        if self.batch.rows > 0 {
+            self.writer.write(&self.batch.flush()?)?;
+        }
+        self.writer.finish()?;
+        self.output.commit()
+    }
+}
+
+impl StagedOutput {
+    fn create(output: &str) -> Result<(Self, Box<dyn Write + Send>)> {
+        let path = PathBuf::from(output);
+        if output == "-" {
+            return Ok((Self { path, temp: None }, get_plain_writer(output)?));
+        }
+        let name = path
+            .file_name()
+            .ok_or_else(|| anyhow!("invalid output path {:?}", output))?;
+        let mut temp_name = OsString::from(".");
+        temp_name.push(name);
+        temp_name.push(".part");
+        let temp = path.with_file_name(temp_name);
+        let file = get_plain_writer(&temp.to_string_lossy())?;
+        let staged = Self {
+            path,
+            temp: Some(temp),
+        };
+        Ok((staged, file))
+    }
+
+    fn commit(&mut self) -> Result<()> {
+        if let Some(temp) = self.temp.take() {
+            fs::rename(&temp, &self.path)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for StagedOutput {
+    fn drop(&mut self) {
+        if let Some(temp) = &self.temp {
+            let _ = fs::remove_file(temp);
+        }
+    }
+}
+
+impl BatchBuilder {
+    fn new(columns: &[Column]) -> Self {
+        let fields: Vec<_> = columns
+            .iter()
+            .map(|c| Field::new(&c.name, data_type(c.ty), true))
+            .collect();
+        Self {
+            schema: Arc::new(Schema::new(fields)),
+            columns: columns.to_vec(),
+            builders: columns.iter().map(|c| ColumnBuilder::new(c.ty)).collect(),
+            rows: 0,
+        }
+    }
+
+    fn append(&mut self, record: &Record) -> Result<()> {
+        for (column, builder) in self.columns.iter().zip(self.builders.iter_mut()) {
+            let value = record.get(&column.name).unwrap_or(&Value::Null);
+            if !builder.append(value) {
+                return Err(anyhow!(
+                    "value {} in column {:?} doesn't match the inferred type {:?}, try a larger --infer-rows",
+                    value,
+                    column.name,
+                    column.ty
+                ));
+            }
+        }
+        self.rows += 1;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<RecordBatch> {
+        let arrays: Vec<ArrayRef> = self.builders.iter_mut().map(|b| b.finish()).collect();
+        self.rows = 0;
+        Ok(RecordBatch::try_new(self.schema.clone(), arrays)?)
+    }
+}
+
+fn data_type(ty: ColumnType) -> DataType {
+    match ty {
+        ColumnType::Integer => DataType::Int64,
+        ColumnType::Float => DataType::Float64,
+        ColumnType::Bool => DataType::Boolean,
+        ColumnType::Date => DataType::Date32,
+        ColumnType::Null | ColumnType::String => DataType::Utf8,
+    }
+}
+
+impl ColumnBuilder {
+    fn new(ty: ColumnType) -> Self {
+        match ty {
+            ColumnType::Integer => ColumnBuilder::Integer(Int64Builder::new()),
+            ColumnType::Float => ColumnBuilder::Float(Float64Builder::new()),
+            ColumnType::Bool => ColumnBuilder::Bool(BooleanBuilder::new()),
+            ColumnType::Date => ColumnBuilder::Date(Date32Builder::new()),
+            ColumnType::Null | ColumnType::String => ColumnBuilder::String(StringBuilder::new()),
+        }
+    }
+
+    /// returns false if the value can't be stored in the column
+    fn append(&mut self, value: &Value) -> bool {
+        match (self, value) {
+            (ColumnBuilder::Integer(b), Value::Null) => b.append_null(),
+            (ColumnBuilder::Float(b), Value::Null) => b.append_null(),
+            (ColumnBuilder::Bool(b), Value::Null) => b.append_null(),
+            (ColumnBuilder::Date(b), Value::Null) => b.append_null(),
+            (ColumnBuilder::String(b), Value::Null) => b.append_null(),
+            (ColumnBuilder::Integer(b), Value::Number(n)) if n.is_i64() => {
+                b.append_value(n.as_i64().unwrap_or_default())
+            }
+            (ColumnBuilder::Float(b), Value::Number(n)) => {
+                b.append_value(n.as_f64().unwrap_or_default())
+            }
+            (ColumnBuilder::Bool(b), Value::Bool(v)) => b.append_value(*v),
+            (ColumnBuilder::Date(b), Value::String(s)) => match days_since_epoch(s) {
+                Some(days) => b.append_value(days),
+                None => return false,
+            },
+            (ColumnBuilder::String(b), Value::String(s)) => b.append_value(s),
+            (ColumnBuilder::String(b), v) => b.append_value(v.to_string()),
+            _ => return false,
+        }
+        true
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            ColumnBuilder::Integer(b) => Arc::new(b.finish()),
+            ColumnBuilder::Float(b) => Arc::new(b.finish()),
+            ColumnBuilder::Bool(b) => Arc::new(b.finish()),
+            ColumnBuilder::Date(b) => Arc::new(b.finish()),
+            ColumnBuilder::String(b) => Arc::new(b.finish()),
+        }
+    }
+}
+
+fn days_since_epoch(value: &str) -> Option<i32> {
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+    let days = date.signed_duration_since(NaiveDate::default()).num_days();
+    i32::try_from(days).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::csv_reader::{open_csv, CsvReaderOptions};
+    use crate::utils::temp_path;
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::Int64Type;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use std::fs::File;
+
+    fn write_juventus(writer: Box<dyn RecordWriter>) -> Result<()> {
+        let stream = open_csv("assets/juventus.csv", &CsvReaderOptions::default())?;
+        let mut writer = writer;
+        for record in stream.rows {
+            writer.write_record(&record?)?;
+        }
+        writer.finish()
+    }
+
+    fn juventus_columns() -> Result<Vec<Column>> {
+        Ok(open_csv("assets/juventus.csv", &CsvReaderOptions::default())?.columns)
+    }
+
+    fn check_batches(batches: &[RecordBatch]) {
+        let rows: usize = batches.iter().map(|b| b.num_rows()).sum();
+        assert_eq!(rows, 27);
+        let schema = batches[0].schema();
+        assert_eq!(schema.field(0).name(), "Name");
+        assert_eq!(schema.field(0).data_type(), &DataType::Utf8);
+        assert_eq!(schema.field(4).data_type(), &DataType::Int64);
+        assert!(schema.field(4).is_nullable());
+        let kits = batches[0].column(4).as_primitive::<Int64Type>();
+        assert_eq!(kits.value(0), 1);
+        assert_eq!(
+            batches[0].column(0).as_string::<i32>().value(1),
+            "Mattia Perin"
+        );
+    }
+
+    #[test]
+    fn test_parquet_round_trip() -> Result<()> {
+        let output = temp_path("cli_round_trip.parquet");
+        let output = output.to_string_lossy();
+        let writer = ParquetWriter::try_new(&output, &juventus_columns()?, Compression::Zstd)?;
+        write_juventus(Box::new(writer))?;
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(output.as_ref())?)?;
+        let batches = reader.build()?.collect::<Result<Vec<_>, _>>()?;
+        check_batches(&batches);
+        Ok(())
+    }
+
+    #[test]
+    fn test_arrow_round_trip() -> Result<()> {
+        let output = temp_path("cli_round_trip.arrow");
+        let output = output.to_string_lossy();
+        let writer = ArrowIpcWriter::try_new(&output, &juventus_columns()?, Compression::Lz4)?;
+        write_juventus(Box::new(writer))?;
+
+        let reader = arrow_ipc::reader::FileReader::try_new(File::open(output.as_ref())?, None)?;
+        let batches = reader.collect::<Result<Vec<_>, _>>()?;
+        check_batches(&batches);
+        Ok(())
+    }
+
+    #[test]
+    fn test_nulls_and_mismatches() -> Result<()> {
+        let columns = vec![Column {
+            name: "n".to_string(),
+            ty: ColumnType::Integer,
+        }];
+        let mut batch = BatchBuilder::new(&columns);
+        let Value::Object(record) = serde_json::json!({ "n": null }) else {
+            unreachable!()
+        };
+        batch.append(&record)?;
+        batch.append(&Record::new())?;
+        assert_eq!(batch.flush()?.column(0).null_count(), 2);
+
+        let Value::Object(record) = serde_json::json!({ "n": "abc" }) else {
+            unreachable!()
+        };
+        assert!(batch.append(&record).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_mismatch_leaves_no_file() -> Result<()> {
+        let output = temp_path("cli_mismatch.parquet");
+        let output = output.to_string_lossy();
+        std::fs::write(output.as_ref(), "old")?;
+        let columns = vec![Column {
+            name: "n".to_string(),
+            ty: ColumnType::Integer,
+        }];
+        let mut writer = Box::new(ParquetWriter::try_new(
+            &output,
+            &columns,
+            Compression::None,
+        )?);
+        let Value::Object(record) = serde_json::json!({ "n": "z" }) else {
+            unreachable!()
+        };
+        assert!(writer.write_record(&record).is_err());
+        drop(writer);
+        assert_eq!(std::fs::read_to_string(output.as_ref())?, "old");
+        let temp = temp_path(".cli_mismatch.parquet.part");
+        assert!(!temp.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_large_unsigned_integers() -> Result<()> {
+        let Value::Object(record) = serde_json::json!({ "n": u64::MAX }) else {
+            unreachable!()
+        };
+        let ty = ColumnType::of_value(&record["n"]);
+        assert_eq!(ty, ColumnType::Float);
+        let mut batch = BatchBuilder::new(&[Column {
+            name: "n".to_string(),
+            ty,
+        }]);
+        batch.append(&record)?;
+        assert_eq!(batch.flush()?.num_rows(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_arrow_rejects_snappy() -> Result<()> {
+        let output = temp_path("cli_snappy.arrow");
+        let result = ArrowIpcWriter::try_new(&output.to_string_lossy(), &[], Compression::Snappy);
+        assert!(result.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_days_since_epoch() {
+        assert_eq!(days_since_epoch("1970-01-02"), Some(1));
+        assert_eq!(days_since_epoch("1969-12-31"), Some(-1));
+        assert_eq!(days_since_epoch("Apr 18, 1990"), None);
+    }
+}
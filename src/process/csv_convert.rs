@@ -1,10 +1,12 @@
-use crate::opt::OutputFormat;
-use csv::Reader;
+use crate::opt::{InputFormat, OutputFormat};
+use crate::process::csv_flatten::open_structured;
+use crate::process::csv_reader::{open_csv, CsvReaderOptions, RecordStream};
+use crate::process::csv_sheet::open_sheet;
+use crate::process::csv_transform::Transform;
+use crate::process::csv_writer::{write_records, CsvWriterOptions};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
-use simple_excel_writer::*;
-use std::fs;
 
+#[allow(dead_code)]
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 struct Player {
@@ -17,50 +19,34 @@ struct Player {
     kit: u8,
 }
 
-pub fn process_csv(input: &str, output: String, format: OutputFormat) -> anyhow::Result<()> {
-    let mut reader = Reader::from_path(input)?;
-    let mut ret = Vec::with_capacity(128);
-    let headers = reader.headers()?.clone();
-    for result in reader.records() {
-        let record = result?;
-        let json_value = headers.iter().zip(record.iter()).collect::<Value>();
-        ret.push(json_value);
-    }
-
-    match format {
-        OutputFormat::Json => {
-            let content = serde_json::to_string_pretty(&ret)?;
-            fs::write(output, content)?;
-        }
-        OutputFormat::Yaml => {
-            let content = serde_yaml::to_string(&ret)?;
-            fs::write(output, content)?;
-        }
-        OutputFormat::Xlsx => {
-            let mut wb = Workbook::create(&output);
-            let mut sheet = wb.create_sheet("Sheet1");
-
-            // set column width
-            sheet.add_column(Column { width: 20.0 });
-            sheet.add_column(Column { width: 10.0 });
-            sheet.add_column(Column { width: 20.0 });
-            sheet.add_column(Column { width: 20.0 });
-            sheet.add_column(Column { width: 30.0 });
-
-            wb.write_sheet(&mut sheet, |sheet_writer| {
-                for rows in ret.as_slice() {
-                    let mut row = Row::new();
-                    for (_, val) in rows.as_object().unwrap().iter() {
-                        row.add_cell(val.to_string().trim_matches('"'));
-                    }
-                    sheet_writer.append_row(row)?;
-                }
-                Ok(())
-            })?;
-
-            wb.close()?;
-        }
-    };
-
+pub fn process_csv(
+    input: &str,
+    output: String,
+    format: OutputFormat,
+    from: InputFormat,
+    opts: &CsvReaderOptions,
+    writer_opts: &CsvWriterOptions,
+    transform: &Transform,
+) -> anyhow::Result<()> {
+    let stream = transform.apply(open_input(input, from, opts)?)?;
+    write_records(stream, format, &output, writer_opts)?;
     Ok(())
 }
+
+/// open any supported input as a stream of records
+pub fn open_input(
+    input: &str,
+    from: InputFormat,
+    opts: &CsvReaderOptions,
+) -> anyhow::Result<RecordStream> {
+    match from {
+        InputFormat::Csv => open_csv(input, opts),
+        InputFormat::Xlsx | InputFormat::Ods => open_sheet(
+            input,
+            opts.sheet.as_deref(),
+            opts.range.as_deref(),
+            opts.has_headers,
+        ),
+        InputFormat::Json | InputFormat::Yaml | InputFormat::Ndjson => open_structured(input, from),
+    }
+}
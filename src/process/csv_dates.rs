@@ -0,0 +1,486 @@
+use crate::process::csv_reader::{Column, ColumnType, RecordStream};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, NaiveDateTime};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::str::FromStr;
+
+/// formats tried in order when a column has no explicit format, month before day when unsure
+const AUTO_FORMATS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S%.f%:z",
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%d",
+    "%Y/%m/%d",
+    "%b %d, %Y",
+    "%B %d, %Y",
+    "%b %d %Y",
+    "%B %d %Y",
+    "%d %b %Y",
+    "%d %B %Y",
+    "%a, %d %b %Y %H:%M:%S %z",
+    "%m/%d/%Y %H:%M:%S",
+    "%m/%d/%Y",
+    "%d.%m.%Y",
+];
+
+/// `--parse-date COL[=FORMAT]`, a chrono format such as `%b %d, %Y` or none to detect it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DateColumn {
+    pub column: String,
+    pub format: Option<String>,
+}
+
+/// `--derive NAME=FUNC(COL)`, a column computed from a date column
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DerivedColumn {
+    pub name: String,
+    pub func: DateFunc,
+    pub column: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateFunc {
+    /// whole years between the date and the reference date
+    Age,
+    Year,
+    Month,
+    Day,
+    Weekday,
+}
+
+/// a parsed cell, keeping whether it had a time and an offset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateValue {
+    Date(NaiveDate),
+    DateTime(NaiveDateTime),
+    Zoned(DateTime<FixedOffset>),
+}
+
+impl FromStr for DateColumn {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (column, format) = match s.split_once('=') {
+            Some((column, "auto")) => (column, None),
+            Some((column, format)) => (column, Some(format.to_string())),
+            None => (s, None),
+        };
+        if column.is_empty() {
+            return Err(anyhow!("expected COLUMN or COLUMN=FORMAT"));
+        }
+        Ok(Self {
+            column: column.to_string(),
+            format,
+        })
+    }
+}
+
+impl FromStr for DerivedColumn {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || anyhow!("expected NAME=FUNC(COLUMN), e.g. \"Age=age(DOB)\"");
+        let (name, call) = s.split_once('=').ok_or_else(invalid)?;
+        let (func, column) = call
+            .trim()
+            .strip_suffix(')')
+            .and_then(|call| call.split_once('('))
+            .ok_or_else(invalid)?;
+        let (name, column) = (name.trim(), column.trim());
+        if name.is_empty() || column.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            name: name.to_string(),
+            func: func.trim().parse()?,
+            column: column.to_string(),
+        })
+    }
+}
+
+impl FromStr for DateFunc {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "age" => Ok(DateFunc::Age),
+            "year" => Ok(DateFunc::Year),
+            "month" => Ok(DateFunc::Month),
+            "day" => Ok(DateFunc::Day),
+            "weekday" => Ok(DateFunc::Weekday),
+            _ => Err(anyhow!(
+                "unknown function {:?}, expected age, year, month, day or weekday",
+                s
+            )),
+        }
+    }
+}
+
+impl DateValue {
+    /// parse with a chrono format, or with the first format of `AUTO_FORMATS` that fits
+    ///
+    /// Whatever follows the date after a space is ignored, so `Apr 18, 1990 (29)` is a date.
+    pub fn parse(text: &str, format: Option<&str>) -> Option<Self> {
+        let text = text.trim();
+        match format {
+            Some(format) => Self::parse_with(text, format),
+            None => AUTO_FORMATS
+                .iter()
+                .find_map(|format| Self::parse_with(text, format)),
+        }
+    }
+
+    fn parse_with(text: &str, format: &str) -> Option<Self> {
+        let rest_ok = |rest: &str| rest.is_empty() || rest.starts_with(char::is_whitespace);
+        if let Ok((value, rest)) = DateTime::parse_and_remainder(text, format) {
+            if rest_ok(rest) {
+                return Some(DateValue::Zoned(value));
+            }
+        }
+        if let Ok((value, rest)) = NaiveDateTime::parse_and_remainder(text, format) {
+            if rest_ok(rest) {
+                return Some(DateValue::DateTime(value));
+            }
+        }
+        match NaiveDate::parse_and_remainder(text, format) {
+            Ok((value, rest)) if rest_ok(rest) => Some(DateValue::Date(value)),
+            _ => None,
+        }
+    }
+
+    pub fn date(&self) -> NaiveDate {
+        match self {
+            DateValue::Date(date) => *date,
+            DateValue::DateTime(datetime) => datetime.date(),
+            DateValue::Zoned(datetime) => datetime.date_naive(),
+        }
+    }
+
+    /// ISO 8601: `1990-04-18`, `1990-04-18T08:30:00` or with its offset
+    pub fn to_iso(&self) -> String {
+        match self {
+            DateValue::Date(date) => date.format("%Y-%m-%d").to_string(),
+            DateValue::DateTime(datetime) => datetime.format("%Y-%m-%dT%H:%M:%S%.f").to_string(),
+            DateValue::Zoned(datetime) => datetime.to_rfc3339(),
+        }
+    }
+}
+
+impl DateFunc {
+    fn ty(self) -> ColumnType {
+        match self {
+            DateFunc::Weekday => ColumnType::String,
+            _ => ColumnType::Integer,
+        }
+    }
+
+    fn apply(self, date: NaiveDate, as_of: NaiveDate) -> Value {
+        match self {
+            DateFunc::Age => as_of.years_since(date).map_or(Value::Null, Value::from),
+            DateFunc::Year => Value::from(date.year()),
+            DateFunc::Month => Value::from(date.month()),
+            DateFunc::Day => Value::from(date.day()),
+            DateFunc::Weekday => Value::String(date.weekday().to_string()),
+        }
+    }
+}
+
+/// rows read ahead to decide whether a parsed column holds dates or datetimes, like the reader's
+/// default `--infer-rows`
+const SAMPLE_ROWS: usize = 1000;
+
+/// normalize date columns to ISO 8601 and append derived columns
+///
+/// A parsed column whose first rows are all plain dates becomes a date column, so xlsx output
+/// gets native date cells; one with times or offsets stays text. A value that doesn't parse is an
+/// error, nulls stay null. Derived columns read their source column the same way, detecting the
+/// format unless it is also given to `--parse-date`.
+pub fn parse_dates(
+    stream: RecordStream,
+    dates: &[DateColumn],
+    derived: &[DerivedColumn],
+    as_of: NaiveDate,
+) -> Result<RecordStream> {
+    let RecordStream { mut columns, rows } = stream;
+    let check =
+        |option: &str, name: &str, columns: &[Column]| match columns.iter().any(|c| c.name == name)
+        {
+            true => Ok(()),
+            false => Err(anyhow!("{}: unknown column {:?}", option, name)),
+        };
+    for date in dates {
+        check("--parse-date", &date.column, &columns)?;
+    }
+    for d in derived {
+        check("--derive", &d.column, &columns)?;
+        if columns.iter().any(|c| c.name == d.name) {
+            return Err(anyhow!("--derive: column {:?} already exists", d.name));
+        }
+    }
+    let mut rows = rows;
+    let mut sample = VecDeque::new();
+    for record in rows.by_ref().take(SAMPLE_ROWS) {
+        sample.push_back(record?);
+    }
+    for column in &mut columns {
+        let Some(date) = dates.iter().find(|d| d.column == column.name) else {
+            continue;
+        };
+        let only_dates = sample.iter().all(|record| {
+            let value = record.get(&date.column).unwrap_or(&Value::Null);
+            // values that don't parse fail below, with their row
+            !matches!(
+                parse_value(&date.column, value, date.format.as_deref()),
+                Ok(Some(DateValue::DateTime(_) | DateValue::Zoned(_)))
+            )
+        });
+        column.ty = if only_dates {
+            ColumnType::Date
+        } else {
+            ColumnType::String
+        };
+    }
+    let date_columns: Vec<bool> = dates
+        .iter()
+        .map(|date| {
+            columns
+                .iter()
+                .any(|c| c.name == date.column && c.ty == ColumnType::Date)
+        })
+        .collect();
+    for d in derived {
+        columns.push(Column {
+            name: d.name.clone(),
+            ty: d.func.ty(),
+        });
+    }
+
+    let dates = dates.to_vec();
+    let derived = derived.to_vec();
+    let rows = sample.into_iter().map(Ok).chain(rows).map(move |record| {
+        let mut record = record?;
+        // derived columns go first, while their source still has its original format
+        for d in &derived {
+            let format = dates
+                .iter()
+                .find(|date| date.column == d.column)
+                .and_then(|date| date.format.as_deref());
+            let value = record.get(&d.column).unwrap_or(&Value::Null);
+            let value = match parse_value(&d.column, value, format)? {
+                Some(date) => d.func.apply(date.date(), as_of),
+                None => Value::Null,
+            };
+            record.insert(d.name.clone(), value);
+        }
+        for (date, &date_column) in dates.iter().zip(&date_columns) {
+            if let Some(value) = record.get_mut(&date.column) {
+                if let Some(parsed) = parse_value(&date.column, value, date.format.as_deref())? {
+                    if date_column && !matches!(parsed, DateValue::Date(_)) {
+                        return Err(anyhow!(
+                            "{}: {:?} has a time, but the first {} rows only had dates",
+                            date.column,
+                            value.as_str().unwrap_or_default(),
+                            SAMPLE_ROWS
+                        ));
+                    }
+                    *value = Value::String(parsed.to_iso());
+                }
+            }
+        }
+        Ok(record)
+    });
+    Ok(RecordStream {
+        columns,
+        rows: Box::new(rows),
+    })
+}
+
+fn parse_value(column: &str, value: &Value, format: Option<&str>) -> Result<Option<DateValue>> {
+    match value {
+        Value::Null => Ok(None),
+        Value::String(text) if text.trim().is_empty() => Ok(None),
+        Value::String(text) => {
+            DateValue::parse(text, format)
+                .map(Some)
+                .ok_or_else(|| match format {
+                    Some(format) => anyhow!("{}: {:?} doesn't match {:?}", column, text, format),
+                    None => anyhow!("{}: {:?} isn't a date in a known format", column, text),
+                })
+        }
+        v => Err(anyhow!("{}: {} isn't a date", column, v)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::csv_reader::{open_csv, CsvReaderOptions, Record};
+    use crate::utils::{temp_path, write_temp};
+
+    fn parse(text: &str) -> Option<String> {
+        DateValue::parse(text, None).map(|date| date.to_iso())
+    }
+
+    #[test]
+    fn test_auto_formats() {
+        assert_eq!(parse("Apr 18, 1990 (29)").as_deref(), Some("1990-04-18"));
+        assert_eq!(parse("April 18, 1990").as_deref(), Some("1990-04-18"));
+        assert_eq!(parse("18 Apr 1990").as_deref(), Some("1990-04-18"));
+        assert_eq!(parse("1990/04/18").as_deref(), Some("1990-04-18"));
+        assert_eq!(parse("04/18/1990").as_deref(), Some("1990-04-18"));
+        assert_eq!(parse("18.04.1990").as_deref(), Some("1990-04-18"));
+        assert_eq!(
+            parse("1990-04-18 08:30:00").as_deref(),
+            Some("1990-04-18T08:30:00")
+        );
+        assert_eq!(
+            parse("1990-04-18T08:30:00+02:00").as_deref(),
+            Some("1990-04-18T08:30:00+02:00")
+        );
+        assert_eq!(parse("Apr 18, 1990x"), None);
+        assert_eq!(parse("Juventus"), None);
+    }
+
+    #[test]
+    fn test_explicit_format() {
+        let date = DateValue::parse("18|04|1990", Some("%d|%m|%Y")).unwrap();
+        assert_eq!(date.to_iso(), "1990-04-18");
+        // an explicit format wins over the month-first guess
+        let date = DateValue::parse("04/05/1990", Some("%d/%m/%Y")).unwrap();
+        assert_eq!(date.to_iso(), "1990-05-04");
+    }
+
+    #[test]
+    fn test_parse_args() -> Result<()> {
+        assert_eq!(
+            "DOB=%b %d, %Y".parse::<DateColumn>()?,
+            DateColumn {
+                column: "DOB".into(),
+                format: Some("%b %d, %Y".into())
+            }
+        );
+        assert_eq!("DOB=auto".parse::<DateColumn>()?.format, None);
+        assert_eq!(
+            "Age = age(DOB)".parse::<DerivedColumn>()?,
+            DerivedColumn {
+                name: "Age".into(),
+                func: DateFunc::Age,
+                column: "DOB".into()
+            }
+        );
+        assert!("Age=age DOB".parse::<DerivedColumn>().is_err());
+        assert!("Age=decade(DOB)".parse::<DerivedColumn>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_juventus_dates_and_ages() -> Result<()> {
+        let stream = open_csv("assets/juventus.csv", &CsvReaderOptions::default())?;
+        let dates = ["DOB".parse()?];
+        let derived = ["Age=age(DOB)".parse()?, "Born=weekday(DOB)".parse()?];
+        // the ages in the fixture were taken in autumn 2019
+        let as_of = NaiveDate::from_ymd_opt(2019, 11, 1).unwrap();
+        let stream = parse_dates(stream, &dates, &derived, as_of)?;
+        assert_eq!(stream.columns[2].ty, ColumnType::Date);
+        assert_eq!(stream.columns[5].name, "Age");
+        assert_eq!(stream.columns[5].ty, ColumnType::Integer);
+
+        let rows: Vec<Record> = stream.rows.collect::<Result<_>>()?;
+        assert_eq!(rows[0]["DOB"], "1990-04-18");
+        assert_eq!(rows[0]["Born"], "Wed");
+        let original = open_csv("assets/juventus.csv", &CsvReaderOptions::default())?;
+        for (row, original) in rows.iter().zip(original.rows) {
+            let dob = original?["DOB"].as_str().unwrap().to_string();
+            let age = dob.split_once('(').unwrap().1.trim_end_matches(')');
+            assert_eq!(row["Age"].to_string(), age, "{}", dob);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_dates_errors() -> Result<()> {
+        let open = || open_csv("assets/juventus.csv", &CsvReaderOptions::default());
+        let as_of = NaiveDate::from_ymd_opt(2019, 11, 1).unwrap();
+        let dates = ["Name".parse()?];
+        let mut rows = parse_dates(open()?, &dates, &[], as_of)?.rows;
+        assert_eq!(
+            rows.next().unwrap().unwrap_err().to_string(),
+            "Name: \"Wojciech Szczesny\" isn't a date in a known format"
+        );
+        let dates = ["DOB=%Y-%m-%d".parse()?];
+        let mut rows = parse_dates(open()?, &dates, &[], as_of)?.rows;
+        assert!(rows.next().unwrap().is_err());
+        let dates = ["Birthday".parse()?];
+        assert!(parse_dates(open()?, &dates, &[], as_of).is_err());
+        let derived = ["Name=age(DOB)".parse()?];
+        assert!(parse_dates(open()?, &[], &derived, as_of).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_datetimes_stay_text() -> Result<()> {
+        use crate::opt::OutputFormat;
+        use crate::process::csv_writer::write_records;
+
+        let csv = |name: &str, content: &str| -> Result<RecordStream> {
+            let path = write_temp(name, content)?;
+            open_csv(&path, &CsvReaderOptions::default())
+        };
+        let dates = ["ts".parse()?];
+        let stream = csv(
+            "cli_dates_ts.csv",
+            "id,ts\n1,2024-01-02\n2,2024-01-02 08:30:00\n",
+        )?;
+        let stream = parse_dates(stream, &dates, &[], NaiveDate::MIN)?;
+        assert_eq!(stream.columns[1].ty, ColumnType::String);
+        let output = temp_path("cli_dates_ts.parquet");
+        write_records(
+            stream,
+            OutputFormat::Parquet,
+            &output.to_string_lossy(),
+            &Default::default(),
+        )?;
+
+        // a time after the sampled rows can't be written as a date
+        let mut content = "id,ts\n".to_string();
+        for i in 0..SAMPLE_ROWS {
+            content.push_str(&format!("{},2024-01-02\n", i));
+        }
+        content.push_str("last,2024-01-02T08:30:00\n");
+        let stream = csv("cli_dates_late_ts.csv", &content)?;
+        let stream = parse_dates(stream, &dates, &[], NaiveDate::MIN)?;
+        assert_eq!(stream.columns[1].ty, ColumnType::Date);
+        let err = stream.rows.last().unwrap().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "ts: \"2024-01-02T08:30:00\" has a time, but the first 1000 rows only had dates"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parsed_dates_are_native_xlsx_dates() -> Result<()> {
+        use crate::opt::OutputFormat;
+        use crate::process::csv_writer::write_records;
+        use calamine::{open_workbook, Data, DataType, Reader, Xlsx};
+
+        let stream = open_csv("assets/juventus.csv", &CsvReaderOptions::default())?;
+        let stream = parse_dates(stream, &["DOB".parse()?], &[], NaiveDate::MIN)?;
+        let output = temp_path("cli_dates.xlsx");
+        write_records(
+            stream,
+            OutputFormat::Xlsx,
+            &output.to_string_lossy(),
+            &Default::default(),
+        )?;
+
+        let mut workbook: Xlsx<_> = open_workbook(&output)?;
+        let range = workbook.worksheet_range_at(0).unwrap()?;
+        let dob = &range.rows().nth(1).unwrap()[2];
+        assert!(matches!(dob, Data::DateTime(_)));
+        assert_eq!(dob.as_date(), NaiveDate::from_ymd_opt(1990, 4, 18));
+        Ok(())
+    }
+}
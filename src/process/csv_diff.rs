@@ -0,0 +1,330 @@
+use crate::opt::{DiffFormat, InputFormat};
+use crate::process::csv_convert::open_input;
+use crate::process::csv_expr::compare;
+use crate::process::csv_join::key;
+use crate::process::csv_reader::{CsvReaderOptions, Record, RecordStream};
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// differences between two versions of a file, rows matched by their key columns
+#[derive(Debug, Default, Serialize)]
+pub struct Diff {
+    pub key: Vec<String>,
+    pub columns_added: Vec<String>,
+    pub columns_removed: Vec<String>,
+    /// the shared columns come in a different order
+    pub columns_reordered: bool,
+    pub added: Vec<Record>,
+    pub removed: Vec<Record>,
+    pub modified: Vec<RowChange>,
+    pub unchanged: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RowChange {
+    pub key: Record,
+    pub changes: Vec<CellChange>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CellChange {
+    pub column: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+pub fn process_csv_diff(
+    old: (&str, InputFormat),
+    new: (&str, InputFormat),
+    opts: &CsvReaderOptions,
+    key: &[String],
+) -> Result<Diff> {
+    let old_stream = open_input(old.0, old.1, opts)?;
+    let new_stream = open_input(new.0, new.1, opts)?;
+    diff(old_stream, new_stream, key)
+}
+
+/// compare two streams, holding the old one in memory and streaming the new one
+///
+/// Only columns present in both are compared, so an added column doesn't mark every row as
+/// modified and reordered columns make no difference. Values compare like `--where` does, so
+/// `7` equals `7.0`.
+pub fn diff(old: RecordStream, new: RecordStream, key_columns: &[String]) -> Result<Diff> {
+    if key_columns.is_empty() {
+        return Err(anyhow!("at least one key column is required"));
+    }
+    for (side, stream) in [("old", &old), ("new", &new)] {
+        if let Some(name) = key_columns
+            .iter()
+            .find(|name| !stream.columns.iter().any(|c| &c.name == *name))
+        {
+            return Err(anyhow!("--key: {} input has no column {:?}", side, name));
+        }
+    }
+
+    let old_names: Vec<String> = old.columns.iter().map(|c| c.name.clone()).collect();
+    let new_names: Vec<String> = new.columns.iter().map(|c| c.name.clone()).collect();
+    let shared: Vec<String> = new_names
+        .iter()
+        .filter(|name| old_names.contains(name) && !key_columns.contains(name))
+        .cloned()
+        .collect();
+    let mut result = Diff {
+        key: key_columns.to_vec(),
+        columns_added: difference(&new_names, &old_names),
+        columns_removed: difference(&old_names, &new_names),
+        columns_reordered: old_names
+            .iter()
+            .filter(|n| new_names.contains(n))
+            .ne(new_names.iter().filter(|n| old_names.contains(n))),
+        ..Default::default()
+    };
+
+    let mut old_rows: Vec<Option<Record>> = Vec::new();
+    let mut index = HashMap::new();
+    for (i, record) in old.rows.enumerate() {
+        let record = record?;
+        let key = row_key(&record, key_columns, "old", i)?;
+        if index.insert(key.clone(), old_rows.len()).is_some() {
+            return Err(anyhow!("duplicate key {:?} in old input", key));
+        }
+        old_rows.push(Some(record));
+    }
+
+    let mut seen = HashMap::new();
+    for (i, record) in new.rows.enumerate() {
+        let record = record?;
+        let key = row_key(&record, key_columns, "new", i)?;
+        if seen.insert(key.clone(), ()).is_some() {
+            return Err(anyhow!("duplicate key {:?} in new input", key));
+        }
+        let Some(old_record) = index.get(&key).and_then(|&j| old_rows[j].take()) else {
+            result.added.push(record);
+            continue;
+        };
+        let changes: Vec<CellChange> = shared
+            .iter()
+            .filter_map(|column| {
+                let old = old_record.get(column).cloned().unwrap_or(Value::Null);
+                let new = record.get(column).cloned().unwrap_or(Value::Null);
+                match compare(&old, &new) {
+                    Some(Ordering::Equal) => None,
+                    _ => Some(CellChange {
+                        column: column.clone(),
+                        old,
+                        new,
+                    }),
+                }
+            })
+            .collect();
+        if changes.is_empty() {
+            result.unchanged += 1;
+        } else {
+            let key = key_columns
+                .iter()
+                .map(|c| (c.clone(), record[c].clone()))
+                .collect();
+            result.modified.push(RowChange { key, changes });
+        }
+    }
+    result.removed = old_rows.into_iter().flatten().collect();
+    Ok(result)
+}
+
+pub fn render_diff(diff: &Diff, format: DiffFormat, color: bool) -> Result<String> {
+    match format {
+        DiffFormat::Json => Ok(serde_json::to_string_pretty(diff)?),
+        DiffFormat::Text => Ok(render_text(diff, color)),
+    }
+}
+
+fn render_text(diff: &Diff, color: bool) -> String {
+    let paint = |code: &str, text: String| match color {
+        true => format!("\x1b[{}m{}\x1b[0m", code, text),
+        false => text,
+    };
+    let mut out = String::new();
+    if !diff.columns_added.is_empty() {
+        let line = format!("+ columns: {}", diff.columns_added.join(", "));
+        let _ = writeln!(out, "{}", paint(GREEN, line));
+    }
+    if !diff.columns_removed.is_empty() {
+        let line = format!("- columns: {}", diff.columns_removed.join(", "));
+        let _ = writeln!(out, "{}", paint(RED, line));
+    }
+    if diff.columns_reordered {
+        let _ = writeln!(out, "~ columns reordered");
+    }
+    for record in &diff.removed {
+        let line = format!("- {}", fields(record, &diff.key));
+        let _ = writeln!(out, "{}", paint(RED, line));
+    }
+    for record in &diff.added {
+        let line = format!("+ {}", fields(record, &diff.key));
+        let _ = writeln!(out, "{}", paint(GREEN, line));
+    }
+    for row in &diff.modified {
+        let line = format!("~ {}", fields(&row.key, &diff.key));
+        let _ = writeln!(out, "{}", paint(YELLOW, line));
+        for change in &row.changes {
+            let _ = writeln!(
+                out,
+                "    {}: {} -> {}",
+                change.column,
+                paint(RED, display_value(&change.old)),
+                paint(GREEN, display_value(&change.new))
+            );
+        }
+    }
+    let _ = writeln!(
+        out,
+        "{} added, {} removed, {} modified, {} unchanged",
+        diff.added.len(),
+        diff.removed.len(),
+        diff.modified.len(),
+        diff.unchanged
+    );
+    out
+}
+
+const RED: &str = "31";
+const GREEN: &str = "32";
+const YELLOW: &str = "33";
+
+/// `Name=alice, Kit Number=7`, key columns first
+fn fields(record: &Record, key: &[String]) -> String {
+    let key_fields = key.iter().filter_map(|k| record.get_key_value(k));
+    let other_fields = record.iter().filter(|(k, _)| !key.contains(k));
+    key_fields
+        .chain(other_fields)
+        .map(|(k, v)| format!("{}={}", k, display_value(v)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn display_value(value: &Value) -> String {
+    match value {
+        Value::Null => "(empty)".to_string(),
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+fn difference(a: &[String], b: &[String]) -> Vec<String> {
+    a.iter().filter(|name| !b.contains(name)).cloned().collect()
+}
+
+fn row_key(record: &Record, columns: &[String], side: &str, i: usize) -> Result<String> {
+    key(record, columns)
+        .ok_or_else(|| anyhow!("record {} of the {} input has an empty key", i + 1, side))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::csv_reader::open_csv;
+    use crate::utils::write_temp;
+    use serde_json::json;
+
+    fn open(name: &str, content: &str) -> Result<RecordStream> {
+        let path = write_temp(name, content)?;
+        open_csv(&path, &CsvReaderOptions::default())
+    }
+
+    fn players() -> Result<Diff> {
+        let old = open(
+            "cli_diff_old.csv",
+            "Name,Kit Number,Position,Age\nalice,7,Forward,30\nbob,9,Forward,25\ncarol,1,Goalkeeper,28\n",
+        )?;
+        // columns reordered, Age dropped and Email added, 7.0 makes Kit Number a float column
+        let new = open(
+            "cli_diff_new.csv",
+            "Position,Name,Kit Number,Email\nForward,alice,7.0,a@x\nDefender,carol,2,c@x\nMidfield,dave,8,\n",
+        )?;
+        diff(old, new, &["Name".to_string()])
+    }
+
+    #[test]
+    fn test_diff() -> Result<()> {
+        let diff = players()?;
+        assert_eq!(diff.columns_added, vec!["Email"]);
+        assert_eq!(diff.columns_removed, vec!["Age"]);
+        assert!(diff.columns_reordered);
+        assert_eq!(diff.unchanged, 1);
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0]["Name"], "dave");
+        assert_eq!(diff.removed.len(), 1);
+        assert_eq!(diff.removed[0]["Name"], "bob");
+        assert_eq!(
+            serde_json::to_value(&diff.modified)?,
+            json!([{
+                "key": {"Name": "carol"},
+                "changes": [
+                    {"column": "Position", "old": "Goalkeeper", "new": "Defender"},
+                    {"column": "Kit Number", "old": 1, "new": 2.0},
+                ],
+            }])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_text() -> Result<()> {
+        let text = render_diff(&players()?, DiffFormat::Text, false)?;
+        assert_eq!(
+            text,
+            "+ columns: Email\n\
+             - columns: Age\n\
+             ~ columns reordered\n\
+             - Name=bob, Kit Number=9, Position=Forward, Age=25\n\
+             + Name=dave, Position=Midfield, Kit Number=8.0, Email=(empty)\n\
+             ~ Name=carol\n    \
+             Position: Goalkeeper -> Defender\n    \
+             Kit Number: 1 -> 2.0\n\
+             1 added, 1 removed, 1 modified, 1 unchanged\n"
+        );
+        let colored = render_diff(&players()?, DiffFormat::Text, true)?;
+        assert!(colored.contains("\x1b[31m- Name=bob"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_integer_and_float_keys_match() -> Result<()> {
+        // `id` is inferred as integer in the old file and as float in the new one
+        let old = open("cli_diff_int_keys.csv", "id,name\n1,a\n2,b\n")?;
+        let new = open("cli_diff_float_keys.csv", "id,name\n1,a\n2,b\n2.5,c\n")?;
+        let diff = diff(old, new, &["id".to_string()])?;
+        assert_eq!(diff.unchanged, 2);
+        assert!(diff.removed.is_empty());
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0]["id"], 2.5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_errors() -> Result<()> {
+        let same = "Name,Kit Number\nalice,7\n";
+        let err = |old: &str, new: &str, key: &str| -> Result<String> {
+            let old = open(&format!("cli_diff_err_old_{}.csv", old.len()), old)?;
+            let new = open(&format!("cli_diff_err_new_{}.csv", new.len()), new)?;
+            Ok(diff(old, new, &[key.to_string()]).unwrap_err().to_string())
+        };
+        assert_eq!(
+            err(same, same, "Age")?,
+            "--key: old input has no column \"Age\""
+        );
+        assert_eq!(
+            err("Name\nalice\nalice\n", same, "Name")?,
+            "duplicate key \"alice\" in old input"
+        );
+        assert_eq!(
+            err(same, "Name,Kit Number\n,7\n", "Name")?,
+            "record 1 of the new input has an empty key"
+        );
+        Ok(())
+    }
+}
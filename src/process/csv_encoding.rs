@@ -0,0 +1,130 @@
+use anyhow::Result;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+use encoding_rs_io::DecodeReaderBytesBuilder;
+use std::io::{Cursor, Read};
+
+/// bytes inspected to guess the encoding of input without a byte order mark
+const SNIFF_LEN: usize = 64 * 1024;
+
+/// wrap a reader so it yields UTF-8
+///
+/// A byte order mark always wins. Without one the given encoding is used, or else it is guessed
+/// from the first 64 KiB: NUL bytes in every other position mean UTF-16, valid UTF-8 stays as
+/// is and anything else is read as Windows-1252, which decodes every byte.
+pub fn decode_reader(
+    mut reader: Box<dyn Read>,
+    encoding: Option<&'static Encoding>,
+) -> Result<Box<dyn Read>> {
+    let mut head = Vec::with_capacity(SNIFF_LEN);
+    reader
+        .by_ref()
+        .take(SNIFF_LEN as u64)
+        .read_to_end(&mut head)?;
+    let encoding = match Encoding::for_bom(&head) {
+        Some((bom, _)) => bom,
+        None => encoding.unwrap_or_else(|| detect(&head)),
+    };
+    let reader = Cursor::new(head).chain(reader);
+    if encoding == UTF_8 {
+        // only the BOM needs to go, the bytes are already UTF-8
+        let reader = DecodeReaderBytesBuilder::new()
+            .encoding(None)
+            .strip_bom(true)
+            .build(reader);
+        return Ok(Box::new(reader));
+    }
+    let reader = DecodeReaderBytesBuilder::new()
+        .encoding(Some(encoding))
+        .bom_override(true)
+        .build(reader);
+    Ok(Box::new(reader))
+}
+
+/// guess the encoding of a prefix of input that has no byte order mark
+pub fn detect(head: &[u8]) -> &'static Encoding {
+    // NUL is valid UTF-8, so UTF-16 has to be ruled out first
+    let pairs = head.len() / 2;
+    if pairs > 0 {
+        let even = head.iter().step_by(2).filter(|&&b| b == 0).count();
+        let odd = head.iter().skip(1).step_by(2).filter(|&&b| b == 0).count();
+        // mostly ASCII text has a NUL in every high byte
+        if odd * 10 > pairs * 3 && even * 10 < pairs {
+            return UTF_16LE;
+        }
+        if even * 10 > pairs * 3 && odd * 10 < pairs {
+            return UTF_16BE;
+        }
+    }
+    if is_utf8(head) {
+        UTF_8
+    } else {
+        WINDOWS_1252
+    }
+}
+
+/// valid UTF-8, allowing a sequence cut off at the end of the prefix
+fn is_utf8(head: &[u8]) -> bool {
+    match std::str::from_utf8(head) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none() && head.len() - e.valid_up_to() < 4,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::csv_reader::{open_csv, CsvReaderOptions};
+
+    const FIXTURES: [&str; 4] = [
+        "fixtures/players_utf8.csv",
+        "fixtures/players_utf8_bom.csv",
+        "fixtures/players_utf16le.csv",
+        "fixtures/players_windows1252.csv",
+    ];
+
+    fn decode(bytes: &[u8], encoding: Option<&'static Encoding>) -> Result<String> {
+        let mut text = String::new();
+        decode_reader(Box::new(Cursor::new(bytes.to_vec())), encoding)?
+            .read_to_string(&mut text)?;
+        Ok(text)
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(detect("Higuaín".as_bytes()), UTF_8);
+        assert_eq!(detect(b"Higua\xedn"), WINDOWS_1252);
+        assert_eq!(detect(b"N\0a\0m\0e\0"), UTF_16LE);
+        assert_eq!(detect(b"\0N\0a\0m\0e"), UTF_16BE);
+        // a multi-byte character cut off by the sniffing window is still UTF-8
+        assert_eq!(detect(&"aí".as_bytes()[..2]), UTF_8);
+    }
+
+    #[test]
+    fn test_decode_reader() -> Result<()> {
+        assert_eq!(decode(b"\xef\xbb\xbfName", None)?, "Name");
+        assert_eq!(decode(b"\xff\xfeN\0\xe9\0", None)?, "Né");
+        assert_eq!(decode(b"Higua\xedn", None)?, "Higuaín");
+        // an explicit encoding is used as is, a BOM overrides it
+        assert_eq!(
+            decode("Higuaín".as_bytes(), Some(WINDOWS_1252))?,
+            "HiguaÃ\u{AD}n"
+        );
+        assert_eq!(decode(b"\xef\xbb\xbfa", Some(UTF_16LE))?, "a");
+        Ok(())
+    }
+
+    #[test]
+    fn test_fixtures_read_the_same() -> Result<()> {
+        let read = |path: &str| -> Result<Vec<_>> {
+            let stream = open_csv(path, &CsvReaderOptions::default())?;
+            assert_eq!(stream.columns[0].name, "Name", "{}", path);
+            stream.rows.collect()
+        };
+        let expected = read(FIXTURES[0])?;
+        assert_eq!(expected[0]["Name"], "Gonzalo Higuaín");
+        for path in &FIXTURES[1..] {
+            assert_eq!(read(path)?, expected, "{}", path);
+        }
+        Ok(())
+    }
+}
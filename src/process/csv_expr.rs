@@ -0,0 +1,884 @@
+use crate::process::csv_reader::{Column, Record};
+use anyhow::{anyhow, Result};
+use serde_json::{Number, Value};
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// a boolean filter over a record
+///
+/// ```text
+/// expr      := and ("OR" and)*
+/// and       := unary ("AND" unary)*
+/// unary     := "NOT" unary | "(" expr ")" | predicate
+/// predicate := column op literal
+///            | column "IS" ["NOT"] "NULL"
+///            | column ["NOT"] "LIKE" literal
+///            | column ["NOT"] "IN" "(" literal ("," literal)* ")"
+/// op        := "=" | "==" | "!=" | "<>" | "<" | "<=" | ">" | ">="
+/// ```
+///
+/// Keywords are case-insensitive. A column is a bare word or a quoted name (`` `Kit Number` ``,
+/// `"Kit Number"`), a literal is a number, `true`, `false`, `null`, a quoted string or a bare
+/// word, which is read as a string (`Position == Goalkeeper`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Compare {
+        column: String,
+        op: CompareOp,
+        value: Value,
+    },
+    IsNull {
+        column: String,
+        negated: bool,
+    },
+    Like {
+        column: String,
+        pattern: String,
+        negated: bool,
+    },
+    In {
+        column: String,
+        values: Vec<Value>,
+        negated: bool,
+    },
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// a sort column, parsed from `COLUMN[:asc|:desc]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortKey {
+    pub column: String,
+    pub descending: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Token {
+    /// bare identifier, keyword or unquoted value
+    Word(String),
+    /// `backticked` name
+    Name(String),
+    /// 'single' or "double" quoted text
+    Text(String),
+    Number(Number),
+    Op(CompareOp),
+    LParen,
+    RParen,
+    Comma,
+    Star,
+}
+
+/// tokens of an expression or query with their character offsets, shared by the query parser
+pub(crate) struct Tokens {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    end: usize,
+}
+
+impl Expr {
+    /// fail with the first column the expression references that isn't in `columns`
+    pub fn check_columns(&self, columns: &[Column]) -> Result<()> {
+        let mut names = Vec::new();
+        self.collect_columns(&mut names);
+        match names
+            .into_iter()
+            .find(|name| !columns.iter().any(|c| &c.name == name))
+        {
+            Some(name) => Err(anyhow!("unknown column {:?}", name)),
+            None => Ok(()),
+        }
+    }
+
+    fn collect_columns<'a>(&'a self, names: &mut Vec<&'a str>) {
+        match self {
+            Expr::Compare { column, .. }
+            | Expr::IsNull { column, .. }
+            | Expr::Like { column, .. }
+            | Expr::In { column, .. } => names.push(column),
+            Expr::Not(expr) => expr.collect_columns(names),
+            Expr::And(a, b) | Expr::Or(a, b) => {
+                a.collect_columns(names);
+                b.collect_columns(names);
+            }
+        }
+    }
+
+    pub fn matches(&self, record: &Record) -> bool {
+        let get = |column: &str| record.get(column).unwrap_or(&Value::Null);
+        match self {
+            Expr::Compare { column, op, value } => {
+                let ordering = compare(get(column), value);
+                match op {
+                    CompareOp::Eq => ordering == Some(Ordering::Equal),
+                    CompareOp::Ne => ordering != Some(Ordering::Equal),
+                    CompareOp::Lt => ordering == Some(Ordering::Less),
+                    CompareOp::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+                    CompareOp::Gt => ordering == Some(Ordering::Greater),
+                    CompareOp::Ge => {
+                        matches!(ordering, Some(Ordering::Greater | Ordering::Equal))
+                    }
+                }
+            }
+            Expr::IsNull { column, negated } => get(column).is_null() != *negated,
+            Expr::Like {
+                column,
+                pattern,
+                negated,
+            } => {
+                let matched = match get(column) {
+                    Value::Null => false,
+                    Value::String(s) => like(s, pattern),
+                    v => like(&v.to_string(), pattern),
+                };
+                matched != *negated
+            }
+            Expr::In {
+                column,
+                values,
+                negated,
+            } => {
+                let value = get(column);
+                let found = values
+                    .iter()
+                    .any(|v| compare(value, v) == Some(Ordering::Equal));
+                found != *negated
+            }
+            Expr::Not(expr) => !expr.matches(record),
+            Expr::And(a, b) => a.matches(record) && b.matches(record),
+            Expr::Or(a, b) => a.matches(record) || b.matches(record),
+        }
+    }
+}
+
+impl FromStr for Expr {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut tokens = Tokens::new(input)?;
+        let expr = tokens.expr()?;
+        tokens.expect_end()?;
+        Ok(expr)
+    }
+}
+
+/// compare two cells, numbers stored as text compare as numbers and null equals only null
+pub fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Null, Value::Null) => Some(Ordering::Equal),
+        (Value::Null, _) | (_, Value::Null) => None,
+        (Value::Number(x), Value::Number(y)) => match (x.as_i64(), y.as_i64()) {
+            (Some(x), Some(y)) => Some(x.cmp(&y)),
+            _ => x.as_f64()?.partial_cmp(&y.as_f64()?),
+        },
+        (Value::String(s), Value::Number(n)) => {
+            s.trim().parse::<f64>().ok()?.partial_cmp(&n.as_f64()?)
+        }
+        (Value::Number(n), Value::String(s)) => {
+            n.as_f64()?.partial_cmp(&s.trim().parse::<f64>().ok()?)
+        }
+        (Value::Bool(x), Value::Bool(y)) => Some(x.cmp(y)),
+        // equal numbers written differently, like "7" and "007", still differ as text
+        (Value::String(x), Value::String(y)) => match (parse_number(x), parse_number(y)) {
+            (Some(a), Some(b)) => Some(a.partial_cmp(&b)?.then_with(|| x.cmp(y))),
+            _ => Some(x.cmp(y)),
+        },
+        (a, b) => Some(text(a).cmp(&text(b))),
+    }
+}
+
+/// a total order for sorting: booleans, then numbers, then text, then arrays and objects, with
+/// nulls last
+///
+/// Text that parses as a number sorts as that number, so `"10"` and `9` order the same as in
+/// `compare`. Ranking by kind first keeps the order transitive when a column mixes numbers and
+/// text, which `sort_by` requires.
+pub fn sort_order(a: &Value, b: &Value) -> Ordering {
+    SortKeyValue::of(a).cmp(&SortKeyValue::of(b))
+}
+
+/// a value as its place in `sort_order`, variants in rank order
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum SortKeyValue<'a> {
+    Bool(bool),
+    Number(SortNumber),
+    Text(&'a str),
+    Other(String),
+    Null,
+}
+
+/// a number compared exactly, integers as integers and everything else by `total_cmp`
+#[derive(Debug, Clone, Copy)]
+enum SortNumber {
+    Integer(i128),
+    Float(f64),
+}
+
+impl<'a> SortKeyValue<'a> {
+    fn of(value: &'a Value) -> Self {
+        match value {
+            Value::Null => SortKeyValue::Null,
+            Value::Bool(b) => SortKeyValue::Bool(*b),
+            Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+                (Some(i), _) => SortKeyValue::Number(SortNumber::Integer(i as i128)),
+                (_, Some(u)) => SortKeyValue::Number(SortNumber::Integer(u as i128)),
+                _ => SortKeyValue::Number(SortNumber::Float(n.as_f64().unwrap_or(f64::NAN))),
+            },
+            Value::String(s) => match s.trim().parse::<f64>() {
+                Ok(f) if f.is_finite() => match s.trim().parse::<i128>() {
+                    Ok(i) => SortKeyValue::Number(SortNumber::Integer(i)),
+                    Err(_) => SortKeyValue::Number(SortNumber::Float(f)),
+                },
+                _ => SortKeyValue::Text(s),
+            },
+            v => SortKeyValue::Other(v.to_string()),
+        }
+    }
+}
+
+impl Ord for SortNumber {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (*self, *other) {
+            (SortNumber::Integer(x), SortNumber::Integer(y)) => x.cmp(&y),
+            (SortNumber::Float(x), SortNumber::Float(y)) => x.total_cmp(&y),
+            (SortNumber::Integer(x), SortNumber::Float(y)) => integer_float_cmp(x, y),
+            (SortNumber::Float(x), SortNumber::Integer(y)) => integer_float_cmp(y, x).reverse(),
+        }
+    }
+}
+
+impl PartialOrd for SortNumber {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for SortNumber {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for SortNumber {}
+
+/// exact comparison of an integer with a float, without rounding the integer to f64
+fn integer_float_cmp(x: i128, y: f64) -> Ordering {
+    if y.is_nan() {
+        return Ordering::Less;
+    }
+    // every i128 from a JSON number or a cell fits well inside ±2^100
+    let bound = 2f64.powi(100);
+    if y >= bound {
+        return Ordering::Less;
+    }
+    if y < -bound {
+        return Ordering::Greater;
+    }
+    let whole = y.trunc();
+    match x.cmp(&(whole as i128)) {
+        Ordering::Equal => 0f64.total_cmp(&(y - whole)),
+        ordering => ordering,
+    }
+}
+
+/// stable sort by several keys, the first key that differs decides
+pub fn sort_records(records: &mut [Record], keys: &[SortKey]) {
+    records.sort_by(|a, b| {
+        keys.iter()
+            .map(|key| {
+                let ordering = sort_order(
+                    a.get(&key.column).unwrap_or(&Value::Null),
+                    b.get(&key.column).unwrap_or(&Value::Null),
+                );
+                if key.descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    });
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+/// text that reads as a finite number
+fn parse_number(text: &str) -> Option<f64> {
+    text.trim().parse::<f64>().ok().filter(|f| f.is_finite())
+}
+
+/// SQL `LIKE`: `%` matches any run of characters, `_` a single one, ASCII case-insensitive
+fn like(value: &str, pattern: &str) -> bool {
+    let value: Vec<char> = value.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+    // classic wildcard matching, backtracking to the last `%`
+    let (mut v, mut p) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while v < value.len() {
+        if p < pattern.len()
+            && (pattern[p] == '_' || pattern[p].eq_ignore_ascii_case(&value[v]))
+            && pattern[p] != '%'
+        {
+            v += 1;
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == '%' {
+            star = Some((p, v));
+            p += 1;
+        } else if let Some((sp, sv)) = star {
+            p = sp + 1;
+            v = sv + 1;
+            star = Some((sp, sv + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '%')
+}
+
+impl FromStr for SortKey {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (column, descending) = match value.rsplit_once(':') {
+            Some((column, order)) if order.eq_ignore_ascii_case("asc") => (column, false),
+            Some((column, order)) if order.eq_ignore_ascii_case("desc") => (column, true),
+            _ => (value, false),
+        };
+        if column.is_empty() {
+            return Err(anyhow!("empty sort column in {:?}", value));
+        }
+        Ok(SortKey {
+            column: column.to_string(),
+            descending,
+        })
+    }
+}
+
+impl Tokens {
+    pub(crate) fn new(input: &str) -> Result<Self> {
+        Ok(Self {
+            tokens: tokenize(input)?,
+            pos: 0,
+            end: input.chars().count(),
+        })
+    }
+
+    pub(crate) fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, token)| token)
+    }
+
+    /// the token after the next one
+    pub(crate) fn peek_second(&self) -> Option<&Token> {
+        self.tokens.get(self.pos + 1).map(|(_, token)| token)
+    }
+
+    pub(crate) fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|(_, token)| token.clone());
+        self.pos += 1;
+        token
+    }
+
+    pub(crate) fn is_end(&self) -> bool {
+        self.pos >= self.tokens.len()
+    }
+
+    /// an error pointing at the next token
+    pub(crate) fn error(&self, expected: &str) -> anyhow::Error {
+        match self.tokens.get(self.pos) {
+            Some((offset, token)) => anyhow!(
+                "expected {} at position {}, found {}",
+                expected,
+                offset + 1,
+                token
+            ),
+            None => anyhow!(
+                "expected {} at position {}, found end of input",
+                expected,
+                self.end + 1
+            ),
+        }
+    }
+
+    pub(crate) fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(w)) if w.eq_ignore_ascii_case(keyword))
+    }
+
+    /// consume the keyword if it is next
+    pub(crate) fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.is_keyword(keyword);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    pub(crate) fn expect_keyword(&mut self, keyword: &str) -> Result<()> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(self.error(keyword))
+        }
+    }
+
+    pub(crate) fn eat(&mut self, token: &Token) -> bool {
+        let found = self.peek() == Some(token);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    pub(crate) fn expect(&mut self, token: &Token) -> Result<()> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.error(&token.to_string()))
+        }
+    }
+
+    pub(crate) fn expect_end(&self) -> Result<()> {
+        if self.is_end() {
+            Ok(())
+        } else {
+            Err(self.error("end of input"))
+        }
+    }
+
+    /// a bare, backticked or quoted column name
+    pub(crate) fn column(&mut self) -> Result<String> {
+        match self.peek() {
+            Some(Token::Word(_) | Token::Name(_) | Token::Text(_)) => match self.next() {
+                Some(Token::Word(name) | Token::Name(name) | Token::Text(name)) => Ok(name),
+                _ => unreachable!(),
+            },
+            _ => Err(self.error("a column name")),
+        }
+    }
+
+    pub(crate) fn literal(&mut self) -> Result<Value> {
+        let value = match self.peek() {
+            Some(Token::Number(n)) => Value::Number(n.clone()),
+            Some(Token::Text(s)) => Value::String(s.clone()),
+            Some(Token::Word(w)) => match w.to_ascii_lowercase().as_str() {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                "null" => Value::Null,
+                _ => Value::String(w.clone()),
+            },
+            _ => return Err(self.error("a value")),
+        };
+        self.pos += 1;
+        Ok(value)
+    }
+
+    pub(crate) fn expr(&mut self) -> Result<Expr> {
+        let mut expr = self.and()?;
+        while self.eat_keyword("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut expr = self.unary()?;
+        while self.eat_keyword("and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if self.eat_keyword("not") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.eat(&Token::LParen) {
+            let expr = self.expr()?;
+            self.expect(&Token::RParen)?;
+            return Ok(expr);
+        }
+        self.predicate()
+    }
+
+    fn predicate(&mut self) -> Result<Expr> {
+        let column = self.column()?;
+        if self.eat_keyword("is") {
+            let negated = self.eat_keyword("not");
+            self.expect_keyword("null")?;
+            return Ok(Expr::IsNull { column, negated });
+        }
+        let negated = self.eat_keyword("not");
+        if self.eat_keyword("like") {
+            let pattern = match self.literal()? {
+                Value::String(s) => s,
+                v => v.to_string(),
+            };
+            return Ok(Expr::Like {
+                column,
+                pattern,
+                negated,
+            });
+        }
+        if self.eat_keyword("in") {
+            self.expect(&Token::LParen)?;
+            let mut values = vec![self.literal()?];
+            while self.eat(&Token::Comma) {
+                values.push(self.literal()?);
+            }
+            self.expect(&Token::RParen)?;
+            return Ok(Expr::In {
+                column,
+                values,
+                negated,
+            });
+        }
+        if negated {
+            return Err(self.error("LIKE or IN"));
+        }
+        match self.peek() {
+            Some(&Token::Op(op)) => {
+                self.pos += 1;
+                let value = self.literal()?;
+                Ok(Expr::Compare { column, op, value })
+            }
+            _ => Err(self.error("a comparison operator")),
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<(usize, Token)>> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        let token = match c {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            ',' => Token::Comma,
+            '*' => Token::Star,
+            '=' | '!' | '<' | '>' => {
+                let next = chars.get(i + 1).copied();
+                let (op, len) = match (c, next) {
+                    ('=', Some('=')) => (CompareOp::Eq, 2),
+                    ('=', _) => (CompareOp::Eq, 1),
+                    ('!', Some('=')) => (CompareOp::Ne, 2),
+                    ('<', Some('>')) => (CompareOp::Ne, 2),
+                    ('<', Some('=')) => (CompareOp::Le, 2),
+                    ('<', _) => (CompareOp::Lt, 1),
+                    ('>', Some('=')) => (CompareOp::Ge, 2),
+                    ('>', _) => (CompareOp::Gt, 1),
+                    _ => return Err(anyhow!("unexpected character '!' at position {}", i + 1)),
+                };
+                i += len;
+                tokens.push((start, Token::Op(op)));
+                continue;
+            }
+            '\'' | '"' | '`' => {
+                let mut text = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        // a doubled quote is an escaped quote
+                        Some(&q) if q == c && chars.get(i + 1) == Some(&c) => {
+                            text.push(c);
+                            i += 2;
+                        }
+                        Some(&q) if q == c => break,
+                        Some(&ch) => {
+                            text.push(ch);
+                            i += 1;
+                        }
+                        None => {
+                            return Err(anyhow!(
+                                "unterminated {} starting at position {}",
+                                if c == '`' { "name" } else { "string" },
+                                start + 1
+                            ))
+                        }
+                    }
+                }
+                i += 1;
+                tokens.push((
+                    start,
+                    if c == '`' {
+                        Token::Name(text)
+                    } else {
+                        Token::Text(text)
+                    },
+                ));
+                continue;
+            }
+            c if c.is_ascii_digit()
+                || (c == '-' && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit())) =>
+            {
+                i += 1;
+                while i < chars.len() && is_word_char(chars[i]) {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                let token = match word.parse::<i64>() {
+                    Ok(n) => Token::Number(n.into()),
+                    Err(_) => match word.parse::<f64>().ok().and_then(Number::from_f64) {
+                        Some(n) => Token::Number(n),
+                        // e.g. a date such as 2019-01-01, compared as text
+                        None => Token::Word(word),
+                    },
+                };
+                tokens.push((start, token));
+                continue;
+            }
+            c if is_word_char(c) => {
+                while i < chars.len() && is_word_char(chars[i]) {
+                    i += 1;
+                }
+                tokens.push((start, Token::Word(chars[start..i].iter().collect())));
+                continue;
+            }
+            c => {
+                return Err(anyhow!(
+                    "unexpected character {:?} at position {}",
+                    c,
+                    i + 1
+                ))
+            }
+        };
+        i += 1;
+        tokens.push((start, token));
+    }
+    Ok(tokens)
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '.' | '-' | ':' | '+')
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(w) => write!(f, "'{}'", w),
+            Token::Name(n) => write!(f, "`{}`", n),
+            Token::Text(s) => write!(f, "{:?}", s),
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Op(op) => write!(f, "'{}'", op),
+            Token::LParen => write!(f, "'('"),
+            Token::RParen => write!(f, "')'"),
+            Token::Comma => write!(f, "','"),
+            Token::Star => write!(f, "'*'"),
+        }
+    }
+}
+
+impl fmt::Display for CompareOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            CompareOp::Eq => "=",
+            CompareOp::Ne => "!=",
+            CompareOp::Lt => "<",
+            CompareOp::Le => "<=",
+            CompareOp::Gt => ">",
+            CompareOp::Ge => ">=",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn record(value: Value) -> Record {
+        match value {
+            Value::Object(record) => record,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_parse_precedence() -> Result<()> {
+        let expr: Expr = "a = 1 or b = 2 and not c = 3".parse()?;
+        let compare = |column: &str, value: i64| Expr::Compare {
+            column: column.to_string(),
+            op: CompareOp::Eq,
+            value: value.into(),
+        };
+        assert_eq!(
+            expr,
+            Expr::Or(
+                Box::new(compare("a", 1)),
+                Box::new(Expr::And(
+                    Box::new(compare("b", 2)),
+                    Box::new(Expr::Not(Box::new(compare("c", 3))))
+                ))
+            )
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_matches() -> Result<()> {
+        let player = record(json!({
+            "Name": "Mattia Perin",
+            "Position": "Goalkeeper",
+            "Kit Number": 37,
+            "Joined": null,
+        }));
+        for (expr, expected) in [
+            ("Position == Goalkeeper", true),
+            ("Position != 'Goalkeeper'", false),
+            ("`Kit Number` >= 37 AND `Kit Number` < 40", true),
+            ("\"Kit Number\" > 37", false),
+            ("Name LIKE '%perin'", true),
+            ("Name NOT LIKE 'M_ttia%'", false),
+            ("Position IN (Defender, Goalkeeper)", true),
+            (
+                "Joined IS NULL and (Name = x or Position = Goalkeeper)",
+                true,
+            ),
+            ("Joined IS NOT NULL", false),
+            ("Joined = null", true),
+            ("Joined < 2020-01-01", false),
+        ] {
+            let parsed: Expr = expr.parse()?;
+            assert_eq!(parsed.matches(&player), expected, "{}", expr);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for (expr, message) in [
+            (
+                "Position ==",
+                "expected a value at position 12, found end of input",
+            ),
+            (
+                "Position Goalkeeper",
+                "expected a comparison operator at position 10, found 'Goalkeeper'",
+            ),
+            ("(a = 1", "expected ')' at position 7, found end of input"),
+            ("a = 'x", "unterminated string starting at position 5"),
+            ("a = 1 b", "expected end of input at position 7, found 'b'"),
+            ("a ~ 1", "unexpected character '~' at position 3"),
+            ("a NOT = 1", "expected LIKE or IN at position 7, found '='"),
+        ] {
+            let err = expr.parse::<Expr>().unwrap_err();
+            assert_eq!(err.to_string(), message, "{}", expr);
+        }
+    }
+
+    #[test]
+    fn test_check_columns() -> Result<()> {
+        let columns = vec![Column {
+            name: "Name".to_string(),
+            ty: crate::process::csv_reader::ColumnType::String,
+        }];
+        assert!("Name = x".parse::<Expr>()?.check_columns(&columns).is_ok());
+        let err = "Name = x or Age > 3"
+            .parse::<Expr>()?
+            .check_columns(&columns);
+        assert_eq!(err.unwrap_err().to_string(), "unknown column \"Age\"");
+        Ok(())
+    }
+
+    #[test]
+    fn test_compare_and_sort_order() {
+        assert_eq!(compare(&json!("10"), &json!(9)), Some(Ordering::Greater));
+        assert_eq!(compare(&json!(2), &json!(2.5)), Some(Ordering::Less));
+        assert_eq!(compare(&json!(null), &json!(1)), None);
+        assert_eq!(compare(&json!("10"), &json!("9")), Some(Ordering::Greater));
+        assert_eq!(compare(&json!("-2"), &json!("1e1")), Some(Ordering::Less));
+        assert_eq!(compare(&json!("007"), &json!("7")), Some(Ordering::Less));
+        assert_eq!(compare(&json!("b"), &json!("10")), Some(Ordering::Greater));
+        assert_eq!(sort_order(&json!(null), &json!(1)), Ordering::Greater);
+        assert_eq!(sort_order(&json!("abc"), &json!(1)), Ordering::Greater);
+        assert_eq!(sort_order(&json!("10"), &json!(9)), Ordering::Greater);
+        assert_eq!(sort_order(&json!(3), &json!(2.5)), Ordering::Greater);
+        assert_eq!(sort_order(&json!(2), &json!(2.0)), Ordering::Equal);
+        assert_eq!(sort_order(&json!(true), &json!(0)), Ordering::Less);
+    }
+
+    #[test]
+    fn test_sort_mixed_types() {
+        // "b" < "c" as text but "10" > "9" as numbers, which made the old order cyclic
+        let values = [
+            json!("10"),
+            json!("b"),
+            json!(9),
+            json!(null),
+            json!("9.5"),
+            json!(false),
+            json!("a"),
+            json!([1]),
+            json!(-1.5),
+            json!(i64::MAX),
+            json!(u64::MAX),
+            json!(1e300),
+        ];
+        let mut records: Vec<Record> = values
+            .iter()
+            .cycle()
+            .take(200)
+            .map(|v| json!({ "a": v }).as_object().unwrap().clone())
+            .collect();
+        sort_records(&mut records, &[SortKey::from_str("a").unwrap()]);
+        let sorted: Vec<&Value> = records.iter().map(|r| &r["a"]).collect();
+        for pair in sorted.windows(2) {
+            assert!(sort_order(pair[0], pair[1]).is_le(), "{:?}", pair);
+        }
+        let distinct: Vec<&Value> = sorted.iter().copied().fold(Vec::new(), |mut acc, v| {
+            if acc.last() != Some(&v) {
+                acc.push(v);
+            }
+            acc
+        });
+        assert_eq!(
+            distinct,
+            [
+                &json!(false),
+                &json!(-1.5),
+                &json!(9),
+                &json!("9.5"),
+                &json!("10"),
+                &json!(i64::MAX),
+                &json!(u64::MAX),
+                &json!(1e300),
+                &json!("a"),
+                &json!("b"),
+                &json!([1]),
+                &json!(null),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_like() {
+        assert!(like("Centre-Back", "%back"));
+        assert!(like("abc", "a_c"));
+        assert!(like("abc", "%%"));
+        assert!(!like("abc", "a_"));
+        assert!(like("aXbXc", "a%b%c"));
+    }
+}
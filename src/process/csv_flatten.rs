@@ -0,0 +1,517 @@
+use crate::opt::InputFormat;
+use crate::process::csv_reader::{Column, ColumnType, Record, RecordStream};
+use crate::utils::get_decompressed_reader;
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+
+/// open a JSON array, YAML sequence or NDJSON file of objects as flat records
+///
+/// The header is the union of every record's keys in first-seen order, so the whole document
+/// is loaded before the first record is returned.
+pub fn open_structured(input: &str, from: InputFormat) -> Result<RecordStream> {
+    let reader = BufReader::new(get_decompressed_reader(input)?);
+    let values: Vec<Value> = match from {
+        InputFormat::Json => serde_json::from_reader(reader)?,
+        InputFormat::Yaml => serde_yaml::from_reader(reader)?,
+        InputFormat::Ndjson => reader
+            .lines()
+            .filter(|line| !matches!(line, Ok(l) if l.trim().is_empty()))
+            .map(|line| Ok(serde_json::from_str(&line?)?))
+            .collect::<Result<_>>()?,
+        InputFormat::Csv | InputFormat::Xlsx | InputFormat::Ods => {
+            return Err(anyhow!("{} is not a structured format", from))
+        }
+    };
+
+    let records = values
+        .into_iter()
+        .enumerate()
+        .map(|(i, value)| match value {
+            Value::Object(obj) => flatten(obj).map_err(|e| anyhow!("record {}: {}", i + 1, e)),
+            _ => Err(anyhow!("record {} is not an object", i + 1)),
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut columns: Vec<Column> = Vec::new();
+    let mut index = HashMap::new();
+    for record in &records {
+        for (key, value) in record {
+            let ty = ColumnType::of_value(value);
+            match index.get(key) {
+                Some(&i) => {
+                    let column: &mut Column = &mut columns[i];
+                    column.ty = column.ty.merge(ty);
+                }
+                None => {
+                    index.insert(key.clone(), columns.len());
+                    columns.push(Column {
+                        name: key.clone(),
+                        ty,
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(RecordStream {
+        columns,
+        rows: Box::new(records.into_iter().map(Ok)),
+    })
+}
+
+/// flatten nested objects into dotted keys (`address.city`), arrays are kept as JSON text
+///
+/// A key that already contains a dot can collide with a nested one, as in
+/// `{"a.b": 1, "a": {"b": 2}}`, which is an error rather than losing one of the values.
+pub fn flatten(record: Record) -> Result<Record> {
+    let mut flat = Record::new();
+    flatten_into(&mut flat, None, record)?;
+    Ok(flat)
+}
+
+fn flatten_into(flat: &mut Record, prefix: Option<&str>, record: Record) -> Result<()> {
+    for (key, value) in record {
+        let key = match prefix {
+            Some(prefix) => format!("{}.{}", prefix, key),
+            None => key,
+        };
+        let value = match value {
+            Value::Object(obj) if !obj.is_empty() => {
+                flatten_into(flat, Some(&key), obj)?;
+                continue;
+            }
+            Value::Object(_) | Value::Array(_) => Value::String(value.to_string()),
+            value => value,
+        };
+        if flat.contains_key(&key) {
+            return Err(anyhow!(
+                "key {:?} appears twice once nested keys are flattened",
+                key
+            ));
+        }
+        flat.insert(key, value);
+    }
+    Ok(())
+}
+
+/// rebuild nested objects and arrays from `address.city` and `tags[0]` style column names
+///
+/// Headers are checked up front, so a path that is both a value and an object (`address` next to
+/// `address.city`) or both an object and an array fails before the first record. A header that
+/// is not a valid path, such as `No.`, is kept as a plain key.
+pub fn unflatten(stream: RecordStream) -> Result<RecordStream> {
+    let paths: Vec<Vec<Segment>> = stream
+        .columns
+        .iter()
+        .map(|column| {
+            parse_path(&column.name).unwrap_or_else(|| vec![Segment::Key(column.name.clone())])
+        })
+        .collect();
+    check_shapes(&stream.columns, &paths)?;
+
+    let mut columns: Vec<Column> = Vec::new();
+    for (column, path) in stream.columns.iter().zip(&paths) {
+        let Segment::Key(name) = &path[0] else {
+            unreachable!("paths start with a key")
+        };
+        if !columns.iter().any(|c| &c.name == name) {
+            let ty = if path.len() == 1 {
+                column.ty
+            } else {
+                ColumnType::String
+            };
+            columns.push(Column {
+                name: name.clone(),
+                ty,
+            });
+        }
+    }
+
+    let names: Vec<String> = stream.columns.iter().map(|c| c.name.clone()).collect();
+    let rows = stream.rows.map(move |record| {
+        let mut record = record?;
+        let mut nested = Value::Object(Record::new());
+        for (name, path) in names.iter().zip(&paths) {
+            let value = record.remove(name).unwrap_or(Value::Null);
+            insert(&mut nested, path, value);
+        }
+        match nested {
+            Value::Object(obj) => Ok(obj),
+            _ => unreachable!("the root is an object"),
+        }
+    });
+    Ok(RecordStream {
+        columns,
+        rows: Box::new(rows),
+    })
+}
+
+/// arrays rebuilt by `--unflatten` hold at most this many items, so a header like
+/// `tags[99999999999]` is an error rather than an allocation that never finishes
+const MAX_INDEX: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// split `a.b[0].c` into segments, `None` unless every key is non-empty and every index a number
+fn parse_path(name: &str) -> Option<Vec<Segment>> {
+    let mut segments = Vec::new();
+    for part in name.split('.') {
+        let (key, mut rest) = part.split_at(part.find('[').unwrap_or(part.len()));
+        if key.is_empty() {
+            return None;
+        }
+        segments.push(Segment::Key(key.to_string()));
+        while !rest.is_empty() {
+            let (index, tail) = rest.strip_prefix('[')?.split_once(']')?;
+            segments.push(Segment::Index(index.parse().ok()?));
+            rest = tail;
+        }
+    }
+    Some(segments)
+}
+
+#[derive(Default)]
+enum Shape {
+    #[default]
+    Empty,
+    Value(usize),
+    Object(HashMap<String, Shape>),
+    Array(HashMap<usize, Shape>),
+}
+
+/// make sure no path is both a value and a container, or both an object and an array
+fn check_shapes(columns: &[Column], paths: &[Vec<Segment>]) -> Result<()> {
+    let mut root = Shape::Object(HashMap::new());
+    for (i, path) in paths.iter().enumerate() {
+        if let Some(index) = path.iter().find_map(|segment| match segment {
+            Segment::Index(index) if *index >= MAX_INDEX => Some(index),
+            _ => None,
+        }) {
+            return Err(anyhow!(
+                "--unflatten: column \"{}\" has index {}, arrays are limited to {} items",
+                columns[i].name,
+                index,
+                MAX_INDEX
+            ));
+        }
+        let mut shape = &mut root;
+        for (depth, segment) in path.iter().enumerate() {
+            if let Shape::Empty = shape {
+                *shape = match segment {
+                    Segment::Key(_) => Shape::Object(HashMap::new()),
+                    Segment::Index(_) => Shape::Array(HashMap::new()),
+                };
+            }
+            shape = match (shape, segment) {
+                (Shape::Object(children), Segment::Key(key)) => {
+                    children.entry(key.clone()).or_default()
+                }
+                (Shape::Array(children), Segment::Index(index)) => {
+                    children.entry(*index).or_default()
+                }
+                (Shape::Value(j), _) => {
+                    return Err(conflict(
+                        columns,
+                        *j,
+                        i,
+                        &path[..depth],
+                        "a value and a container",
+                    ))
+                }
+                (Shape::Object(children), Segment::Index(_)) => {
+                    return Err(conflict(
+                        columns,
+                        first(children.values()),
+                        i,
+                        &path[..depth],
+                        "an object and an array",
+                    ))
+                }
+                (Shape::Array(children), Segment::Key(_)) => {
+                    return Err(conflict(
+                        columns,
+                        first(children.values()),
+                        i,
+                        &path[..depth],
+                        "an object and an array",
+                    ))
+                }
+                (Shape::Empty, _) => unreachable!("empty shapes are filled in above"),
+            };
+        }
+        match shape {
+            Shape::Empty => *shape = Shape::Value(i),
+            Shape::Value(j) => return Err(conflict(columns, *j, i, path, "set twice")),
+            Shape::Object(_) | Shape::Array(_) => {
+                return Err(conflict(
+                    columns,
+                    first(std::iter::once(&*shape)),
+                    i,
+                    path,
+                    "a value and a container",
+                ))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// index of a column that contributes to this shape
+fn first<'a>(mut shapes: impl Iterator<Item = &'a Shape>) -> usize {
+    match shapes.next() {
+        Some(Shape::Value(i)) => *i,
+        Some(Shape::Object(children)) => first(children.values()),
+        Some(Shape::Array(children)) => first(children.values()),
+        Some(Shape::Empty) | None => 0,
+    }
+}
+
+fn conflict(columns: &[Column], a: usize, b: usize, path: &[Segment], what: &str) -> anyhow::Error {
+    anyhow!(
+        "--unflatten: columns \"{}\" and \"{}\" conflict, {} is {}",
+        columns[a].name,
+        columns[b].name,
+        display_path(path),
+        what
+    )
+}
+
+fn display_path(path: &[Segment]) -> String {
+    let mut text = String::new();
+    for segment in path {
+        match segment {
+            Segment::Key(key) if text.is_empty() => text.push_str(key),
+            Segment::Key(key) => {
+                text.push('.');
+                text.push_str(key);
+            }
+            Segment::Index(index) => text.push_str(&format!("[{}]", index)),
+        }
+    }
+    text
+}
+
+/// place a value at a checked path, growing arrays with nulls as needed
+fn insert(target: &mut Value, path: &[Segment], value: Value) {
+    let Some((segment, rest)) = path.split_first() else {
+        *target = value;
+        return;
+    };
+    if target.is_null() {
+        *target = match segment {
+            Segment::Key(_) => Value::Object(Record::new()),
+            Segment::Index(_) => Value::Array(Vec::new()),
+        };
+    }
+    let child = match (target, segment) {
+        (Value::Object(obj), Segment::Key(key)) => obj.entry(key.clone()).or_insert(Value::Null),
+        (Value::Array(items), Segment::Index(index)) => {
+            if items.len() <= *index {
+                items.resize(index + 1, Value::Null);
+            }
+            &mut items[*index]
+        }
+        _ => unreachable!("shapes are checked before records are unflattened"),
+    };
+    insert(child, rest, value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::csv_reader::{open_csv, CsvReaderOptions};
+    use crate::utils::write_temp;
+    use serde_json::json;
+
+    #[test]
+    fn test_flatten() -> Result<()> {
+        let record = json!({
+            "name": "alice",
+            "address": {"city": "Turin", "geo": {"lat": 45.07}},
+            "tags": ["a", "b"],
+        });
+        let Value::Object(record) = record else {
+            unreachable!()
+        };
+        assert_eq!(
+            Value::Object(flatten(record)?),
+            json!({
+                "name": "alice",
+                "address.city": "Turin",
+                "address.geo.lat": 45.07,
+                "tags": "[\"a\",\"b\"]",
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_flatten_collisions() -> Result<()> {
+        for (content, key) in [
+            ("{\"a.b\": 1, \"a\": {\"b\": 2}}", "a.b"),
+            ("{\"a\": {\"b\": 2}, \"a.b\": 1}", "a.b"),
+            ("{\"x\": {\"a.b\": 1, \"a\": {\"b\": 2}}}", "x.a.b"),
+        ] {
+            let path = write_temp(
+                "cli_flatten_collision.json",
+                &format!("[{{}}, {}]", content),
+            )?;
+            let err = open_structured(&path, InputFormat::Json)
+                .err()
+                .unwrap()
+                .to_string();
+            assert_eq!(
+                err,
+                format!(
+                    "record 2: key {:?} appears twice once nested keys are flattened",
+                    key
+                )
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_structured_unions_keys() -> Result<()> {
+        let path = write_temp(
+            "cli_open_structured.ndjson",
+            "{\"b\": 1, \"a\": \"x\"}\n\n{\"c\": true, \"b\": 2.5}\n",
+        )?;
+        let stream = open_structured(&path, InputFormat::Ndjson)?;
+        let columns: Vec<_> = stream
+            .columns
+            .iter()
+            .map(|c| (c.name.as_str(), c.ty))
+            .collect();
+        assert_eq!(
+            columns,
+            vec![
+                ("b", ColumnType::Float),
+                ("a", ColumnType::String),
+                ("c", ColumnType::Bool),
+            ]
+        );
+        assert_eq!(stream.rows.count(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_structured_rejects_scalars() -> Result<()> {
+        let path = write_temp("cli_open_structured.yaml", "- a: 1\n- 2\n")?;
+        assert!(open_structured(&path, InputFormat::Yaml).is_err());
+        Ok(())
+    }
+
+    fn unflatten_csv(name: &str, content: &str) -> Result<Vec<Value>> {
+        let path = write_temp(name, content)?;
+        let stream = unflatten(open_csv(&path, &CsvReaderOptions::default())?)?;
+        stream.rows.map(|r| Ok(Value::Object(r?))).collect()
+    }
+
+    #[test]
+    fn test_parse_path() {
+        use Segment::*;
+        assert_eq!(
+            parse_path("address.geo[1][0].lat"),
+            Some(vec![
+                Key("address".into()),
+                Key("geo".into()),
+                Index(1),
+                Index(0),
+                Key("lat".into()),
+            ])
+        );
+        assert_eq!(
+            parse_path("Kit Number"),
+            Some(vec![Key("Kit Number".into())])
+        );
+        assert_eq!(parse_path("No."), None);
+        assert_eq!(parse_path("tags[x]"), None);
+        assert_eq!(parse_path("[0]"), None);
+    }
+
+    #[test]
+    fn test_unflatten() -> Result<()> {
+        let records = unflatten_csv(
+            "cli_unflatten.csv",
+            "name,address.city,address.geo.lat,tags[1],tags[0],No.\nalice,Turin,45.07,b,a,1\nbob,,,,c,\n",
+        )?;
+        assert_eq!(
+            records,
+            vec![
+                json!({
+                    "name": "alice",
+                    "address": {"city": "Turin", "geo": {"lat": 45.07}},
+                    "tags": ["a", "b"],
+                    "No.": 1,
+                }),
+                json!({
+                    "name": "bob",
+                    "address": {"city": null, "geo": {"lat": null}},
+                    "tags": ["c", null],
+                    "No.": null,
+                }),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_unflatten_round_trips_flatten() -> Result<()> {
+        let record = json!({"name": "alice", "address": {"city": "Turin", "zip": 10121}});
+        let Value::Object(obj) = record.clone() else {
+            unreachable!()
+        };
+        let stream = RecordStream {
+            columns: ["name", "address.city", "address.zip"]
+                .iter()
+                .map(|name| Column {
+                    name: name.to_string(),
+                    ty: ColumnType::String,
+                })
+                .collect(),
+            rows: Box::new(std::iter::once(flatten(obj))),
+        };
+        let stream = unflatten(stream)?;
+        let names: Vec<_> = stream.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["name", "address"]);
+        let rows: Vec<Record> = stream.rows.collect::<Result<_>>()?;
+        assert_eq!(Value::Object(rows[0].clone()), record);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unflatten_conflicts() {
+        let err = |content: &str| {
+            unflatten_csv("cli_unflatten_conflict.csv", content)
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            err("address,address.city\nx,y\n"),
+            "--unflatten: columns \"address\" and \"address.city\" conflict, address is a value and a container"
+        );
+        assert_eq!(
+            err("address.city,address\nx,y\n"),
+            "--unflatten: columns \"address.city\" and \"address\" conflict, address is a value and a container"
+        );
+        assert_eq!(
+            err("tags[0],tags.first\nx,y\n"),
+            "--unflatten: columns \"tags[0]\" and \"tags.first\" conflict, tags is an object and an array"
+        );
+        assert_eq!(
+            err("a.b,a[0].b\nx,y\n"),
+            "--unflatten: columns \"a.b\" and \"a[0].b\" conflict, a is an object and an array"
+        );
+        assert_eq!(
+            err("id,tags[99999999999]\nx,y\n"),
+            "--unflatten: column \"tags[99999999999]\" has index 99999999999, arrays are limited to 10000 items"
+        );
+    }
+}
@@ -0,0 +1,542 @@
+use crate::opt::OutputFormat;
+use crate::process::csv_reader::{Column, ColumnType, Record, RecordStream};
+use crate::process::csv_writer::{write_records, CsvWriterOptions};
+use anyhow::{anyhow, Result};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{Duration, NaiveDate};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde::Deserialize;
+use serde_json::Value;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+const FIRST_NAMES: &[&str] = &[
+    "Alessandro",
+    "Alice",
+    "Andrea",
+    "Anna",
+    "Carlos",
+    "Chiara",
+    "Daniel",
+    "Elena",
+    "Emma",
+    "Federico",
+    "Francesca",
+    "Gianluigi",
+    "Giorgio",
+    "Giulia",
+    "Hugo",
+    "Isabel",
+    "João",
+    "Leonardo",
+    "Lucas",
+    "Luca",
+    "Marco",
+    "Maria",
+    "Martina",
+    "Matteo",
+    "Miralem",
+    "Paulo",
+    "Sara",
+    "Sofia",
+    "Thomas",
+    "Wojciech",
+];
+
+const LAST_NAMES: &[&str] = &[
+    "Bernardeschi",
+    "Bianchi",
+    "Bonucci",
+    "Chiellini",
+    "Colombo",
+    "Costa",
+    "Dybala",
+    "Esposito",
+    "Ferrari",
+    "Fernández",
+    "García",
+    "Greco",
+    "Higuaín",
+    "Kean",
+    "Marino",
+    "Martínez",
+    "Moreau",
+    "Müller",
+    "Nowak",
+    "Pjanić",
+    "Ricci",
+    "Romano",
+    "Rossi",
+    "Rugani",
+    "Russo",
+    "Sandro",
+    "Silva",
+    "Szczęsny",
+    "Verdi",
+    "Zanetti",
+];
+
+/// columns to generate, YAML unless the file ends in .json
+#[derive(Debug, Clone, Deserialize)]
+pub struct GenerateSchema {
+    /// makes the output reproducible, `--seed` takes precedence
+    pub seed: Option<u64>,
+    pub columns: Vec<GeneratedColumn>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GeneratedColumn {
+    pub name: String,
+    /// share of null values, from 0 to 1
+    #[serde(default)]
+    pub null_rate: f64,
+    #[serde(flatten)]
+    pub generator: Generator,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "generator", rename_all = "snake_case")]
+pub enum Generator {
+    /// first and last name
+    Name,
+    FirstName,
+    LastName,
+    /// a day between `min` and `max`, written as `%Y-%m-%d` or `format`
+    Date {
+        min: NaiveDate,
+        max: NaiveDate,
+        format: Option<String>,
+    },
+    Int {
+        min: i64,
+        max: i64,
+    },
+    Float {
+        min: f64,
+        max: f64,
+        #[serde(default = "default_decimals")]
+        decimals: u32,
+    },
+    Bool,
+    /// one of `values`
+    Enum {
+        values: Vec<String>,
+    },
+    /// a random version 4 UUID
+    Uuid,
+    /// a string matching the regular expression `pattern`
+    Regex {
+        pattern: String,
+    },
+    /// 1, 2, 3... or counting from `start`
+    Sequence {
+        #[serde(default = "default_start")]
+        start: i64,
+    },
+}
+
+fn default_decimals() -> u32 {
+    2
+}
+
+fn default_start() -> i64 {
+    1
+}
+
+impl GenerateSchema {
+    /// load a schema, YAML unless the extension is .json
+    pub fn load(path: &str) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+        let is_json = Path::new(path)
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        let schema = if is_json {
+            serde_json::from_str(&content)?
+        } else {
+            serde_yaml::from_str(&content)?
+        };
+        Ok(schema)
+    }
+}
+
+pub fn process_csv_generate(
+    schema: &GenerateSchema,
+    rows: usize,
+    seed: Option<u64>,
+    output: &str,
+    format: OutputFormat,
+    writer_opts: &CsvWriterOptions,
+) -> Result<usize> {
+    let stream = generate(schema, rows, seed)?;
+    write_records(stream, format, output, writer_opts)
+}
+
+/// a lazy stream of `rows` fake records, the same seed gives the same records
+pub fn generate(schema: &GenerateSchema, rows: usize, seed: Option<u64>) -> Result<RecordStream> {
+    let columns: Vec<(String, f64, ValueGenerator)> = schema
+        .columns
+        .iter()
+        .map(|column| {
+            if !(0.0..=1.0).contains(&column.null_rate) {
+                return Err(anyhow!(
+                    "column {:?}: null_rate must be between 0 and 1",
+                    column.name
+                ));
+            }
+            let generator = ValueGenerator::new(&column.generator)
+                .map_err(|e| anyhow!("column {:?}: {}", column.name, e))?;
+            if let ValueGenerator::Sequence(start) = generator {
+                let last = i64::try_from(rows.saturating_sub(1))
+                    .ok()
+                    .and_then(|n| start.checked_add(n));
+                if last.is_none() {
+                    return Err(anyhow!(
+                        "column {:?}: a sequence from {} overflows within {} rows",
+                        column.name,
+                        start,
+                        rows
+                    ));
+                }
+            }
+            Ok((column.name.clone(), column.null_rate, generator))
+        })
+        .collect::<Result<_>>()?;
+    let header = schema
+        .columns
+        .iter()
+        .zip(&columns)
+        .map(|(column, (_, _, generator))| Column {
+            name: column.name.clone(),
+            ty: generator.ty(),
+        })
+        .collect();
+
+    let mut rng = match seed.or(schema.seed) {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    let rows = (0..rows).map(move |i| {
+        let mut record = Record::new();
+        for (name, null_rate, generator) in &columns {
+            let value = if *null_rate > 0.0 && rng.gen_bool(*null_rate) {
+                Value::Null
+            } else {
+                generator.value(&mut rng, i)
+            };
+            record.insert(name.clone(), value);
+        }
+        Ok(record)
+    });
+    Ok(RecordStream {
+        columns: header,
+        rows: Box::new(rows),
+    })
+}
+
+/// a generator with its settings checked and its regex compiled
+pub(crate) enum ValueGenerator {
+    Name,
+    FirstName,
+    LastName,
+    Date {
+        min: NaiveDate,
+        days: i64,
+        format: String,
+    },
+    Int {
+        min: i64,
+        max: i64,
+    },
+    Float {
+        min: f64,
+        max: f64,
+        scale: f64,
+    },
+    Bool,
+    Enum(Vec<String>),
+    Uuid,
+    Regex(Box<rand_regex::Regex>),
+    Sequence(i64),
+}
+
+/// the most times `*` and `+` repeat in regex generators
+const MAX_REPEAT: u32 = 16;
+
+impl ValueGenerator {
+    pub(crate) fn new(generator: &Generator) -> Result<Self> {
+        let generator = match generator {
+            Generator::Name => ValueGenerator::Name,
+            Generator::FirstName => ValueGenerator::FirstName,
+            Generator::LastName => ValueGenerator::LastName,
+            Generator::Date { min, max, format } => {
+                if min > max {
+                    return Err(anyhow!("min {} is after max {}", min, max));
+                }
+                let format = format.clone().unwrap_or_else(|| "%Y-%m-%d".to_string());
+                // a valid format can still ask for a time, which a date can't give
+                if StrftimeItems::new(&format).any(|item| item == Item::Error)
+                    || write!(String::new(), "{}", min.format(&format)).is_err()
+                {
+                    return Err(anyhow!("invalid date format {:?}", format));
+                }
+                ValueGenerator::Date {
+                    min: *min,
+                    days: (*max - *min).num_days(),
+                    format,
+                }
+            }
+            Generator::Int { min, max } => {
+                if min > max {
+                    return Err(anyhow!("min {} is greater than max {}", min, max));
+                }
+                ValueGenerator::Int {
+                    min: *min,
+                    max: *max,
+                }
+            }
+            Generator::Float { min, max, decimals } => {
+                if !min.is_finite() || !max.is_finite() {
+                    return Err(anyhow!(
+                        "min {} and max {} must be finite numbers",
+                        min,
+                        max
+                    ));
+                }
+                if min > max {
+                    return Err(anyhow!("min {} is greater than max {}", min, max));
+                }
+                if !(max - min).is_finite() {
+                    return Err(anyhow!("the range from min to max is too wide"));
+                }
+                ValueGenerator::Float {
+                    min: *min,
+                    max: *max,
+                    scale: 10f64.powi((*decimals).min(15) as i32),
+                }
+            }
+            Generator::Bool => ValueGenerator::Bool,
+            Generator::Enum { values } => {
+                if values.is_empty() {
+                    return Err(anyhow!("enum needs at least one value"));
+                }
+                ValueGenerator::Enum(values.clone())
+            }
+            Generator::Uuid => ValueGenerator::Uuid,
+            Generator::Regex { pattern } => {
+                let regex = rand_regex::Regex::compile(pattern, MAX_REPEAT)?;
+                ValueGenerator::Regex(Box::new(regex))
+            }
+            Generator::Sequence { start } => ValueGenerator::Sequence(*start),
+        };
+        Ok(generator)
+    }
+
+    pub(crate) fn ty(&self) -> ColumnType {
+        match self {
+            // other formats are only text to the writers
+            ValueGenerator::Date { format, .. } if format == "%Y-%m-%d" => ColumnType::Date,
+            ValueGenerator::Int { .. } | ValueGenerator::Sequence(_) => ColumnType::Integer,
+            ValueGenerator::Float { .. } => ColumnType::Float,
+            ValueGenerator::Bool => ColumnType::Bool,
+            _ => ColumnType::String,
+        }
+    }
+
+    /// a value for row `i`
+    pub(crate) fn value(&self, rng: &mut StdRng, i: usize) -> Value {
+        match self {
+            ValueGenerator::Name => Value::String(format!(
+                "{} {}",
+                pick(rng, FIRST_NAMES),
+                pick(rng, LAST_NAMES)
+            )),
+            ValueGenerator::FirstName => Value::String(pick(rng, FIRST_NAMES).to_string()),
+            ValueGenerator::LastName => Value::String(pick(rng, LAST_NAMES).to_string()),
+            ValueGenerator::Date { min, days, format } => {
+                let date = *min + Duration::days(rng.gen_range(0..=*days));
+                Value::String(date.format(format).to_string())
+            }
+            ValueGenerator::Int { min, max } => Value::from(rng.gen_range(*min..=*max)),
+            ValueGenerator::Float { min, max, scale } => {
+                let value = rng.gen_range(*min..=*max);
+                Value::from((value * scale).round() / scale)
+            }
+            ValueGenerator::Bool => Value::Bool(rng.gen()),
+            ValueGenerator::Enum(values) => Value::String(pick(rng, values).to_string()),
+            ValueGenerator::Uuid => Value::String(uuid_v4(rng.gen())),
+            ValueGenerator::Regex(regex) => Value::String(rng.sample::<String, _>(regex.as_ref())),
+            ValueGenerator::Sequence(start) => Value::from(start + i as i64),
+        }
+    }
+}
+
+fn pick<'a, T: AsRef<str>>(rng: &mut StdRng, values: &'a [T]) -> &'a str {
+    values.choose(rng).expect("lists are never empty").as_ref()
+}
+
+/// format random bytes as a version 4, variant 1 UUID
+fn uuid_v4(mut bytes: [u8; 16]) -> String {
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::temp_path;
+    use regex::Regex;
+
+    const SCHEMA: &str = r#"
+seed: 42
+columns:
+  - name: Id
+    generator: sequence
+  - name: Name
+    generator: name
+  - name: Position
+    generator: enum
+    values: [Goalkeeper, Defender, Midfield, Forward]
+  - name: DOB
+    generator: date
+    min: 1985-01-01
+    max: 2004-12-31
+  - name: Kit Number
+    generator: int
+    min: 1
+    max: 99
+  - name: Rating
+    generator: float
+    min: 5
+    max: 10
+    decimals: 1
+  - name: Active
+    generator: bool
+  - name: Uuid
+    generator: uuid
+  - name: Code
+    generator: regex
+    pattern: "[A-Z]{3}-[0-9]{4}"
+  - name: Nickname
+    generator: first_name
+    null_rate: 0.5
+"#;
+
+    fn records(schema: &GenerateSchema, seed: Option<u64>) -> Result<Vec<Record>> {
+        generate(schema, 200, seed)?.rows.collect()
+    }
+
+    #[test]
+    fn test_generate() -> Result<()> {
+        let schema: GenerateSchema = serde_yaml::from_str(SCHEMA)?;
+        let stream = generate(&schema, 200, None)?;
+        let types: Vec<_> = stream.columns.iter().map(|c| c.ty).collect();
+        assert_eq!(types[3], ColumnType::Date);
+        assert_eq!(types[4], ColumnType::Integer);
+        let rows: Vec<Record> = stream.rows.collect::<Result<_>>()?;
+        assert_eq!(rows.len(), 200);
+
+        let uuid =
+            Regex::new("^[0-9a-f]{8}-[0-9a-f]{4}-4[0-9a-f]{3}-[89ab][0-9a-f]{3}-[0-9a-f]{12}$")?;
+        let code = Regex::new("^[A-Z]{3}-[0-9]{4}$")?;
+        let mut nulls = 0;
+        for (i, row) in rows.iter().enumerate() {
+            assert_eq!(row["Id"], i as i64 + 1);
+            assert_eq!(row["Name"].as_str().unwrap().split(' ').count(), 2);
+            let kit = row["Kit Number"].as_i64().unwrap();
+            assert!((1..=99).contains(&kit));
+            let rating = row["Rating"].as_f64().unwrap();
+            assert!((5.0..=10.0).contains(&rating));
+            assert_eq!((rating * 10.0).round() / 10.0, rating);
+            let dob = NaiveDate::parse_from_str(row["DOB"].as_str().unwrap(), "%Y-%m-%d")?;
+            assert!(dob.format("%Y").to_string().as_str() >= "1985");
+            assert!(uuid.is_match(row["Uuid"].as_str().unwrap()));
+            assert!(code.is_match(row["Code"].as_str().unwrap()));
+            nulls += row["Nickname"].is_null() as usize;
+        }
+        assert!((50..150).contains(&nulls), "{} nulls", nulls);
+        Ok(())
+    }
+
+    #[test]
+    fn test_seed_is_reproducible() -> Result<()> {
+        let schema: GenerateSchema = serde_yaml::from_str(SCHEMA)?;
+        assert_eq!(records(&schema, None)?, records(&schema, None)?);
+        assert_eq!(records(&schema, Some(7))?, records(&schema, Some(7))?);
+        assert_ne!(records(&schema, None)?, records(&schema, Some(7))?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_custom_date_format_is_text() -> Result<()> {
+        let schema: GenerateSchema = serde_yaml::from_str(
+            "columns: [{name: d, generator: date, min: 2000-01-01, max: 2001-01-01, format: '%d/%m/%Y'}]",
+        )?;
+        let stream = generate(&schema, 20, None)?;
+        assert_eq!(stream.columns[0].ty, ColumnType::String);
+        let output = temp_path("cli_generate_dates.parquet");
+        let output = output.to_string_lossy();
+        write_records(stream, OutputFormat::Parquet, &output, &Default::default())?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_generator_errors() {
+        let err = |yaml: &str| {
+            let schema: GenerateSchema = serde_yaml::from_str(yaml).unwrap();
+            generate(&schema, 1, None).err().unwrap().to_string()
+        };
+        assert_eq!(
+            err("columns: [{name: n, generator: int, min: 5, max: 1}]"),
+            "column \"n\": min 5 is greater than max 1"
+        );
+        assert_eq!(
+            err("columns: [{name: n, generator: enum, values: []}]"),
+            "column \"n\": enum needs at least one value"
+        );
+        assert_eq!(
+            err("columns: [{name: n, generator: bool, null_rate: 2}]"),
+            "column \"n\": null_rate must be between 0 and 1"
+        );
+        assert_eq!(
+            err("columns: [{name: n, generator: date, min: 2000-01-01, max: 2001-01-01, format: '%Q'}]"),
+            "column \"n\": invalid date format \"%Q\""
+        );
+        assert_eq!(
+            err("columns: [{name: n, generator: date, min: 2000-01-01, max: 2001-01-01, format: '%Y %H:%M'}]"),
+            "column \"n\": invalid date format \"%Y %H:%M\""
+        );
+        let schema: GenerateSchema = serde_yaml::from_str(
+            "columns: [{name: n, generator: sequence, start: 9223372036854775806}]",
+        )
+        .unwrap();
+        assert!(generate(&schema, 2, None).is_ok());
+        assert_eq!(
+            generate(&schema, 3, None).err().unwrap().to_string(),
+            "column \"n\": a sequence from 9223372036854775806 overflows within 3 rows"
+        );
+        assert_eq!(
+            err("columns: [{name: n, generator: float, min: 0, max: .inf}]"),
+            "column \"n\": min 0 and max inf must be finite numbers"
+        );
+        assert_eq!(
+            err("columns: [{name: n, generator: float, min: -1e308, max: 1e308}]"),
+            "column \"n\": the range from min to max is too wide"
+        );
+        assert!(
+            serde_yaml::from_str::<GenerateSchema>("columns: [{name: n, generator: phone}]")
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,394 @@
+use crate::opt::{InputFormat, JoinKind, OutputFormat};
+use crate::process::csv_convert::open_input;
+use crate::process::csv_reader::{Column, CsvReaderOptions, Record, RecordStream};
+use crate::process::csv_writer::{write_records, CsvWriterOptions};
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::rc::Rc;
+
+#[derive(Debug, Clone)]
+pub struct JoinOptions {
+    /// pairs of left and right key columns
+    pub on: Vec<(String, String)>,
+    pub kind: JoinKind,
+}
+
+/// an output column and where its value comes from, key columns take the left value and fall
+/// back to the right one for unmatched right records
+struct OutputColumn {
+    name: String,
+    left: Option<String>,
+    right: Option<String>,
+}
+
+/// join two inputs, loading the smaller file into a hash table and streaming the larger one
+pub fn process_csv_join(
+    left: (&str, InputFormat),
+    right: (&str, InputFormat),
+    opts: &CsvReaderOptions,
+    join_opts: &JoinOptions,
+    output: &str,
+    format: OutputFormat,
+    writer_opts: &CsvWriterOptions,
+) -> Result<()> {
+    // stdin can't be measured, so it is always the streamed side
+    let size = |path: &str| match path {
+        "-" => u64::MAX,
+        path => fs::metadata(path).map(|m| m.len()).unwrap_or(u64::MAX),
+    };
+    let build_left = size(left.0) < size(right.0);
+    let left = open_input(left.0, left.1, opts)?;
+    let right = open_input(right.0, right.1, opts)?;
+    let stream = join(left, right, join_opts, build_left)?;
+    write_records(stream, format, output, writer_opts)?;
+    Ok(())
+}
+
+/// hash join two streams, `build_left` picks the side held in memory
+///
+/// Records come out in the order of the streamed side, followed by the unmatched records of the
+/// held side for outer joins. Null keys never match, like in SQL.
+pub fn join(
+    left: RecordStream,
+    right: RecordStream,
+    opts: &JoinOptions,
+    build_left: bool,
+) -> Result<RecordStream> {
+    if opts.on.is_empty() {
+        return Err(anyhow!("at least one join column is required"));
+    }
+    for (l, r) in &opts.on {
+        if !left.columns.iter().any(|c| &c.name == l) {
+            return Err(anyhow!("left input has no column {:?}", l));
+        }
+        if !right.columns.iter().any(|c| &c.name == r) {
+            return Err(anyhow!("right input has no column {:?}", r));
+        }
+    }
+    let (columns, layout) = output_columns(&left.columns, &right.columns, &opts.on);
+    let layout = Rc::new(layout);
+    let left_keys: Vec<String> = opts.on.iter().map(|(l, _)| l.clone()).collect();
+    let right_keys: Vec<String> = opts.on.iter().map(|(_, r)| r.clone()).collect();
+
+    // keep-unmatched flags for the (probe, build) sides
+    let (keep_left, keep_right) = match opts.kind {
+        JoinKind::Inner => (false, false),
+        JoinKind::Left => (true, false),
+        JoinKind::Right => (false, true),
+        JoinKind::Full => (true, true),
+    };
+    let (build, probe, build_keys, probe_keys, keep_probe, keep_build) = if build_left {
+        (left, right, left_keys, right_keys, keep_right, keep_left)
+    } else {
+        (right, left, right_keys, left_keys, keep_left, keep_right)
+    };
+
+    let records = build.rows.collect::<Result<Vec<_>>>()?;
+    let mut table: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, record) in records.iter().enumerate() {
+        if let Some(key) = key(record, &build_keys) {
+            table.entry(key).or_default().push(i);
+        }
+    }
+    let records = Rc::new(records);
+    let matched = Rc::new(RefCell::new(vec![false; records.len()]));
+
+    // puts the probe and build records back into (left, right) order
+    let combine = {
+        let layout = layout.clone();
+        move |probe: Option<&Record>, build: Option<&Record>| {
+            let (l, r) = if build_left {
+                (build, probe)
+            } else {
+                (probe, build)
+            };
+            combine(&layout, l, r)
+        }
+    };
+    let combine = Rc::new(combine);
+
+    let probed = {
+        let (records, matched, combine) = (records.clone(), matched.clone(), combine.clone());
+        probe.rows.flat_map(move |record| {
+            let record = match record {
+                Ok(record) => record,
+                Err(e) => return vec![Err(e)],
+            };
+            let hits = key(&record, &probe_keys).and_then(|k| table.get(&k));
+            match hits {
+                Some(hits) => {
+                    let mut matched = matched.borrow_mut();
+                    hits.iter()
+                        .map(|&i| {
+                            matched[i] = true;
+                            Ok(combine(Some(&record), Some(&records[i])))
+                        })
+                        .collect()
+                }
+                None if keep_probe => vec![Ok(combine(Some(&record), None))],
+                None => vec![],
+            }
+        })
+    };
+    // evaluated lazily, once the probe side is exhausted
+    let unmatched = std::iter::once(()).flat_map(move |_| {
+        let matched = matched.borrow();
+        records
+            .iter()
+            .zip(matched.iter())
+            .filter(|(_, &matched)| keep_build && !matched)
+            .map(|(record, _)| Ok(combine(None, Some(record))))
+            .collect::<Vec<_>>()
+    });
+
+    Ok(RecordStream {
+        columns,
+        rows: Box::new(probed.chain(unmatched)),
+    })
+}
+
+/// left columns, then right columns that aren't join keys, suffixed with `_right` on clashes
+fn output_columns(
+    left: &[Column],
+    right: &[Column],
+    on: &[(String, String)],
+) -> (Vec<Column>, Vec<OutputColumn>) {
+    let mut columns = Vec::new();
+    let mut layout = Vec::new();
+    for column in left {
+        let key = on.iter().find(|(l, _)| l == &column.name);
+        let ty = match key.and_then(|(_, r)| right.iter().find(|c| &c.name == r)) {
+            Some(right) => column.ty.merge(right.ty),
+            None => column.ty,
+        };
+        columns.push(Column {
+            name: column.name.clone(),
+            ty,
+        });
+        layout.push(OutputColumn {
+            name: column.name.clone(),
+            left: Some(column.name.clone()),
+            right: key.map(|(_, r)| r.clone()),
+        });
+    }
+    for column in right {
+        if on.iter().any(|(_, r)| r == &column.name) {
+            continue;
+        }
+        let mut name = column.name.clone();
+        while columns.iter().any(|c| c.name == name) {
+            name = format!("{}_right", name);
+        }
+        columns.push(Column {
+            name: name.clone(),
+            ty: column.ty,
+        });
+        layout.push(OutputColumn {
+            name,
+            left: None,
+            right: Some(column.name.clone()),
+        });
+    }
+    (columns, layout)
+}
+
+fn combine(layout: &[OutputColumn], left: Option<&Record>, right: Option<&Record>) -> Record {
+    layout
+        .iter()
+        .map(|column| {
+            let from_left = left.zip(column.left.as_ref()).and_then(|(r, n)| r.get(n));
+            let from_right = right.zip(column.right.as_ref()).and_then(|(r, n)| r.get(n));
+            let value = match (from_left, from_right) {
+                (Some(v), _) if !v.is_null() => v.clone(),
+                (_, Some(v)) => v.clone(),
+                _ => Value::Null,
+            };
+            (column.name.clone(), value)
+        })
+        .collect()
+}
+
+/// the join key as text, so `7`, `7.0` and `"7"` match, `None` if any key column is null
+pub(crate) fn key(record: &Record, columns: &[String]) -> Option<String> {
+    let mut key = String::new();
+    for (i, column) in columns.iter().enumerate() {
+        if i > 0 {
+            key.push('\u{1f}');
+        }
+        match record.get(column) {
+            None | Some(Value::Null) => return None,
+            Some(Value::String(s)) => key.push_str(s),
+            // a column can be inferred as integer in one file and float in the other
+            Some(Value::Number(n)) => match n.as_f64() {
+                Some(f) if n.is_f64() && f.fract() == 0.0 && f.abs() < i64::MAX as f64 => {
+                    key.push_str(&(f as i64).to_string())
+                }
+                _ => key.push_str(&n.to_string()),
+            },
+            Some(v) => key.push_str(&v.to_string()),
+        }
+    }
+    Some(key)
+}
+
+/// stack inputs on top of each other, the header is the union of all headers in first-seen order
+pub fn process_csv_concat(
+    inputs: &[(&str, InputFormat)],
+    opts: &CsvReaderOptions,
+    output: &str,
+    format: OutputFormat,
+    writer_opts: &CsvWriterOptions,
+) -> Result<()> {
+    let streams = inputs
+        .iter()
+        .map(|(input, from)| open_input(input, *from, opts))
+        .collect::<Result<Vec<_>>>()?;
+    write_records(concat(streams), format, output, writer_opts)?;
+    Ok(())
+}
+
+pub fn concat(streams: Vec<RecordStream>) -> RecordStream {
+    let mut columns: Vec<Column> = Vec::new();
+    for stream in &streams {
+        for column in &stream.columns {
+            match columns.iter_mut().find(|c| c.name == column.name) {
+                Some(existing) => existing.ty = existing.ty.merge(column.ty),
+                None => columns.push(column.clone()),
+            }
+        }
+    }
+    let names: Rc<Vec<String>> = Rc::new(columns.iter().map(|c| c.name.clone()).collect());
+    let rows = streams.into_iter().flat_map(move |stream| {
+        let names = names.clone();
+        stream.rows.map(move |record| {
+            let mut record = record?;
+            Ok(names
+                .iter()
+                .map(|name| (name.clone(), record.remove(name).unwrap_or(Value::Null)))
+                .collect::<Record>())
+        })
+    });
+    RecordStream {
+        columns,
+        rows: Box::new(rows),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::csv_reader::open_csv;
+    use crate::utils::write_temp;
+    use serde_json::json;
+
+    fn open(name: &str, content: &str) -> Result<RecordStream> {
+        open_csv(&write_temp(name, content)?, &CsvReaderOptions::default())
+    }
+
+    fn run(kind: JoinKind, build_left: bool) -> Result<Vec<Value>> {
+        let players = open(
+            "cli_join_players.csv",
+            "Name,Nationality\nSzczesny,Poland\nPerin,Italy\nRonaldo,Portugal\nNobody,\n",
+        )?;
+        let countries = open(
+            "cli_join_countries.csv",
+            "Country,Name\nItaly,Italia\nPoland,Polska\nSpain,España\n",
+        )?;
+        let opts = JoinOptions {
+            on: vec![("Nationality".to_string(), "Country".to_string())],
+            kind,
+        };
+        let stream = join(players, countries, &opts, build_left)?;
+        let names: Vec<_> = stream.columns.iter().map(|c| c.name.clone()).collect();
+        assert_eq!(names, vec!["Name", "Nationality", "Name_right"]);
+        stream.rows.map(|r| r.map(Value::Object)).collect()
+    }
+
+    #[test]
+    fn test_key_canonical_numbers() {
+        let key_of = |value: Value| {
+            let record = json!({ "id": value }).as_object().unwrap().clone();
+            key(&record, &["id".to_string()])
+        };
+        assert_eq!(key_of(json!(1)), Some("1".to_string()));
+        assert_eq!(key_of(json!(1.0)), Some("1".to_string()));
+        assert_eq!(key_of(json!("1")), Some("1".to_string()));
+        assert_eq!(key_of(json!(2.5)), Some("2.5".to_string()));
+        assert_eq!(key_of(json!(null)), None);
+    }
+
+    #[test]
+    fn test_inner_join() -> Result<()> {
+        let expected = vec![
+            json!({"Name": "Szczesny", "Nationality": "Poland", "Name_right": "Polska"}),
+            json!({"Name": "Perin", "Nationality": "Italy", "Name_right": "Italia"}),
+        ];
+        assert_eq!(run(JoinKind::Inner, false)?, expected);
+        // holding the left side only changes the order
+        let mut rows = run(JoinKind::Inner, true)?;
+        rows.reverse();
+        assert_eq!(rows, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_outer_joins() -> Result<()> {
+        let ronaldo = json!({"Name": "Ronaldo", "Nationality": "Portugal", "Name_right": null});
+        let nobody = json!({"Name": "Nobody", "Nationality": null, "Name_right": null});
+        let spain = json!({"Name": null, "Nationality": "Spain", "Name_right": "España"});
+
+        let rows = run(JoinKind::Left, false)?;
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[2..], [ronaldo.clone(), nobody.clone()]);
+        let rows = run(JoinKind::Left, true)?;
+        assert_eq!(rows.len(), 4);
+        assert!(rows.contains(&ronaldo) && rows.contains(&nobody));
+
+        for build_left in [false, true] {
+            let rows = run(JoinKind::Right, build_left)?;
+            assert_eq!(rows.len(), 3);
+            assert!(rows.contains(&spain));
+            let rows = run(JoinKind::Full, build_left)?;
+            assert_eq!(rows.len(), 5);
+            assert!(rows.contains(&spain) && rows.contains(&ronaldo) && rows.contains(&nobody));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_join_errors() -> Result<()> {
+        let left = open("cli_join_err_l.csv", "a,b\n1,2\n")?;
+        let right = open("cli_join_err_r.csv", "a,c\n1,3\n")?;
+        let opts = JoinOptions {
+            on: vec![("a".to_string(), "b".to_string())],
+            kind: JoinKind::Inner,
+        };
+        let err = join(left, right, &opts, false).err().map(|e| e.to_string());
+        assert_eq!(err.as_deref(), Some("right input has no column \"b\""));
+        Ok(())
+    }
+
+    #[test]
+    fn test_concat_unions_headers() -> Result<()> {
+        let a = open("cli_concat_a.csv", "id,name\n1,alice\n")?;
+        let b = open("cli_concat_b.csv", "name,score,id\nbob,1.5,2\n")?;
+        let stream = concat(vec![a, b]);
+        let names: Vec<_> = stream.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["id", "name", "score"]);
+        let rows = stream
+            .rows
+            .map(|r| r.map(Value::Object))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            rows,
+            vec![
+                json!({"id": 1, "name": "alice", "score": null}),
+                json!({"id": 2, "name": "bob", "score": 1.5}),
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,445 @@
+use crate::opt::{InputFormat, OutputFormat};
+use crate::process::csv_convert::open_input;
+use crate::process::csv_query::{Accumulator, Aggregate};
+use crate::process::csv_reader::{Column, ColumnType, CsvReaderOptions, Record, RecordStream};
+use crate::process::csv_writer::{write_records, CsvWriterOptions};
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub struct PivotOptions {
+    /// columns identifying an output row
+    pub index: Vec<String>,
+    /// column whose values become the output columns
+    pub columns: String,
+    /// column fed to the aggregate, `None` counts rows
+    pub values: Option<String>,
+    pub agg: Aggregate,
+}
+
+#[derive(Debug, Clone)]
+pub struct MeltOptions {
+    /// columns repeated on every output row
+    pub id: Vec<String>,
+    /// columns turned into rows, empty for every column that isn't an id
+    pub value_columns: Vec<String>,
+    pub var_name: String,
+    pub value_name: String,
+}
+
+pub fn process_csv_pivot(
+    input: &str,
+    from: InputFormat,
+    opts: &CsvReaderOptions,
+    pivot_opts: &PivotOptions,
+    output: &str,
+    format: OutputFormat,
+    writer_opts: &CsvWriterOptions,
+) -> Result<()> {
+    let stream = pivot(open_input(input, from, opts)?, pivot_opts)?;
+    write_records(stream, format, output, writer_opts)?;
+    Ok(())
+}
+
+pub fn process_csv_melt(
+    input: &str,
+    from: InputFormat,
+    opts: &CsvReaderOptions,
+    melt_opts: &MeltOptions,
+    output: &str,
+    format: OutputFormat,
+    writer_opts: &CsvWriterOptions,
+) -> Result<()> {
+    let stream = melt(open_input(input, from, opts)?, melt_opts)?;
+    write_records(stream, format, output, writer_opts)?;
+    Ok(())
+}
+
+/// long to wide: one row per distinct index, one column per distinct value of `columns`
+///
+/// Rows and columns come in first-seen order, like `GROUP BY`. Only one accumulator per cell is
+/// kept, so memory grows with the size of the result rather than the input. Cells without
+/// records are 0 for count and empty otherwise.
+pub fn pivot(stream: RecordStream, opts: &PivotOptions) -> Result<RecordStream> {
+    let find = |name: &str| {
+        stream
+            .columns
+            .iter()
+            .find(|c| c.name == name)
+            .ok_or_else(|| anyhow!("unknown column {:?}", name))
+    };
+    let mut columns = opts
+        .index
+        .iter()
+        .map(|name| find(name).cloned())
+        .collect::<Result<Vec<_>>>()?;
+    find(&opts.columns)?;
+    let input_ty = match &opts.values {
+        Some(name) => find(name)?.ty,
+        None if opts.agg == Aggregate::Count => ColumnType::Integer,
+        None => return Err(anyhow!("--values is required unless --agg is count")),
+    };
+
+    let mut rows: Vec<Record> = Vec::new();
+    let mut row_index: HashMap<String, usize> = HashMap::new();
+    let mut headers: Vec<String> = Vec::new();
+    let mut header_index: HashMap<String, usize> = HashMap::new();
+    let mut cells: HashMap<(usize, usize), Accumulator> = HashMap::new();
+    for record in stream.rows {
+        let record = record?;
+        let key: Vec<&Value> = opts
+            .index
+            .iter()
+            .map(|name| record.get(name).unwrap_or(&Value::Null))
+            .collect();
+        let key = serde_json::to_string(&key)?;
+        let row = *row_index.entry(key).or_insert_with(|| {
+            rows.push(
+                opts.index
+                    .iter()
+                    .map(|name| {
+                        (
+                            name.clone(),
+                            record.get(name).cloned().unwrap_or(Value::Null),
+                        )
+                    })
+                    .collect(),
+            );
+            rows.len() - 1
+        });
+        let header = header_text(record.get(&opts.columns).unwrap_or(&Value::Null));
+        let col = match header_index.get(&header) {
+            Some(&col) => col,
+            None => {
+                if opts.index.contains(&header) {
+                    return Err(anyhow!(
+                        "{:?} is both an index column and a value of {:?}",
+                        header,
+                        opts.columns
+                    ));
+                }
+                header_index.insert(header.clone(), headers.len());
+                headers.push(header);
+                headers.len() - 1
+            }
+        };
+        let accumulator = cells
+            .entry((row, col))
+            .or_insert_with(|| Accumulator::new(opts.agg));
+        match &opts.values {
+            Some(name) => accumulator
+                .add(record.get(name).unwrap_or(&Value::Null))
+                .map_err(|e| anyhow!("column {:?}: {}", name, e))?,
+            // without --values count rows, like count(*)
+            None => accumulator.add(&Value::Bool(true))?,
+        }
+    }
+
+    let ty = opts.agg.output_type(input_ty);
+    columns.extend(headers.iter().map(|name| Column {
+        name: name.clone(),
+        ty,
+    }));
+    let empty = Accumulator::new(opts.agg).finish();
+    let rows: Vec<Record> = rows
+        .into_iter()
+        .enumerate()
+        .map(|(row, mut record)| {
+            for (col, header) in headers.iter().enumerate() {
+                let value = cells
+                    .remove(&(row, col))
+                    .map_or_else(|| empty.clone(), Accumulator::finish);
+                record.insert(header.clone(), value);
+            }
+            record
+        })
+        .collect();
+    Ok(RecordStream {
+        columns,
+        rows: Box::new(rows.into_iter().map(Ok)),
+    })
+}
+
+/// wide to long: every value column of every record becomes a row of id columns, the column
+/// name and its value, streamed
+pub fn melt(stream: RecordStream, opts: &MeltOptions) -> Result<RecordStream> {
+    for name in opts.id.iter().chain(&opts.value_columns) {
+        if !stream.columns.iter().any(|c| &c.name == name) {
+            return Err(anyhow!("unknown column {:?}", name));
+        }
+    }
+    let value_columns: Vec<&Column> = match opts.value_columns.is_empty() {
+        true => stream
+            .columns
+            .iter()
+            .filter(|c| !opts.id.contains(&c.name))
+            .collect(),
+        false => opts
+            .value_columns
+            .iter()
+            .filter_map(|name| stream.columns.iter().find(|c| &c.name == name))
+            .collect(),
+    };
+    if value_columns.is_empty() {
+        return Err(anyhow!("no columns left to melt"));
+    }
+    for name in [&opts.var_name, &opts.value_name] {
+        if opts.id.contains(name) {
+            return Err(anyhow!("{:?} is already an id column", name));
+        }
+    }
+    if opts.var_name == opts.value_name {
+        return Err(anyhow!(
+            "--var-name and --value-name are both {:?}",
+            opts.var_name
+        ));
+    }
+
+    let mut columns: Vec<Column> = opts
+        .id
+        .iter()
+        .filter_map(|name| stream.columns.iter().find(|c| &c.name == name).cloned())
+        .collect();
+    columns.push(Column {
+        name: opts.var_name.clone(),
+        ty: ColumnType::String,
+    });
+    columns.push(Column {
+        name: opts.value_name.clone(),
+        ty: value_columns
+            .iter()
+            .fold(ColumnType::Null, |ty, c| ty.merge(c.ty)),
+    });
+
+    let names: Vec<String> = value_columns.iter().map(|c| c.name.clone()).collect();
+    let opts = opts.clone();
+    let rows = stream.rows.flat_map(move |record| {
+        let melted: Vec<Result<Record>> = match record {
+            Ok(record) => names
+                .iter()
+                .map(|name| {
+                    let mut row: Record = opts
+                        .id
+                        .iter()
+                        .map(|id| (id.clone(), record.get(id).cloned().unwrap_or(Value::Null)))
+                        .collect();
+                    row.insert(opts.var_name.clone(), Value::String(name.clone()));
+                    row.insert(
+                        opts.value_name.clone(),
+                        record.get(name).cloned().unwrap_or(Value::Null),
+                    );
+                    Ok(row)
+                })
+                .collect(),
+            Err(e) => vec![Err(e)],
+        };
+        melted
+    });
+    Ok(RecordStream {
+        columns,
+        rows: Box::new(rows),
+    })
+}
+
+/// the output column name for a value of the `--columns` column
+fn header_text(value: &Value) -> String {
+    match value {
+        Value::Null => "(empty)".to_string(),
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::csv_reader::open_csv;
+    use crate::utils::write_temp;
+    use serde_json::json;
+
+    fn juventus() -> Result<RecordStream> {
+        open_csv("assets/juventus.csv", &CsvReaderOptions::default())
+    }
+
+    fn open(name: &str, content: &str) -> Result<RecordStream> {
+        let path = write_temp(name, content)?;
+        open_csv(&path, &CsvReaderOptions::default())
+    }
+
+    fn collect(stream: RecordStream) -> Result<(Vec<String>, Vec<Value>)> {
+        let names = stream.columns.iter().map(|c| c.name.clone()).collect();
+        let rows = stream
+            .rows
+            .map(|r| r.map(Value::Object))
+            .collect::<Result<_>>()?;
+        Ok((names, rows))
+    }
+
+    fn pivot_opts(values: Option<&str>, agg: Aggregate) -> PivotOptions {
+        PivotOptions {
+            index: vec!["Nationality".to_string()],
+            columns: "Position".to_string(),
+            values: values.map(String::from),
+            agg,
+        }
+    }
+
+    #[test]
+    fn test_pivot_count() -> Result<()> {
+        let stream = pivot(
+            juventus()?,
+            &pivot_opts(Some("Kit Number"), Aggregate::Count),
+        )?;
+        assert!(stream.columns[1..]
+            .iter()
+            .all(|c| c.ty == ColumnType::Integer));
+        let (names, rows) = collect(stream)?;
+        assert_eq!(names.len(), 11);
+        assert_eq!(names[..3], ["Nationality", "Goalkeeper", "Centre-Back"]);
+        assert_eq!(rows.len(), 14);
+        assert_eq!(rows[1]["Nationality"], "Italy");
+        assert_eq!(rows[1]["Goalkeeper"], 3);
+        assert_eq!(rows[1]["Centre-Back"], 3);
+        assert_eq!(rows[1]["Left-Back"], 0);
+        // every player is counted once
+        let total: i64 = rows
+            .iter()
+            .flat_map(|row| names[1..].iter().map(move |n| row[n].as_i64().unwrap()))
+            .sum();
+        assert_eq!(total, 27);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pivot_aggregates() -> Result<()> {
+        let scores = "team,quarter,score\na,q1,3\na,q2,5\nb,q1,2\na,q1,4\nb,q2,\n";
+        let stream = open("cli_pivot_scores.csv", scores)?;
+        let opts = PivotOptions {
+            index: vec!["team".to_string()],
+            columns: "quarter".to_string(),
+            values: Some("score".to_string()),
+            agg: Aggregate::Sum,
+        };
+        let (_, rows) = collect(pivot(stream, &opts)?)?;
+        assert_eq!(
+            rows,
+            vec![
+                json!({"team": "a", "q1": 7, "q2": 5}),
+                json!({"team": "b", "q1": 2, "q2": null}),
+            ]
+        );
+
+        let stream = open("cli_pivot_scores.csv", scores)?;
+        let opts = PivotOptions {
+            agg: Aggregate::Avg,
+            ..opts
+        };
+        let stream = pivot(stream, &opts)?;
+        assert_eq!(stream.columns[1].ty, ColumnType::Float);
+        let (_, rows) = collect(stream)?;
+        assert_eq!(rows[0], json!({"team": "a", "q1": 3.5, "q2": 5.0}));
+        Ok(())
+    }
+
+    #[test]
+    fn test_pivot_errors() -> Result<()> {
+        let err = |opts: &PivotOptions| -> Result<String> {
+            Ok(pivot(juventus()?, opts)
+                .map(|_| ())
+                .unwrap_err()
+                .to_string())
+        };
+        assert_eq!(
+            err(&pivot_opts(None, Aggregate::Sum))?,
+            "--values is required unless --agg is count"
+        );
+        assert_eq!(
+            err(&pivot_opts(Some("Age"), Aggregate::Sum))?,
+            "unknown column \"Age\""
+        );
+        assert_eq!(
+            err(&pivot_opts(Some("Position"), Aggregate::Avg))?,
+            "column \"Position\": \"Goalkeeper\" is not a number"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_melt() -> Result<()> {
+        let opts = MeltOptions {
+            id: vec!["Name".to_string()],
+            value_columns: vec!["Position".to_string(), "Kit Number".to_string()],
+            var_name: "variable".to_string(),
+            value_name: "value".to_string(),
+        };
+        let stream = melt(juventus()?, &opts)?;
+        assert_eq!(stream.columns[2].ty, ColumnType::String);
+        let (names, rows) = collect(stream)?;
+        assert_eq!(names, vec!["Name", "variable", "value"]);
+        assert_eq!(rows.len(), 54);
+        assert_eq!(
+            rows[..2],
+            [
+                json!({"Name": "Wojciech Szczesny", "variable": "Position", "value": "Goalkeeper"}),
+                json!({"Name": "Wojciech Szczesny", "variable": "Kit Number", "value": 1}),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_melt_then_pivot_round_trips() -> Result<()> {
+        let wide = "team,q1,q2\na,3,5\nb,2,\n";
+        let opts = MeltOptions {
+            id: vec!["team".to_string()],
+            value_columns: Vec::new(),
+            var_name: "quarter".to_string(),
+            value_name: "score".to_string(),
+        };
+        let long = melt(open("cli_melt_wide.csv", wide)?, &opts)?;
+        assert_eq!(long.columns[2].ty, ColumnType::Integer);
+        let opts = PivotOptions {
+            index: vec!["team".to_string()],
+            columns: "quarter".to_string(),
+            values: Some("score".to_string()),
+            agg: Aggregate::Max,
+        };
+        let (names, rows) = collect(pivot(long, &opts)?)?;
+        assert_eq!(names, vec!["team", "q1", "q2"]);
+        assert_eq!(
+            rows,
+            vec![
+                json!({"team": "a", "q1": 3, "q2": 5}),
+                json!({"team": "b", "q1": 2, "q2": null}),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_melt_errors() -> Result<()> {
+        let opts = |id: &str, var_name: &str| MeltOptions {
+            id: vec![id.to_string()],
+            value_columns: Vec::new(),
+            var_name: var_name.to_string(),
+            value_name: "value".to_string(),
+        };
+        let err = |opts: MeltOptions| -> Result<String> {
+            Ok(melt(juventus()?, &opts)
+                .map(|_| ())
+                .unwrap_err()
+                .to_string())
+        };
+        assert_eq!(err(opts("Age", "variable"))?, "unknown column \"Age\"");
+        assert_eq!(
+            err(opts("Name", "Name"))?,
+            "\"Name\" is already an id column"
+        );
+        assert_eq!(
+            err(opts("Name", "value"))?,
+            "--var-name and --value-name are both \"value\""
+        );
+        Ok(())
+    }
+}
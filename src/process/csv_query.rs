@@ -0,0 +1,749 @@
+use crate::opt::{InputFormat, OutputFormat};
+use crate::process::csv_convert::open_input;
+use crate::process::csv_expr::{sort_order, sort_records, Expr, SortKey, Token, Tokens};
+use crate::process::csv_reader::{Column, ColumnType, CsvReaderOptions, Record, RecordStream};
+use crate::process::csv_writer::{write_records, CsvWriterOptions};
+use anyhow::{anyhow, Result};
+use serde_json::{Number, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// a parsed `SELECT` statement
+///
+/// ```text
+/// query  := "SELECT" items ["FROM" name] ["WHERE" expr] ["GROUP BY" columns]
+///           ["ORDER BY" order ("," order)*] ["LIMIT" n]
+/// items  := "*" | item ("," item)*
+/// item   := (column | agg "(" (column | "*") ")") ["AS" name]
+/// agg    := "count" | "sum" | "avg" | "min" | "max"
+/// order  := (name | agg "(" (column | "*") ")") ["ASC" | "DESC"]
+/// ```
+///
+/// `FROM` is accepted for familiarity and ignored, the input file is the table. See `Expr` for
+/// the `WHERE` grammar. `ORDER BY` refers to the output columns, by alias or by name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    select: Vec<SelectItem>,
+    filter: Option<Expr>,
+    group_by: Vec<String>,
+    order_by: Vec<SortKey>,
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum SelectItem {
+    All,
+    Column {
+        name: String,
+        alias: Option<String>,
+    },
+    Aggregate {
+        func: Aggregate,
+        /// `None` for `count(*)`
+        column: Option<String>,
+        alias: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregate {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+/// running state of one aggregate within one group
+pub(crate) enum Accumulator {
+    Count(i64),
+    Sum {
+        int: i64,
+        float: f64,
+        seen: bool,
+        fractional: bool,
+    },
+    Avg {
+        sum: f64,
+        count: u64,
+    },
+    Min(Value),
+    Max(Value),
+}
+
+/// what an output column is computed from
+enum Output {
+    Column(String),
+    Aggregate(Aggregate, Option<String>),
+}
+
+pub fn process_csv_query(
+    input: &str,
+    from: InputFormat,
+    opts: &CsvReaderOptions,
+    query: &str,
+    output: &str,
+    format: OutputFormat,
+    writer_opts: &CsvWriterOptions,
+) -> Result<()> {
+    let query: Query = query.parse()?;
+    let stream = open_input(input, from, opts)?;
+    let stream = query.run(stream)?;
+    write_records(stream, format, output, writer_opts)?;
+    Ok(())
+}
+
+impl Query {
+    /// run the query over a stream, results are only buffered for GROUP BY and ORDER BY
+    pub fn run(&self, stream: RecordStream) -> Result<RecordStream> {
+        let (columns, outputs) = self.outputs(&stream.columns)?;
+        if let Some(filter) = &self.filter {
+            filter.check_columns(&stream.columns)?;
+        }
+        let names: Vec<String> = columns.iter().map(|c| c.name.clone()).collect();
+        let order_by = self.resolve_order_by(&names)?;
+
+        let filter = self.filter.clone();
+        let rows = stream.rows.filter(move |record| match (record, &filter) {
+            (Ok(record), Some(filter)) => filter.matches(record),
+            _ => true,
+        });
+
+        let aggregated = !self.group_by.is_empty()
+            || outputs
+                .iter()
+                .any(|output| matches!(output, Output::Aggregate(..)));
+        let rows: Box<dyn Iterator<Item = Result<Record>>> = if aggregated {
+            let records = aggregate(rows, &self.group_by, &outputs, &names)?;
+            Box::new(records.into_iter().map(Ok))
+        } else {
+            Box::new(rows.map(move |record| {
+                let record = record?;
+                Ok(outputs
+                    .iter()
+                    .zip(&names)
+                    .map(|(output, name)| match output {
+                        Output::Column(column) => (
+                            name.clone(),
+                            record.get(column).cloned().unwrap_or(Value::Null),
+                        ),
+                        Output::Aggregate(..) => unreachable!(),
+                    })
+                    .collect())
+            }))
+        };
+
+        let rows: Box<dyn Iterator<Item = Result<Record>>> = if order_by.is_empty() {
+            rows
+        } else {
+            let mut records = rows.collect::<Result<Vec<_>>>()?;
+            sort_records(&mut records, &order_by);
+            Box::new(records.into_iter().map(Ok))
+        };
+
+        let rows = match self.limit {
+            Some(limit) => Box::new(rows.take(limit)),
+            None => rows,
+        };
+        Ok(RecordStream { columns, rows })
+    }
+
+    /// point every ORDER BY key at an output column, a select item given an alias can still be
+    /// ordered by its column or aggregate
+    fn resolve_order_by(&self, names: &[String]) -> Result<Vec<SortKey>> {
+        self.order_by
+            .iter()
+            .map(|key| {
+                if names.contains(&key.column) {
+                    return Ok(key.clone());
+                }
+                // `SELECT *` expands to several outputs, the others to one each
+                let aliased = match self.select.as_slice() {
+                    [SelectItem::All] => None,
+                    select => select
+                        .iter()
+                        .zip(names)
+                        .find(|(item, _)| item.to_string() == key.column),
+                };
+                match aliased {
+                    Some((_, name)) => Ok(SortKey {
+                        column: name.clone(),
+                        descending: key.descending,
+                    }),
+                    None => Err(anyhow!(
+                        "ORDER BY {:?} is not a column of the result, available: {:?}",
+                        key.column,
+                        names
+                    )),
+                }
+            })
+            .collect()
+    }
+
+    /// resolve the select list against the input columns
+    fn outputs(&self, input: &[Column]) -> Result<(Vec<Column>, Vec<Output>)> {
+        let find = |name: &str| {
+            input
+                .iter()
+                .find(|c| c.name == name)
+                .ok_or_else(|| anyhow!("unknown column {:?}", name))
+        };
+        for name in &self.group_by {
+            find(name)?;
+        }
+        let aggregated = !self.group_by.is_empty()
+            || self
+                .select
+                .iter()
+                .any(|item| matches!(item, SelectItem::Aggregate { .. }));
+
+        let mut columns = Vec::new();
+        let mut outputs = Vec::new();
+        for item in &self.select {
+            match item {
+                SelectItem::All if aggregated => {
+                    return Err(anyhow!(
+                        "SELECT * can't be combined with GROUP BY or aggregates"
+                    ))
+                }
+                SelectItem::All => {
+                    for column in input {
+                        columns.push(column.clone());
+                        outputs.push(Output::Column(column.name.clone()));
+                    }
+                }
+                SelectItem::Column { name, alias } => {
+                    let column = find(name)?;
+                    if aggregated && !self.group_by.contains(name) {
+                        return Err(anyhow!(
+                            "column {:?} must appear in GROUP BY or be used in an aggregate",
+                            name
+                        ));
+                    }
+                    columns.push(Column {
+                        name: alias.clone().unwrap_or_else(|| name.clone()),
+                        ty: column.ty,
+                    });
+                    outputs.push(Output::Column(name.clone()));
+                }
+                SelectItem::Aggregate {
+                    func,
+                    column,
+                    alias,
+                } => {
+                    let input_ty = match column {
+                        Some(name) => find(name)?.ty,
+                        None => ColumnType::Integer,
+                    };
+                    columns.push(Column {
+                        name: alias.clone().unwrap_or_else(|| item.to_string()),
+                        ty: func.output_type(input_ty),
+                    });
+                    outputs.push(Output::Aggregate(*func, column.clone()));
+                }
+            }
+        }
+        Ok((columns, outputs))
+    }
+}
+
+/// group records by the GROUP BY columns in first-seen order and fold every aggregate
+fn aggregate(
+    rows: impl Iterator<Item = Result<Record>>,
+    group_by: &[String],
+    outputs: &[Output],
+    names: &[String],
+) -> Result<Vec<Record>> {
+    let mut groups: Vec<(Record, Vec<Accumulator>)> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for record in rows {
+        let record = record?;
+        let key: Vec<&Value> = group_by
+            .iter()
+            .map(|name| record.get(name).unwrap_or(&Value::Null))
+            .collect();
+        let key_text = serde_json::to_string(&key)?;
+        let i = match index.get(&key_text) {
+            Some(&i) => i,
+            None => {
+                index.insert(key_text, groups.len());
+                let accumulators = outputs
+                    .iter()
+                    .filter_map(|output| match output {
+                        Output::Aggregate(func, _) => Some(Accumulator::new(*func)),
+                        Output::Column(_) => None,
+                    })
+                    .collect();
+                groups.push((record.clone(), accumulators));
+                groups.len() - 1
+            }
+        };
+        let accumulators = outputs.iter().filter_map(|output| match output {
+            Output::Aggregate(_, column) => Some(column),
+            Output::Column(_) => None,
+        });
+        for (column, accumulator) in accumulators.zip(groups[i].1.iter_mut()) {
+            match column {
+                Some(column) => {
+                    let value = record.get(column).unwrap_or(&Value::Null);
+                    accumulator
+                        .add(value)
+                        .map_err(|e| anyhow!("column {:?}: {}", column, e))?;
+                }
+                // count(*) counts rows, nulls included
+                None => accumulator.add(&Value::Bool(true))?,
+            }
+        }
+    }
+
+    // an aggregate without GROUP BY over no rows still yields one row, like SQL
+    if groups.is_empty() && group_by.is_empty() {
+        let accumulators = outputs
+            .iter()
+            .filter_map(|output| match output {
+                Output::Aggregate(func, _) => Some(Accumulator::new(*func)),
+                Output::Column(_) => None,
+            })
+            .collect();
+        groups.push((Record::new(), accumulators));
+    }
+
+    Ok(groups
+        .into_iter()
+        .map(|(first, accumulators)| {
+            let mut accumulators = accumulators.into_iter();
+            outputs
+                .iter()
+                .zip(names)
+                .map(|(output, name)| {
+                    let value = match output {
+                        Output::Column(column) => first.get(column).cloned().unwrap_or(Value::Null),
+                        Output::Aggregate(..) => accumulators
+                            .next()
+                            .map(Accumulator::finish)
+                            .unwrap_or(Value::Null),
+                    };
+                    (name.clone(), value)
+                })
+                .collect()
+        })
+        .collect())
+}
+
+impl Aggregate {
+    /// the type of the result over a column of type `input`
+    pub(crate) fn output_type(self, input: ColumnType) -> ColumnType {
+        match self {
+            Aggregate::Count => ColumnType::Integer,
+            Aggregate::Sum if input == ColumnType::Integer => ColumnType::Integer,
+            Aggregate::Sum | Aggregate::Avg => ColumnType::Float,
+            Aggregate::Min | Aggregate::Max => input,
+        }
+    }
+}
+
+impl Accumulator {
+    pub(crate) fn new(func: Aggregate) -> Self {
+        match func {
+            Aggregate::Count => Accumulator::Count(0),
+            Aggregate::Sum => Accumulator::Sum {
+                int: 0,
+                float: 0.0,
+                seen: false,
+                fractional: false,
+            },
+            Aggregate::Avg => Accumulator::Avg { sum: 0.0, count: 0 },
+            Aggregate::Min => Accumulator::Min(Value::Null),
+            Aggregate::Max => Accumulator::Max(Value::Null),
+        }
+    }
+
+    /// fold one value in, nulls are skipped by every aggregate
+    pub(crate) fn add(&mut self, value: &Value) -> Result<()> {
+        if value.is_null() {
+            return Ok(());
+        }
+        match self {
+            Accumulator::Count(n) => *n += 1,
+            Accumulator::Sum {
+                int,
+                float,
+                seen,
+                fractional,
+            } => {
+                match value.as_i64() {
+                    Some(v) => {
+                        *int = int
+                            .checked_add(v)
+                            .ok_or_else(|| anyhow!("sum overflows a 64-bit integer"))?
+                    }
+                    None => {
+                        *float += number(value)?;
+                        *fractional = true;
+                    }
+                }
+                *seen = true;
+            }
+            Accumulator::Avg { sum, count } => {
+                *sum += number(value)?;
+                *count += 1;
+            }
+            Accumulator::Min(min) => {
+                if min.is_null() || sort_order(value, min).is_lt() {
+                    *min = value.clone();
+                }
+            }
+            Accumulator::Max(max) => {
+                if max.is_null() || sort_order(value, max).is_gt() {
+                    *max = value.clone();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn finish(self) -> Value {
+        match self {
+            Accumulator::Count(n) => Value::from(n),
+            Accumulator::Sum { seen: false, .. } => Value::Null,
+            Accumulator::Sum {
+                int,
+                fractional: false,
+                ..
+            } => Value::from(int),
+            Accumulator::Sum { int, float, .. } => float_value(int as f64 + float),
+            Accumulator::Avg { count: 0, .. } => Value::Null,
+            Accumulator::Avg { sum, count } => float_value(sum / count as f64),
+            Accumulator::Min(value) | Accumulator::Max(value) => value,
+        }
+    }
+}
+
+fn number(value: &Value) -> Result<f64> {
+    let n = match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    };
+    n.ok_or_else(|| anyhow!("{} is not a number", value))
+}
+
+fn float_value(f: f64) -> Value {
+    Number::from_f64(f)
+        .map(Value::Number)
+        .unwrap_or(Value::Null)
+}
+
+impl FromStr for Query {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut tokens = Tokens::new(input)?;
+        tokens.expect_keyword("select")?;
+        let mut select = vec![select_item(&mut tokens)?];
+        while tokens.eat(&Token::Comma) {
+            select.push(select_item(&mut tokens)?);
+        }
+        if tokens.eat_keyword("from") {
+            tokens.column()?;
+        }
+        let filter = if tokens.eat_keyword("where") {
+            Some(tokens.expr()?)
+        } else {
+            None
+        };
+        let mut group_by = Vec::new();
+        if tokens.eat_keyword("group") {
+            tokens.expect_keyword("by")?;
+            group_by.push(tokens.column()?);
+            while tokens.eat(&Token::Comma) {
+                group_by.push(tokens.column()?);
+            }
+        }
+        let mut order_by = Vec::new();
+        if tokens.eat_keyword("order") {
+            tokens.expect_keyword("by")?;
+            loop {
+                let column = match aggregate_call(&mut tokens)? {
+                    Some(item) => item.to_string(),
+                    None => tokens.column()?,
+                };
+                let descending = tokens.eat_keyword("desc");
+                if !descending {
+                    tokens.eat_keyword("asc");
+                }
+                order_by.push(SortKey { column, descending });
+                if !tokens.eat(&Token::Comma) {
+                    break;
+                }
+            }
+        }
+        let limit = if tokens.eat_keyword("limit") {
+            match tokens.next() {
+                Some(Token::Number(n)) if n.is_u64() => {
+                    Some(n.as_u64().unwrap_or_default() as usize)
+                }
+                _ => return Err(anyhow!("LIMIT expects a non-negative integer")),
+            }
+        } else {
+            None
+        };
+        tokens.expect_end()?;
+        Ok(Query {
+            select,
+            filter,
+            group_by,
+            order_by,
+            limit,
+        })
+    }
+}
+
+fn select_item(tokens: &mut Tokens) -> Result<SelectItem> {
+    if tokens.eat(&Token::Star) {
+        return Ok(SelectItem::All);
+    }
+    let item = match aggregate_call(tokens)? {
+        Some(item) => item,
+        None => SelectItem::Column {
+            name: tokens.column()?,
+            alias: None,
+        },
+    };
+    if !tokens.eat_keyword("as") {
+        return Ok(item);
+    }
+    let alias = Some(tokens.column()?);
+    Ok(match item {
+        SelectItem::Column { name, .. } => SelectItem::Column { name, alias },
+        SelectItem::Aggregate { func, column, .. } => SelectItem::Aggregate {
+            func,
+            column,
+            alias,
+        },
+        SelectItem::All => unreachable!(),
+    })
+}
+
+/// `agg(column)` or `count(*)`, if the next tokens are a function call
+fn aggregate_call(tokens: &mut Tokens) -> Result<Option<SelectItem>> {
+    let func = match (tokens.peek(), tokens.peek_second()) {
+        (Some(Token::Word(word)), Some(Token::LParen)) => word.parse::<Aggregate>()?,
+        _ => return Ok(None),
+    };
+    tokens.next();
+    tokens.next();
+    let column = if func == Aggregate::Count && tokens.eat(&Token::Star) {
+        None
+    } else {
+        Some(tokens.column()?)
+    };
+    tokens.expect(&Token::RParen)?;
+    Ok(Some(SelectItem::Aggregate {
+        func,
+        column,
+        alias: None,
+    }))
+}
+
+impl FromStr for Aggregate {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "count" => Ok(Aggregate::Count),
+            "sum" => Ok(Aggregate::Sum),
+            "avg" => Ok(Aggregate::Avg),
+            "min" => Ok(Aggregate::Min),
+            "max" => Ok(Aggregate::Max),
+            _ => Err(anyhow!(
+                "unknown function {:?}, expected count, sum, avg, min or max",
+                value
+            )),
+        }
+    }
+}
+
+impl From<Aggregate> for &'static str {
+    fn from(func: Aggregate) -> Self {
+        match func {
+            Aggregate::Count => "count",
+            Aggregate::Sum => "sum",
+            Aggregate::Avg => "avg",
+            Aggregate::Min => "min",
+            Aggregate::Max => "max",
+        }
+    }
+}
+
+/// the default output column name, e.g. `count(*)` or `max(Kit Number)`
+impl fmt::Display for SelectItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SelectItem::All => write!(f, "*"),
+            SelectItem::Column { name, .. } => write!(f, "{}", name),
+            SelectItem::Aggregate { func, column, .. } => write!(
+                f,
+                "{}({})",
+                Into::<&str>::into(*func),
+                column.as_deref().unwrap_or("*")
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::csv_reader::open_csv;
+    use serde_json::json;
+
+    fn run(query: &str) -> Result<(Vec<Column>, Vec<Value>)> {
+        let stream = open_csv("assets/juventus.csv", &CsvReaderOptions::default())?;
+        let stream = query.parse::<Query>()?.run(stream)?;
+        let rows = stream
+            .rows
+            .map(|record| record.map(Value::Object))
+            .collect::<Result<Vec<_>>>()?;
+        Ok((stream.columns, rows))
+    }
+
+    #[test]
+    fn test_players_by_nationality() -> Result<()> {
+        let (columns, rows) = run(
+            "SELECT Nationality, count(*) AS players GROUP BY Nationality \
+             ORDER BY players DESC, Nationality LIMIT 3",
+        )?;
+        assert_eq!(columns[1].name, "players");
+        assert_eq!(columns[1].ty, ColumnType::Integer);
+        assert_eq!(
+            rows,
+            vec![
+                json!({"Nationality": "Italy", "players": 8}),
+                json!({"Nationality": "Brazil", "players": 3}),
+                json!({"Nationality": "Argentina", "players": 2}),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_where_order_limit() -> Result<()> {
+        let (columns, rows) = run("select Name, `Kit Number` as kit from players \
+             where Position = Goalkeeper and `Kit Number` > 1 order by kit desc limit 2")?;
+        let names: Vec<_> = columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["Name", "kit"]);
+        assert_eq!(
+            rows,
+            vec![
+                json!({"Name": "Gianluigi Buffon", "kit": 77}),
+                json!({"Name": "Mattia Perin", "kit": 37}),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_aggregates_without_group_by() -> Result<()> {
+        let (_, rows) = run(
+            "SELECT count(*), count(`Kit Number`), sum(`Kit Number`), min(Name), max(`Kit Number`) \
+             WHERE Position = Goalkeeper",
+        )?;
+        assert_eq!(
+            rows,
+            vec![json!({
+                "count(*)": 4,
+                "count(Kit Number)": 4,
+                "sum(Kit Number)": 146,
+                "min(Name)": "Carlo Pinsoglio",
+                "max(Kit Number)": 77,
+            })]
+        );
+        let (_, rows) = run("SELECT avg(`Kit Number`) AS avg WHERE Position = Goalkeeper")?;
+        assert_eq!(rows, vec![json!({"avg": 36.5})]);
+        let (_, rows) = run("SELECT count(*) AS n, sum(`Kit Number`) AS s WHERE Name = nobody")?;
+        assert_eq!(rows, vec![json!({"n": 0, "s": null})]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sum_overflow() -> Result<()> {
+        let sum = |values: &[Value]| -> Result<Value> {
+            let mut accumulator = Accumulator::new(Aggregate::Sum);
+            for value in values {
+                accumulator.add(value)?;
+            }
+            Ok(accumulator.finish())
+        };
+        assert_eq!(sum(&[json!(i64::MAX - 1), json!(1)])?, json!(i64::MAX));
+        assert_eq!(
+            sum(&[json!(i64::MAX), json!(-5), json!(5)])?,
+            json!(i64::MAX)
+        );
+        assert_eq!(
+            sum(&[json!(i64::MAX), json!(1)]).unwrap_err().to_string(),
+            "sum overflows a 64-bit integer"
+        );
+        assert!(sum(&[json!(i64::MIN), json!(-1)]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_order_by_aggregate() -> Result<()> {
+        let (_, rows) = run(
+            "SELECT Position, count(*) GROUP BY Position ORDER BY count(*) DESC, Position LIMIT 1",
+        )?;
+        assert_eq!(
+            rows,
+            vec![json!({"Position": "Central Midfield", "count(*)": 6})]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_order_by_aliased_items() -> Result<()> {
+        let (_, rows) = run(
+            "SELECT Position AS p, count(*) AS n GROUP BY Position ORDER BY count(*) DESC, Position LIMIT 2",
+        )?;
+        assert_eq!(
+            rows,
+            vec![
+                json!({"p": "Central Midfield", "n": 6}),
+                json!({"p": "Centre-Back", "n": 5}),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_errors() {
+        for (query, message) in [
+            (
+                "SELECT Name, count(*)",
+                "column \"Name\" must appear in GROUP BY",
+            ),
+            ("SELECT Age", "unknown column \"Age\""),
+            ("SELECT * WHERE Age > 3", "unknown column \"Age\""),
+            ("SELECT * GROUP BY Position", "SELECT * can't be combined"),
+            (
+                "SELECT Name ORDER BY Position",
+                "ORDER BY \"Position\" is not a column",
+            ),
+            ("SELECT median(Name)", "unknown function \"median\""),
+            (
+                "SELECT sum(Name)",
+                "column \"Name\": \"Wojciech Szczesny\" is not a number",
+            ),
+            (
+                "SELECT Name LIMIT -1",
+                "LIMIT expects a non-negative integer",
+            ),
+            ("SELECT Name WHERE", "expected a column name at position 18"),
+            ("Name", "expected select at position 1"),
+        ] {
+            let err = run(query).unwrap_err().to_string();
+            assert!(err.starts_with(message), "{}: {}", query, err);
+        }
+    }
+}
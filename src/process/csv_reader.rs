@@ -0,0 +1,402 @@
+use crate::process::csv_encoding::decode_reader;
+use crate::utils::get_decompressed_reader;
+use anyhow::Result;
+use chrono::NaiveDate;
+use csv::{Reader, ReaderBuilder, StringRecord};
+use encoding_rs::Encoding;
+use serde_json::{Map, Number, Value};
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::io::Read;
+
+pub type Record = Map<String, Value>;
+
+#[derive(Debug, Clone)]
+pub struct CsvReaderOptions {
+    pub delimiter: u8,
+    pub quote: u8,
+    pub has_headers: bool,
+    pub comment: Option<u8>,
+    pub flexible: bool,
+    /// number of leading records used to infer column types
+    pub infer_rows: usize,
+    /// sheet name or zero-based index of spreadsheet input
+    pub sheet: Option<String>,
+    /// A1-style cell range of spreadsheet input
+    pub range: Option<String>,
+    /// encoding of CSV input without a byte order mark, detected when `None`
+    pub encoding: Option<&'static Encoding>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Column {
+    pub name: String,
+    pub ty: ColumnType,
+}
+
+/// columns plus a lazy iterator over the records, so inputs larger than memory can be converted
+pub struct RecordStream {
+    pub columns: Vec<Column>,
+    pub rows: Box<dyn Iterator<Item = Result<Record>>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Null,
+    Bool,
+    Integer,
+    Float,
+    /// ISO-8601 calendar date (`YYYY-MM-DD`), kept as a string in JSON
+    Date,
+    String,
+}
+
+impl Default for CsvReaderOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            quote: b'"',
+            has_headers: true,
+            comment: None,
+            flexible: false,
+            infer_rows: 1000,
+            sheet: None,
+            range: None,
+            encoding: None,
+        }
+    }
+}
+
+impl CsvReaderOptions {
+    pub fn builder(&self) -> ReaderBuilder {
+        let mut builder = ReaderBuilder::new();
+        builder
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .has_headers(self.has_headers)
+            .comment(self.comment)
+            .flexible(self.flexible);
+        builder
+    }
+
+    /// a CSV reader over a file or stdin (`-`), decoded to UTF-8
+    pub fn reader(&self, input: &str) -> Result<Reader<Box<dyn Read>>> {
+        let reader = decode_reader(get_decompressed_reader(input)?, self.encoding)?;
+        Ok(self.builder().from_reader(reader))
+    }
+}
+
+impl ColumnType {
+    /// the narrowest type that can hold a single cell
+    pub fn of(value: &str) -> Self {
+        if value.is_empty() {
+            ColumnType::Null
+        } else if value.eq_ignore_ascii_case("true") || value.eq_ignore_ascii_case("false") {
+            ColumnType::Bool
+        } else if is_integer(value) {
+            ColumnType::Integer
+        } else if is_float(value) {
+            ColumnType::Float
+        } else if is_date(value) {
+            ColumnType::Date
+        } else {
+            ColumnType::String
+        }
+    }
+
+    /// the type of an already parsed value, nested values are kept as strings
+    pub fn of_value(value: &Value) -> Self {
+        match value {
+            Value::Null => ColumnType::Null,
+            Value::Bool(_) => ColumnType::Bool,
+            Value::Number(n) if n.is_i64() => ColumnType::Integer,
+            // floats, and integers above i64::MAX as columnar outputs have no room for them
+            Value::Number(_) => ColumnType::Float,
+            Value::String(_) | Value::Array(_) | Value::Object(_) => ColumnType::String,
+        }
+    }
+
+    /// the narrowest type that can hold cells of both types
+    pub fn merge(self, other: Self) -> Self {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (ColumnType::Null, t) | (t, ColumnType::Null) => t,
+            (ColumnType::Integer, ColumnType::Float) | (ColumnType::Float, ColumnType::Integer) => {
+                ColumnType::Float
+            }
+            _ => ColumnType::String,
+        }
+    }
+
+    /// convert a cell to a JSON value, falling back to a string if it doesn't fit the type
+    pub fn parse(self, value: &str) -> Value {
+        if value.is_empty() {
+            return Value::Null;
+        }
+        let parsed = match self {
+            ColumnType::Bool => value.to_ascii_lowercase().parse().ok().map(Value::Bool),
+            ColumnType::Integer => value.parse::<i64>().ok().map(Value::from),
+            ColumnType::Float if overflows_integer(value) => None,
+            ColumnType::Float => value
+                .parse::<f64>()
+                .ok()
+                .and_then(Number::from_f64)
+                .map(Value::Number),
+            ColumnType::Null | ColumnType::Date | ColumnType::String => None,
+        };
+        parsed.unwrap_or_else(|| Value::String(value.to_string()))
+    }
+}
+
+impl From<ColumnType> for &'static str {
+    fn from(ty: ColumnType) -> Self {
+        match ty {
+            ColumnType::Null => "null",
+            ColumnType::Bool => "bool",
+            ColumnType::Integer => "integer",
+            ColumnType::Float => "float",
+            ColumnType::Date => "date",
+            ColumnType::String => "string",
+        }
+    }
+}
+
+impl fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+/// open a CSV file, inferring column types from the first `infer_rows` records
+pub fn open_csv(input: &str, opts: &CsvReaderOptions) -> Result<RecordStream> {
+    let mut reader = opts.reader(input)?;
+    let mut headers: Vec<String> = if opts.has_headers {
+        reader.headers()?.iter().map(String::from).collect()
+    } else {
+        Vec::new()
+    };
+
+    let mut records = reader.into_records();
+    let mut sample = VecDeque::new();
+    for record in records.by_ref().take(opts.infer_rows.max(1)) {
+        sample.push_back(record?);
+    }
+
+    let width = sample.iter().map(StringRecord::len).max().unwrap_or(0);
+    for i in headers.len()..width {
+        headers.push(format!("col_{}", i + 1));
+    }
+    let mut columns: Vec<Column> = unique_names(headers)
+        .into_iter()
+        .map(|name| Column {
+            name,
+            ty: ColumnType::Null,
+        })
+        .collect();
+    for record in &sample {
+        for (column, value) in columns.iter_mut().zip(record.iter()) {
+            column.ty = column.ty.merge(ColumnType::of(value));
+        }
+    }
+
+    let row_columns = columns.clone();
+    let rows = sample
+        .into_iter()
+        .map(Ok)
+        .chain(records)
+        .map(move |record| Ok(to_record(&row_columns, &record?)));
+
+    Ok(RecordStream {
+        columns,
+        rows: Box::new(rows),
+    })
+}
+
+/// rename repeated column names to `name_2`, `name_3`, ... so no cell overwrites another
+pub(crate) fn unique_names(names: Vec<String>) -> Vec<String> {
+    let mut taken: HashSet<String> = names.iter().cloned().collect();
+    let mut seen = HashSet::new();
+    names
+        .into_iter()
+        .map(|name| {
+            if seen.insert(name.clone()) {
+                return name;
+            }
+            let unique = (2..)
+                .map(|n| format!("{}_{}", name, n))
+                .find(|candidate| !taken.contains(candidate))
+                .expect("some suffix is free");
+            taken.insert(unique.clone());
+            seen.insert(unique.clone());
+            unique
+        })
+        .collect()
+}
+
+fn to_record(columns: &[Column], record: &StringRecord) -> Record {
+    let mut row: Record = columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| {
+            let value = record.get(i).map_or(Value::Null, |v| column.ty.parse(v));
+            (column.name.clone(), value)
+        })
+        .collect();
+    // flexible rows may be wider than the header
+    for (i, value) in record.iter().enumerate().skip(columns.len()) {
+        row.insert(format!("col_{}", i + 1), ColumnType::of(value).parse(value));
+    }
+    row
+}
+
+fn is_integer(value: &str) -> bool {
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    !digits.is_empty()
+        && digits.bytes().all(|b| b.is_ascii_digit())
+        && !has_leading_zero(digits)
+        && value.parse::<i64>().is_ok()
+}
+
+fn is_float(value: &str) -> bool {
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    !has_leading_zero(digits)
+        && !overflows_integer(value)
+        && value.bytes().any(|b| b.is_ascii_digit())
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_digit() || matches!(b, b'-' | b'+' | b'.' | b'e' | b'E'))
+        && value.parse::<f64>().is_ok_and(f64::is_finite)
+}
+
+/// digits only but too large for i64 and u64, rounding them to a float would lose digits
+fn overflows_integer(value: &str) -> bool {
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    !digits.is_empty()
+        && digits.bytes().all(|b| b.is_ascii_digit())
+        && value.parse::<i64>().is_err()
+        && value.parse::<u64>().is_err()
+}
+
+fn is_date(value: &str) -> bool {
+    value.len() == 10 && NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok()
+}
+
+// leading zeros are usually identifiers (zip codes, ids), keep them as strings
+fn has_leading_zero(digits: &str) -> bool {
+    let bytes = digits.as_bytes();
+    bytes.len() > 1 && bytes[0] == b'0' && bytes[1].is_ascii_digit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::write_temp;
+    use serde_json::json;
+
+    fn read_csv(input: &str, opts: &CsvReaderOptions) -> Result<(Vec<String>, Vec<Value>)> {
+        let stream = open_csv(input, opts)?;
+        let headers = stream.columns.into_iter().map(|c| c.name).collect();
+        let rows = stream
+            .rows
+            .map(|row| row.map(Value::Object))
+            .collect::<Result<_>>()?;
+        Ok((headers, rows))
+    }
+
+    #[test]
+    fn test_column_type_of() {
+        assert_eq!(ColumnType::of(""), ColumnType::Null);
+        assert_eq!(ColumnType::of("TRUE"), ColumnType::Bool);
+        assert_eq!(ColumnType::of("-42"), ColumnType::Integer);
+        assert_eq!(ColumnType::of("007"), ColumnType::String);
+        assert_eq!(ColumnType::of("3.14"), ColumnType::Float);
+        assert_eq!(ColumnType::of("1e3"), ColumnType::Float);
+        assert_eq!(ColumnType::of("NaN"), ColumnType::String);
+        assert_eq!(ColumnType::of("18446744073709551615"), ColumnType::Float);
+        assert_eq!(
+            ColumnType::of("12345678901234567890123"),
+            ColumnType::String
+        );
+        assert_eq!(ColumnType::of("-9223372036854775809"), ColumnType::String);
+        assert_eq!(
+            ColumnType::Float.parse("12345678901234567890123"),
+            json!("12345678901234567890123")
+        );
+        assert_eq!(ColumnType::of("1990-04-18"), ColumnType::Date);
+        assert_eq!(ColumnType::of("1990-02-30"), ColumnType::String);
+        assert_eq!(ColumnType::of("Italy"), ColumnType::String);
+    }
+
+    #[test]
+    fn test_column_type_merge() {
+        use ColumnType::*;
+        assert_eq!(Null.merge(Integer), Integer);
+        assert_eq!(Integer.merge(Float), Float);
+        assert_eq!(Bool.merge(Integer), String);
+        assert_eq!(Float.merge(Null), Float);
+        assert_eq!(Date.merge(Null), Date);
+        assert_eq!(Date.merge(Integer), String);
+    }
+
+    #[test]
+    fn test_read_csv_infers_types() -> Result<()> {
+        let (headers, rows) = read_csv("assets/juventus.csv", &CsvReaderOptions::default())?;
+        assert_eq!(
+            headers,
+            vec!["Name", "Position", "DOB", "Nationality", "Kit Number"]
+        );
+        assert_eq!(rows.len(), 27);
+        assert_eq!(rows[0]["Kit Number"], json!(1));
+        assert_eq!(rows[0]["DOB"], json!("Apr 18, 1990 (29)"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_csv_with_options() -> Result<()> {
+        let path = write_temp(
+            "cli_read_csv_with_options.csv",
+            "# exported data\nalice;30;true;1.5\nbob;;false\n",
+        )?;
+        let opts = CsvReaderOptions {
+            delimiter: b';',
+            has_headers: false,
+            comment: Some(b'#'),
+            flexible: true,
+            ..Default::default()
+        };
+        let (headers, rows) = read_csv(&path, &opts)?;
+        assert_eq!(headers, vec!["col_1", "col_2", "col_3", "col_4"]);
+        assert_eq!(
+            rows,
+            vec![
+                json!({"col_1": "alice", "col_2": 30, "col_3": true, "col_4": 1.5}),
+                json!({"col_1": "bob", "col_2": null, "col_3": false, "col_4": null}),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_csv_renames_duplicate_headers() -> Result<()> {
+        let path = write_temp("cli_open_csv_duplicates.csv", "id,a,a,a_2\n1,2,3,4\n")?;
+        let (headers, rows) = read_csv(&path, &CsvReaderOptions::default())?;
+        assert_eq!(headers, vec!["id", "a", "a_3", "a_2"]);
+        assert_eq!(rows, vec![json!({"id": 1, "a": 2, "a_3": 3, "a_2": 4})]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_csv_falls_back_after_sample() -> Result<()> {
+        let path = write_temp("cli_open_csv_sample.csv", "id\n1\n2\nabc\n")?;
+        let opts = CsvReaderOptions {
+            infer_rows: 2,
+            ..Default::default()
+        };
+        let stream = open_csv(&path, &opts)?;
+        assert_eq!(stream.columns[0].ty, ColumnType::Integer);
+        let rows = stream.rows.collect::<Result<Vec<_>>>()?;
+        assert_eq!(rows[1]["id"], json!(2));
+        assert_eq!(rows[2]["id"], json!("abc"));
+        Ok(())
+    }
+}
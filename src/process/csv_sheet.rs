@@ -0,0 +1,302 @@
+use crate::process::csv_reader::{unique_names, Column, ColumnType, Record, RecordStream};
+use crate::utils::get_reader;
+use anyhow::{anyhow, Result};
+use calamine::{open_workbook_auto, open_workbook_auto_from_rs, Data, Range, Reader, Sheets};
+use chrono::NaiveTime;
+use serde_json::Value;
+use std::io::{Cursor, Read, Seek};
+
+/// open a worksheet of an xlsx/xls/ods file as records
+///
+/// `sheet` is a sheet name or a zero-based index, defaulting to the first sheet, and `range`
+/// an A1-style cell range such as `A1:E20`. A workbook on stdin (`-`) is read into memory first,
+/// since its format needs random access.
+pub fn open_sheet(
+    input: &str,
+    sheet: Option<&str>,
+    range: Option<&str>,
+    has_headers: bool,
+) -> Result<RecordStream> {
+    let data = if input == "-" {
+        let mut buffer = Vec::new();
+        get_reader(input)?.read_to_end(&mut buffer)?;
+        worksheet(open_workbook_auto_from_rs(Cursor::new(buffer))?, sheet)?
+    } else {
+        worksheet(open_workbook_auto(input)?, sheet)?
+    };
+    let data = match range {
+        Some(range) => {
+            let (start, end) = parse_range(range)?;
+            within(&data, start, end)
+        }
+        None => data,
+    };
+    Ok(sheet_records(&data, has_headers))
+}
+
+/// the part of `data` inside the range, which `Range::range` would pad with empty cells up to
+/// a corner as far as `XFD1048576`
+fn within(data: &Range<Data>, start: (u32, u32), end: (u32, u32)) -> Range<Data> {
+    let (Some(first), Some(last)) = (data.start(), data.end()) else {
+        return Range::empty();
+    };
+    let start = (start.0.max(first.0), start.1.max(first.1));
+    let end = (end.0.min(last.0), end.1.min(last.1));
+    if start.0 > end.0 || start.1 > end.1 {
+        return Range::empty();
+    }
+    data.range(start, end)
+}
+
+fn worksheet<RS: Read + Seek>(
+    mut workbook: Sheets<RS>,
+    sheet: Option<&str>,
+) -> Result<Range<Data>> {
+    let names = workbook.sheet_names();
+    let data = match sheet {
+        Some(name) if names.iter().any(|n| n == name) => workbook.worksheet_range(name)?,
+        Some(index) => {
+            let index: usize = index
+                .parse()
+                .map_err(|_| anyhow!("sheet {:?} not found, available: {:?}", index, names))?;
+            workbook
+                .worksheet_range_at(index)
+                .ok_or_else(|| anyhow!("sheet index {} out of bounds", index))??
+        }
+        None => workbook
+            .worksheet_range_at(0)
+            .ok_or_else(|| anyhow!("workbook has no sheets"))??,
+    };
+    Ok(data)
+}
+
+fn sheet_records(data: &Range<Data>, has_headers: bool) -> RecordStream {
+    let mut rows = data.rows();
+    let headers: Vec<String> = match rows.next() {
+        Some(row) if has_headers => row
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| match cell {
+                Data::Empty => format!("col_{}", i + 1),
+                cell => cell.to_string(),
+            })
+            .collect(),
+        _ => (0..data.width())
+            .map(|i| format!("col_{}", i + 1))
+            .collect(),
+    };
+    let skip = usize::from(has_headers);
+
+    let mut columns: Vec<Column> = unique_names(headers)
+        .into_iter()
+        .map(|name| Column {
+            name,
+            ty: ColumnType::Null,
+        })
+        .collect();
+    let records: Vec<Record> = data
+        .rows()
+        .skip(skip)
+        .map(|row| {
+            columns
+                .iter_mut()
+                .zip(row)
+                .map(|(column, cell)| {
+                    let value = cell_value(cell);
+                    column.ty = column.ty.merge(cell_type(cell, &value));
+                    (column.name.clone(), value)
+                })
+                .collect()
+        })
+        .collect();
+
+    RecordStream {
+        columns,
+        rows: Box::new(records.into_iter().map(Ok)),
+    }
+}
+
+fn cell_value(cell: &Data) -> Value {
+    match cell {
+        Data::Empty => Value::Null,
+        Data::Int(i) => Value::from(*i),
+        // excel stores every number as a float
+        Data::Float(f) if f.fract() == 0.0 && f.abs() < i64::MAX as f64 => Value::from(*f as i64),
+        Data::Float(f) => Value::from(*f),
+        Data::Bool(b) => Value::Bool(*b),
+        Data::String(s) | Data::DateTimeIso(s) | Data::DurationIso(s) => Value::String(s.clone()),
+        Data::DateTime(dt) => match dt.as_datetime() {
+            Some(dt) if dt.time() == NaiveTime::MIN => {
+                Value::String(dt.date().format("%Y-%m-%d").to_string())
+            }
+            Some(dt) => Value::String(dt.format("%Y-%m-%dT%H:%M:%S").to_string()),
+            None => Value::from(dt.as_f64()),
+        },
+        Data::Error(e) => Value::String(e.to_string()),
+    }
+}
+
+/// text cells keep their type even if they look like numbers
+fn cell_type(cell: &Data, value: &Value) -> ColumnType {
+    match (cell, value) {
+        (Data::DateTime(_) | Data::DateTimeIso(_), Value::String(s)) => ColumnType::of(s),
+        _ => ColumnType::of_value(value),
+    }
+}
+
+/// last row and column of an xlsx worksheet, `XFD1048576`
+const MAX_ROWS: u32 = 1_048_576;
+const MAX_COLUMNS: u32 = 16_384;
+
+/// parse an A1-style range into zero-based (row, col) corners
+fn parse_range(range: &str) -> Result<((u32, u32), (u32, u32))> {
+    let (start, end) = range
+        .split_once(':')
+        .ok_or_else(|| anyhow!("invalid range {:?}, expected e.g. A1:E20", range))?;
+    let (start, end) = (parse_cell(start)?, parse_cell(end)?);
+    if start.0 > end.0 || start.1 > end.1 {
+        return Err(anyhow!(
+            "invalid range {:?}, the first cell must be above and left of the second",
+            range
+        ));
+    }
+    Ok((start, end))
+}
+
+fn parse_cell(cell: &str) -> Result<(u32, u32)> {
+    let cell = cell.trim().to_ascii_uppercase();
+    let split = cell
+        .find(|c: char| c.is_ascii_digit())
+        .ok_or_else(|| anyhow!("invalid cell {:?}", cell))?;
+    let (letters, digits) = cell.split_at(split);
+    if letters.is_empty()
+        || !letters.bytes().all(|b| b.is_ascii_uppercase())
+        || !digits.bytes().all(|b| b.is_ascii_digit())
+    {
+        return Err(anyhow!("invalid cell {:?}", cell));
+    }
+    let col = letters
+        .bytes()
+        .try_fold(0u32, |acc, b| {
+            acc.checked_mul(26)?.checked_add((b - b'A' + 1) as u32)
+        })
+        .filter(|&col| col <= MAX_COLUMNS)
+        .ok_or_else(|| anyhow!("invalid cell {:?}, the last column is XFD", cell))?;
+    let row = digits
+        .parse::<u32>()
+        .ok()
+        .filter(|&row| row <= MAX_ROWS)
+        .ok_or_else(|| anyhow!("invalid cell {:?}, the last row is {}", cell, MAX_ROWS))?;
+    if row == 0 {
+        return Err(anyhow!("invalid cell {:?}, rows start at 1", cell));
+    }
+    Ok((row - 1, col - 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opt::OutputFormat;
+    use crate::process::csv_reader::{open_csv, CsvReaderOptions};
+    use crate::process::csv_writer::{write_records, CsvWriterOptions};
+    use crate::utils::temp_path;
+
+    fn juventus_xlsx(name: &str) -> Result<String> {
+        let stream = open_csv("assets/juventus.csv", &CsvReaderOptions::default())?;
+        let output = temp_path(name);
+        let output = output.to_string_lossy().into_owned();
+        let opts = CsvWriterOptions {
+            sheet_name: Some("Juventus".to_string()),
+            ..Default::default()
+        };
+        write_records(stream, OutputFormat::Xlsx, &output, &opts)?;
+        Ok(output)
+    }
+
+    #[test]
+    fn test_parse_range() -> Result<()> {
+        assert_eq!(parse_range("A1:E20")?, ((0, 0), (19, 4)));
+        assert_eq!(parse_range("b2:AA3")?, ((1, 1), (2, 26)));
+        assert!(parse_range("A0:B2").is_err());
+        assert!(parse_range("A1").is_err());
+        assert_eq!(parse_range("A1:XFD1048576")?, ((0, 0), (1_048_575, 16_383)));
+        let err = |range: &str| parse_range(range).unwrap_err().to_string();
+        assert_eq!(
+            err("E3:A1"),
+            "invalid range \"E3:A1\", the first cell must be above and left of the second"
+        );
+        assert_eq!(
+            err("A1:ZZZZZZZZ9"),
+            "invalid cell \"ZZZZZZZZ9\", the last column is XFD"
+        );
+        assert_eq!(
+            err("A1:XFE1"),
+            "invalid cell \"XFE1\", the last column is XFD"
+        );
+        assert_eq!(
+            err("A1:A1048577"),
+            "invalid cell \"A1048577\", the last row is 1048576"
+        );
+        assert_eq!(
+            err("A1:A99999999999"),
+            "invalid cell \"A99999999999\", the last row is 1048576"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_sheet_matches_csv() -> Result<()> {
+        let path = juventus_xlsx("cli_open_sheet.xlsx")?;
+        let expected = open_csv("assets/juventus.csv", &CsvReaderOptions::default())?;
+        let stream = open_sheet(&path, Some("Juventus"), None, true)?;
+        assert_eq!(stream.columns, expected.columns);
+        let rows = stream.rows.collect::<Result<Vec<_>>>()?;
+        let expected = expected.rows.collect::<Result<Vec<_>>>()?;
+        assert_eq!(rows, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_sheet_with_index_and_range() -> Result<()> {
+        let path = juventus_xlsx("cli_open_sheet_range.xlsx")?;
+        let stream = open_sheet(&path, Some("0"), Some("D1:E3"), true)?;
+        let names: Vec<_> = stream.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["Nationality", "Kit Number"]);
+        let rows = stream.rows.collect::<Result<Vec<_>>>()?;
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1]["Kit Number"], Value::from(37));
+        assert!(open_sheet(&path, Some("Missing"), None, true).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_sheet_range_past_the_data() -> Result<()> {
+        let path = juventus_xlsx("cli_open_sheet_large_range.xlsx")?;
+        for range in ["A1:XFD1048576", "A1:E40", "A1:Z28"] {
+            let stream = open_sheet(&path, None, Some(range), true)?;
+            assert_eq!(stream.columns.len(), 5, "{}", range);
+            let rows = stream.rows.collect::<Result<Vec<_>>>()?;
+            assert_eq!(rows.len(), 27, "{}", range);
+        }
+        let stream = open_sheet(&path, None, Some("A20:XFD1048576"), false)?;
+        assert_eq!(stream.rows.count(), 9);
+        let stream = open_sheet(&path, None, Some("G40:H50"), true)?;
+        assert!(stream.columns.is_empty());
+        assert_eq!(stream.rows.count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_ods() -> Result<()> {
+        let stream = open_sheet("fixtures/players.ods", None, None, true)?;
+        let types: Vec<_> = stream.columns.iter().map(|c| c.ty).collect();
+        assert_eq!(
+            types,
+            vec![ColumnType::String, ColumnType::Integer, ColumnType::Date]
+        );
+        let rows = stream.rows.collect::<Result<Vec<_>>>()?;
+        assert_eq!(rows[0]["Joined"], Value::from("2015-07-01"));
+        assert_eq!(rows[1]["Joined"], Value::Null);
+        Ok(())
+    }
+}
@@ -0,0 +1,419 @@
+use crate::opt::{InputFormat, OutputFormat};
+use crate::process::csv_convert::open_input;
+use crate::process::csv_reader::{CsvReaderOptions, Record, RecordStream};
+use crate::process::csv_writer::{create_writer, prepare_stream, write_records, CsvWriterOptions};
+use crate::utils::strip_compression_ext;
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone)]
+pub enum SplitMode {
+    /// pieces of at most this many records
+    Rows(usize),
+    /// one piece per distinct value of the column
+    By(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct SplitOptions {
+    pub mode: SplitMode,
+    /// directory the pieces are written to
+    pub dir: String,
+    /// start of every piece's file name
+    pub prefix: String,
+    pub format: OutputFormat,
+    /// most spool files kept open at once by `--by`
+    pub max_open: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitPiece {
+    pub path: String,
+    pub records: usize,
+}
+
+/// per-group NDJSON files that `--by` appends to, with at most `max_open` of them open
+///
+/// The least recently written file is closed to make room, and reopened for appending when its
+/// group comes up again. The directory is removed on drop.
+struct Spool {
+    dir: PathBuf,
+    open: HashMap<usize, (u64, BufWriter<File>)>,
+    max_open: usize,
+    clock: u64,
+}
+
+pub fn process_csv_split(
+    input: &str,
+    from: InputFormat,
+    opts: &CsvReaderOptions,
+    split_opts: &SplitOptions,
+    writer_opts: &CsvWriterOptions,
+) -> Result<Vec<SplitPiece>> {
+    split(open_input(input, from, opts)?, split_opts, writer_opts)
+}
+
+/// split a stream into pieces in a single pass, each with the full header
+///
+/// `Rows` writes `<prefix>_0001.<format>`, `<prefix>_0002.<format>`… one at a time. `By` writes
+/// `<prefix>_<value>.<format>` per value in first-seen order, the value reduced to characters
+/// safe in file names. Its records are spooled to NDJSON first, since most formats can't be
+/// appended to once closed.
+pub fn split(
+    stream: RecordStream,
+    opts: &SplitOptions,
+    writer_opts: &CsvWriterOptions,
+) -> Result<Vec<SplitPiece>> {
+    let stream = prepare_stream(stream, opts.format, writer_opts)?;
+    let writer_opts = CsvWriterOptions {
+        unflatten: false,
+        ..writer_opts.clone()
+    };
+    if opts.max_open == 0 {
+        return Err(anyhow!("--max-open must be at least 1"));
+    }
+    fs::create_dir_all(&opts.dir)?;
+    match &opts.mode {
+        SplitMode::Rows(0) => Err(anyhow!("--rows must be at least 1")),
+        SplitMode::Rows(rows) => split_rows(stream, *rows, opts, &writer_opts),
+        SplitMode::By(column) => split_by(stream, column, opts, &writer_opts),
+    }
+}
+
+fn split_rows(
+    stream: RecordStream,
+    rows: usize,
+    opts: &SplitOptions,
+    writer_opts: &CsvWriterOptions,
+) -> Result<Vec<SplitPiece>> {
+    let mut pieces: Vec<SplitPiece> = Vec::new();
+    let mut writer = None;
+    for record in stream.rows {
+        let record = record?;
+        let current = match &mut writer {
+            Some(current) => current,
+            None => {
+                let path = piece_path(opts, &format!("{:04}", pieces.len() + 1));
+                pieces.push(SplitPiece { path, records: 0 });
+                let path = &pieces[pieces.len() - 1].path;
+                writer.insert(create_writer(
+                    opts.format,
+                    path,
+                    &stream.columns,
+                    writer_opts,
+                )?)
+            }
+        };
+        current.write_record(&record)?;
+        let piece = pieces.last_mut().expect("a piece was just started");
+        piece.records += 1;
+        if piece.records == rows {
+            writer.take().expect("the piece is open").finish()?;
+        }
+    }
+    if let Some(writer) = writer {
+        writer.finish()?;
+    }
+    Ok(pieces)
+}
+
+fn split_by(
+    stream: RecordStream,
+    column: &str,
+    opts: &SplitOptions,
+    writer_opts: &CsvWriterOptions,
+) -> Result<Vec<SplitPiece>> {
+    if !stream.columns.iter().any(|c| c.name == column) {
+        return Err(anyhow!("--by: unknown column {:?}", column));
+    }
+    let spool_dir =
+        Path::new(&opts.dir).join(format!(".{}.split-{}", opts.prefix, std::process::id()));
+    let mut spool = Spool::new(spool_dir, opts.max_open)?;
+    let mut groups: HashMap<String, usize> = HashMap::new();
+    let mut pieces: Vec<SplitPiece> = Vec::new();
+    let mut names = HashSet::new();
+    for record in stream.rows {
+        let record = record?;
+        let value = record.get(column).unwrap_or(&Value::Null);
+        let key = serde_json::to_string(value)?;
+        let i = match groups.get(&key) {
+            Some(&i) => i,
+            None => {
+                let mut name = file_name_part(value);
+                let mut n = 2;
+                while !names.insert(name.clone()) {
+                    name = format!("{}_{}", file_name_part(value), n);
+                    n += 1;
+                }
+                groups.insert(key, pieces.len());
+                pieces.push(SplitPiece {
+                    path: piece_path(opts, &name),
+                    records: 0,
+                });
+                pieces.len() - 1
+            }
+        };
+        spool.write(i, &record)?;
+        pieces[i].records += 1;
+    }
+    spool.close()?;
+
+    for (i, piece) in pieces.iter().enumerate() {
+        let rows = spool.read(i)?;
+        let stream = RecordStream {
+            columns: stream.columns.clone(),
+            rows: Box::new(rows),
+        };
+        write_records(stream, opts.format, &piece.path, writer_opts)?;
+    }
+    Ok(pieces)
+}
+
+fn piece_path(opts: &SplitOptions, name: &str) -> String {
+    Path::new(&opts.dir)
+        .join(format!("{}_{}.{}", opts.prefix, name, opts.format))
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// a value as part of a file name, `Bosnia-Herzegovina` stays, `a/b` becomes `a_b`
+fn file_name_part(value: &Value) -> String {
+    let text = match value {
+        Value::Null => return "empty".to_string(),
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    };
+    let name: String = text
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    match name.trim_matches('.') {
+        "" => "empty".to_string(),
+        name => name.to_string(),
+    }
+}
+
+/// the default prefix, the input file name without extensions or `split` for stdin
+pub fn split_prefix(input: &str) -> String {
+    if input == "-" {
+        return "split".to_string();
+    }
+    Path::new(strip_compression_ext(input))
+        .file_stem()
+        .map_or_else(
+            || "split".into(),
+            |stem| stem.to_string_lossy().into_owned(),
+        )
+}
+
+impl Spool {
+    fn new(dir: PathBuf, max_open: usize) -> Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            open: HashMap::new(),
+            max_open,
+            clock: 0,
+        })
+    }
+
+    fn path(&self, group: usize) -> PathBuf {
+        self.dir.join(format!("{}.ndjson", group))
+    }
+
+    fn write(&mut self, group: usize, record: &Record) -> Result<()> {
+        self.clock += 1;
+        if !self.open.contains_key(&group) {
+            if self.open.len() >= self.max_open {
+                let oldest = self
+                    .open
+                    .iter()
+                    .min_by_key(|(_, (used, _))| *used)
+                    .map(|(&group, _)| group)
+                    .expect("max_open is at least 1");
+                let (_, mut writer) = self.open.remove(&oldest).expect("found above");
+                writer.flush()?;
+            }
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(self.path(group))?;
+            self.open.insert(group, (self.clock, BufWriter::new(file)));
+        }
+        let (used, writer) = self.open.get_mut(&group).expect("opened above");
+        *used = self.clock;
+        serde_json::to_writer(&mut *writer, record)?;
+        writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<()> {
+        for (_, (_, mut writer)) in self.open.drain() {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    fn read(&self, group: usize) -> Result<impl Iterator<Item = Result<Record>>> {
+        let reader = BufReader::new(File::open(self.path(group))?);
+        Ok(reader.lines().map(|line| Ok(serde_json::from_str(&line?)?)))
+    }
+}
+
+impl Drop for Spool {
+    fn drop(&mut self) {
+        self.open.clear();
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+impl fmt::Display for SplitPiece {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({} records)", self.path, self.records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::csv_reader::open_csv;
+    use crate::utils::temp_path;
+
+    fn juventus() -> Result<RecordStream> {
+        open_csv("assets/juventus.csv", &CsvReaderOptions::default())
+    }
+
+    fn split_opts(dir: &str, mode: SplitMode, format: OutputFormat) -> SplitOptions {
+        let dir = temp_path(dir);
+        let _ = fs::remove_dir_all(&dir);
+        SplitOptions {
+            mode,
+            dir: dir.to_string_lossy().into_owned(),
+            prefix: "juventus".to_string(),
+            format,
+            max_open: 64,
+        }
+    }
+
+    fn names(pieces: &[SplitPiece]) -> Vec<String> {
+        pieces
+            .iter()
+            .map(|p| {
+                Path::new(&p.path)
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_split_rows() -> Result<()> {
+        let opts = split_opts("cli_split_rows", SplitMode::Rows(10), OutputFormat::Csv);
+        let pieces = split(juventus()?, &opts, &CsvWriterOptions::default())?;
+        assert_eq!(
+            names(&pieces),
+            vec![
+                "juventus_0001.csv",
+                "juventus_0002.csv",
+                "juventus_0003.csv"
+            ]
+        );
+        let counts: Vec<usize> = pieces.iter().map(|p| p.records).collect();
+        assert_eq!(counts, vec![10, 10, 7]);
+
+        let header = "Name,Position,DOB,Nationality,Kit Number";
+        let mut lines = Vec::new();
+        for piece in &pieces {
+            let content = fs::read_to_string(&piece.path)?;
+            assert!(content.starts_with(header), "{}", piece.path);
+            lines.extend(content.lines().skip(1).map(String::from));
+        }
+        let original = fs::read_to_string("assets/juventus.csv")?;
+        assert_eq!(lines, original.lines().skip(1).collect::<Vec<_>>());
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_by_with_one_open_file() -> Result<()> {
+        let mut opts = split_opts(
+            "cli_split_by",
+            SplitMode::By("Nationality".to_string()),
+            OutputFormat::Json,
+        );
+        // every record of a new nationality evicts the previous spool file
+        opts.max_open = 1;
+        let pieces = split(juventus()?, &opts, &CsvWriterOptions::default())?;
+        assert_eq!(pieces.len(), 14);
+        assert_eq!(
+            names(&pieces)[..2],
+            ["juventus_Poland.json", "juventus_Italy.json"]
+        );
+        assert_eq!(pieces.iter().map(|p| p.records).sum::<usize>(), 27);
+
+        let italy: Vec<Value> = serde_json::from_str(&fs::read_to_string(&pieces[1].path)?)?;
+        assert_eq!(italy.len(), pieces[1].records);
+        assert!(italy.iter().all(|r| r["Nationality"] == "Italy"));
+        assert!(italy[0]["Kit Number"].is_i64());
+        // only the pieces are left, the spool is gone
+        assert_eq!(fs::read_dir(&opts.dir)?.count(), 14);
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_by_to_binary_format() -> Result<()> {
+        let opts = split_opts(
+            "cli_split_xlsx",
+            SplitMode::By("Position".to_string()),
+            OutputFormat::Xlsx,
+        );
+        let pieces = split(juventus()?, &opts, &CsvWriterOptions::default())?;
+        assert_eq!(names(&pieces)[0], "juventus_Goalkeeper.xlsx");
+        let goalkeepers = crate::process::csv_sheet::open_sheet(&pieces[0].path, None, None, true)?;
+        assert_eq!(goalkeepers.rows.count(), pieces[0].records);
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_name_part() {
+        assert_eq!(
+            file_name_part(&Value::from("Bosnia-Herzegovina")),
+            "Bosnia-Herzegovina"
+        );
+        assert_eq!(file_name_part(&Value::from("a/b c")), "a_b_c");
+        assert_eq!(file_name_part(&Value::from("..")), "empty");
+        assert_eq!(file_name_part(&Value::from(7)), "7");
+        assert_eq!(file_name_part(&Value::Null), "empty");
+        assert_eq!(split_prefix("data/players.csv.gz"), "players");
+        assert_eq!(split_prefix("-"), "split");
+    }
+
+    #[test]
+    fn test_split_errors() -> Result<()> {
+        let err = |mode: SplitMode| -> Result<String> {
+            let opts = split_opts("cli_split_err", mode, OutputFormat::Csv);
+            Ok(split(juventus()?, &opts, &CsvWriterOptions::default())
+                .unwrap_err()
+                .to_string())
+        };
+        assert_eq!(err(SplitMode::Rows(0))?, "--rows must be at least 1");
+        assert_eq!(
+            err(SplitMode::By("Age".to_string()))?,
+            "--by: unknown column \"Age\""
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,493 @@
+use crate::opt::{InputFormat, StatsFormat};
+use crate::process::csv_convert::open_input;
+use crate::process::csv_expr::sort_order;
+use crate::process::csv_reader::{ColumnType, CsvReaderOptions, RecordStream};
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::hash::{Hash, Hasher};
+
+#[derive(Debug, Clone)]
+pub struct StatsOptions {
+    /// number of most frequent values reported per column
+    pub top: usize,
+    /// count distinct values with HyperLogLog and top values with a bounded summary, so memory
+    /// doesn't grow with the number of distinct values
+    pub approximate: bool,
+}
+
+/// profile of a single column
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ColumnStats {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: String,
+    pub count: u64,
+    pub nulls: u64,
+    pub distinct: u64,
+    /// whether `distinct` and the `top` counts are estimates
+    pub approximate: bool,
+    pub min: Value,
+    pub max: Value,
+    /// only for numeric columns
+    pub mean: Option<f64>,
+    /// sample standard deviation, only for numeric columns
+    pub stddev: Option<f64>,
+    pub top: Vec<ValueCount>,
+    /// value lengths in characters, bucketed by powers of two
+    pub lengths: Vec<LengthBucket>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ValueCount {
+    pub value: String,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LengthBucket {
+    pub min: usize,
+    pub max: usize,
+    pub count: u64,
+}
+
+/// accumulates the profile of one column in a single pass
+struct Profiler {
+    ty: ColumnType,
+    count: u64,
+    nulls: u64,
+    min: Value,
+    max: Value,
+    // Welford's online mean and variance
+    numbers: u64,
+    mean: f64,
+    m2: f64,
+    frequencies: Frequencies,
+    distinct: Option<HyperLogLog>,
+    /// bucket `i` counts lengths in `[2^(i-1), 2^i - 1]`, bucket 0 the empty values
+    lengths: Vec<u64>,
+}
+
+/// value counts, exact or a Misra-Gries summary holding at most `capacity` values
+struct Frequencies {
+    counts: HashMap<String, u64>,
+    capacity: Option<usize>,
+}
+
+struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+/// 2^12 registers, a standard error of about 1.6%
+const HLL_PRECISION: u32 = 12;
+
+pub fn process_csv_stats(
+    input: &str,
+    from: InputFormat,
+    opts: &CsvReaderOptions,
+    stats_opts: &StatsOptions,
+) -> Result<Vec<ColumnStats>> {
+    let stream = open_input(input, from, opts)?;
+    profile(stream, stats_opts)
+}
+
+/// profile every column of a stream in one pass over the records
+pub fn profile(stream: RecordStream, opts: &StatsOptions) -> Result<Vec<ColumnStats>> {
+    let mut profilers: Vec<Profiler> = stream.columns.iter().map(|_| Profiler::new(opts)).collect();
+    for record in stream.rows {
+        let record = record?;
+        for (column, profiler) in stream.columns.iter().zip(profilers.iter_mut()) {
+            profiler.add(record.get(&column.name).unwrap_or(&Value::Null));
+        }
+    }
+    Ok(stream
+        .columns
+        .iter()
+        .zip(profilers)
+        .map(|(column, profiler)| profiler.finish(&column.name, opts))
+        .collect())
+}
+
+pub fn render_stats(stats: &[ColumnStats], format: StatsFormat) -> Result<String> {
+    match format {
+        StatsFormat::Json => Ok(serde_json::to_string_pretty(stats)?),
+        StatsFormat::Table => Ok(render_table(stats)),
+    }
+}
+
+impl Profiler {
+    fn new(opts: &StatsOptions) -> Self {
+        let (capacity, distinct) = if opts.approximate {
+            (Some(opts.top.max(1) * 10), Some(HyperLogLog::new()))
+        } else {
+            (None, None)
+        };
+        Self {
+            ty: ColumnType::Null,
+            count: 0,
+            nulls: 0,
+            min: Value::Null,
+            max: Value::Null,
+            numbers: 0,
+            mean: 0.0,
+            m2: 0.0,
+            frequencies: Frequencies {
+                counts: HashMap::new(),
+                capacity,
+            },
+            distinct,
+            lengths: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, value: &Value) {
+        self.count += 1;
+        if value.is_null() {
+            self.nulls += 1;
+            return;
+        }
+        self.ty = self.ty.merge(match value {
+            Value::String(s) => ColumnType::of(s),
+            v => ColumnType::of_value(v),
+        });
+        if self.min.is_null() || sort_order(value, &self.min).is_lt() {
+            self.min = value.clone();
+        }
+        if self.max.is_null() || sort_order(value, &self.max).is_gt() {
+            self.max = value.clone();
+        }
+        if let Some(x) = value.as_f64() {
+            self.numbers += 1;
+            let delta = x - self.mean;
+            self.mean += delta / self.numbers as f64;
+            self.m2 += delta * (x - self.mean);
+        }
+
+        let text = match value {
+            Value::String(s) => s.clone(),
+            v => v.to_string(),
+        };
+        let bucket = length_bucket(text.chars().count());
+        if self.lengths.len() <= bucket {
+            self.lengths.resize(bucket + 1, 0);
+        }
+        self.lengths[bucket] += 1;
+        if let Some(distinct) = &mut self.distinct {
+            distinct.insert(&text);
+        }
+        self.frequencies.add(text);
+    }
+
+    fn finish(self, name: &str, opts: &StatsOptions) -> ColumnStats {
+        let numeric = matches!(self.ty, ColumnType::Integer | ColumnType::Float);
+        let distinct = match &self.distinct {
+            Some(hll) => hll.estimate(),
+            None => self.frequencies.counts.len() as u64,
+        };
+        let mut top: Vec<ValueCount> = self
+            .frequencies
+            .counts
+            .into_iter()
+            .map(|(value, count)| ValueCount { value, count })
+            .collect();
+        top.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+        top.truncate(opts.top);
+        let lengths = self
+            .lengths
+            .iter()
+            .enumerate()
+            .filter(|(_, &count)| count > 0)
+            .map(|(i, &count)| {
+                let (min, max) = bucket_range(i);
+                LengthBucket { min, max, count }
+            })
+            .collect();
+        ColumnStats {
+            name: name.to_string(),
+            ty: self.ty.to_string(),
+            count: self.count,
+            nulls: self.nulls,
+            distinct,
+            approximate: opts.approximate,
+            min: self.min,
+            max: self.max,
+            mean: (numeric && self.numbers > 0).then_some(self.mean),
+            stddev: (numeric && self.numbers > 1)
+                .then(|| (self.m2 / (self.numbers - 1) as f64).sqrt()),
+            top,
+            lengths,
+        }
+    }
+}
+
+fn length_bucket(len: usize) -> usize {
+    (usize::BITS - len.leading_zeros()) as usize
+}
+
+fn bucket_range(bucket: usize) -> (usize, usize) {
+    match bucket {
+        0 => (0, 0),
+        i => (1 << (i - 1), (1 << i) - 1),
+    }
+}
+
+impl Frequencies {
+    fn add(&mut self, value: String) {
+        if let Some(count) = self.counts.get_mut(&value) {
+            *count += 1;
+            return;
+        }
+        match self.capacity {
+            Some(capacity) if self.counts.len() >= capacity => {
+                // no room: the new value and every tracked value lose one occurrence
+                self.counts.retain(|_, count| {
+                    *count -= 1;
+                    *count > 0
+                });
+            }
+            _ => {
+                self.counts.insert(value, 1);
+            }
+        }
+    }
+}
+
+impl HyperLogLog {
+    fn new() -> Self {
+        Self {
+            registers: vec![0; 1 << HLL_PRECISION],
+        }
+    }
+
+    fn insert(&mut self, value: &str) {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        let hash = hasher.finish();
+        let index = (hash >> (64 - HLL_PRECISION)) as usize;
+        // position of the first set bit in the remaining bits, the sentinel bounds it
+        let rest = (hash << HLL_PRECISION) | (1 << (HLL_PRECISION - 1));
+        let rank = rest.leading_zeros() as u8 + 1;
+        self.registers[index] = self.registers[index].max(rank);
+    }
+
+    fn estimate(&self) -> u64 {
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+        let estimate = alpha * m * m / sum;
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        if estimate <= 2.5 * m && zeros > 0 {
+            // linear counting is more accurate for small cardinalities
+            (m * (m / zeros as f64).ln()).round() as u64
+        } else {
+            estimate.round() as u64
+        }
+    }
+}
+
+fn render_table(stats: &[ColumnStats]) -> String {
+    let header = [
+        "column", "type", "count", "nulls", "distinct", "min", "max", "mean", "stddev",
+    ];
+    let rows: Vec<Vec<String>> = stats
+        .iter()
+        .map(|s| {
+            let distinct = if s.approximate {
+                format!("~{}", s.distinct)
+            } else {
+                s.distinct.to_string()
+            };
+            vec![
+                s.name.clone(),
+                s.ty.clone(),
+                s.count.to_string(),
+                s.nulls.to_string(),
+                distinct,
+                display_value(&s.min),
+                display_value(&s.max),
+                s.mean.map(|m| format!("{:.2}", m)).unwrap_or_default(),
+                s.stddev.map(|d| format!("{:.2}", d)).unwrap_or_default(),
+            ]
+        })
+        .collect();
+    let widths: Vec<usize> = (0..header.len())
+        .map(|i| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .chain([header[i].len()])
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+
+    let mut out = String::new();
+    let line = |cells: Vec<&str>| {
+        let cells: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, &width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        cells.join("  ").trim_end().to_string()
+    };
+    let _ = writeln!(out, "{}", line(header.to_vec()));
+    let rule: Vec<String> = widths.iter().map(|&w| "-".repeat(w)).collect();
+    let _ = writeln!(out, "{}", line(rule.iter().map(String::as_str).collect()));
+    for row in &rows {
+        let _ = writeln!(out, "{}", line(row.iter().map(String::as_str).collect()));
+    }
+
+    for s in stats {
+        let _ = writeln!(out, "\n{}", s.name);
+        let top: Vec<String> = s
+            .top
+            .iter()
+            .map(|v| format!("{} ({})", v.value, v.count))
+            .collect();
+        let _ = writeln!(out, "  top:     {}", top.join(", "));
+        let lengths: Vec<String> = s
+            .lengths
+            .iter()
+            .map(|b| match b.min == b.max {
+                true => format!("{}: {}", b.min, b.count),
+                false => format!("{}-{}: {}", b.min, b.max, b.count),
+            })
+            .collect();
+        let _ = writeln!(out, "  lengths: {}", lengths.join(", "));
+    }
+    out
+}
+
+fn display_value(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::csv_reader::open_csv;
+
+    fn juventus(approximate: bool) -> Result<Vec<ColumnStats>> {
+        let stream = open_csv("assets/juventus.csv", &CsvReaderOptions::default())?;
+        profile(
+            stream,
+            &StatsOptions {
+                top: 3,
+                approximate,
+            },
+        )
+    }
+
+    #[test]
+    fn test_profile_juventus() -> Result<()> {
+        let stats = juventus(false)?;
+        assert_eq!(stats.len(), 5);
+
+        let nationality = &stats[3];
+        assert_eq!(nationality.ty, "string");
+        assert_eq!((nationality.count, nationality.nulls), (27, 0));
+        assert_eq!(nationality.distinct, 14);
+        assert_eq!(nationality.min, Value::from("Argentina"));
+        assert_eq!(nationality.max, Value::from("Wales"));
+        assert_eq!(
+            nationality.top[0],
+            ValueCount {
+                value: "Italy".to_string(),
+                count: 8
+            }
+        );
+        assert_eq!(nationality.mean, None);
+
+        let kit = &stats[4];
+        assert_eq!(kit.ty, "integer");
+        assert_eq!(kit.min, Value::from(1));
+        assert_eq!(kit.max, Value::from(77));
+        let numbers: Vec<f64> = open_csv("assets/juventus.csv", &CsvReaderOptions::default())?
+            .rows
+            .map(|r| Ok(r?["Kit Number"].as_f64().unwrap_or_default()))
+            .collect::<Result<_>>()?;
+        let mean = numbers.iter().sum::<f64>() / numbers.len() as f64;
+        let variance =
+            numbers.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (numbers.len() - 1) as f64;
+        assert!((kit.mean.unwrap_or_default() - mean).abs() < 1e-9);
+        assert!((kit.stddev.unwrap_or_default() - variance.sqrt()).abs() < 1e-9);
+        // one and two digit kit numbers
+        assert_eq!(
+            kit.lengths,
+            vec![
+                LengthBucket {
+                    min: 1,
+                    max: 1,
+                    count: 8
+                },
+                LengthBucket {
+                    min: 2,
+                    max: 3,
+                    count: 19
+                }
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_approximate_profile() -> Result<()> {
+        let exact = juventus(false)?;
+        let approximate = juventus(true)?;
+        for (e, a) in exact.iter().zip(&approximate) {
+            // small cardinalities are exact under linear counting
+            assert_eq!(e.distinct, a.distinct, "{}", e.name);
+            assert_eq!(e.top[0].value, a.top[0].value, "{}", e.name);
+            assert!(a.approximate);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_hyperloglog_estimate() {
+        let mut hll = HyperLogLog::new();
+        for i in 0..100_000 {
+            hll.insert(&i.to_string());
+        }
+        let estimate = hll.estimate() as f64;
+        assert!(
+            (estimate - 100_000.0).abs() / 100_000.0 < 0.05,
+            "{}",
+            estimate
+        );
+    }
+
+    #[test]
+    fn test_misra_gries_keeps_heavy_hitters() {
+        let mut frequencies = Frequencies {
+            counts: HashMap::new(),
+            capacity: Some(4),
+        };
+        for i in 0..1000 {
+            frequencies.add("hot".to_string());
+            frequencies.add(format!("cold {}", i));
+        }
+        assert!(frequencies.counts.len() <= 4);
+        assert!(frequencies.counts["hot"] > 0);
+    }
+
+    #[test]
+    fn test_render() -> Result<()> {
+        let stats = juventus(false)?;
+        let table = render_stats(&stats, StatsFormat::Table)?;
+        let mut lines = table.lines();
+        assert!(lines.next().unwrap_or_default().starts_with("column"));
+        assert!(table.contains("Kit Number   integer  27     0      27"));
+        assert!(table.contains("  top:     Italy (8), Brazil (3), Argentina (2)"));
+        let json: Value = serde_json::from_str(&render_stats(&stats, StatsFormat::Json)?)?;
+        assert_eq!(json[4]["type"], Value::from("integer"));
+        assert_eq!(json[4]["top"][0]["count"], Value::from(1));
+        Ok(())
+    }
+}
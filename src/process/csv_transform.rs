@@ -0,0 +1,270 @@
+use crate::process::csv_dates::{parse_dates, DateColumn, DerivedColumn};
+use crate::process::csv_expr::{sort_records, Expr, SortKey};
+use crate::process::csv_reader::{Column, Record, RecordStream};
+use anyhow::{anyhow, Result};
+use chrono::{Local, NaiveDate};
+use serde_json::Value;
+use std::collections::{HashSet, VecDeque};
+
+/// row and column trimming applied between reading and writing
+///
+/// The steps run in a fixed order: dates, filter, dedupe, sort, head, tail and finally
+/// select/exclude, so `--where` and `--sort-by` may use normalized dates, derived columns and
+/// columns that aren't part of the output. Only sorting and tail need to buffer records, tail
+/// keeps at most `n` of them.
+#[derive(Debug, Clone, Default)]
+pub struct Transform {
+    pub select: Vec<String>,
+    pub exclude: Vec<String>,
+    pub filter: Option<Expr>,
+    pub sort_by: Vec<SortKey>,
+    /// keep the first record of every distinct combination of these columns
+    pub unique_by: Vec<String>,
+    pub head: Option<usize>,
+    pub tail: Option<usize>,
+    /// date columns normalized to ISO 8601
+    pub dates: Vec<DateColumn>,
+    /// columns computed from date columns
+    pub derive: Vec<DerivedColumn>,
+    /// reference date of `age()`, today if unset
+    pub as_of: Option<NaiveDate>,
+}
+
+type Rows = Box<dyn Iterator<Item = Result<Record>>>;
+
+impl Transform {
+    pub fn is_empty(&self) -> bool {
+        self.select.is_empty()
+            && self.exclude.is_empty()
+            && self.filter.is_none()
+            && self.sort_by.is_empty()
+            && self.unique_by.is_empty()
+            && self.head.is_none()
+            && self.tail.is_none()
+            && self.dates.is_empty()
+            && self.derive.is_empty()
+    }
+
+    pub fn apply(&self, stream: RecordStream) -> Result<RecordStream> {
+        if self.is_empty() {
+            return Ok(stream);
+        }
+        let stream = if self.dates.is_empty() && self.derive.is_empty() {
+            stream
+        } else {
+            let as_of = self.as_of.unwrap_or_else(|| Local::now().date_naive());
+            parse_dates(stream, &self.dates, &self.derive, as_of)?
+        };
+        let RecordStream { columns, mut rows } = stream;
+        check_columns("--select", &self.select, &columns)?;
+        check_columns("--exclude", &self.exclude, &columns)?;
+        check_columns("--unique-by", &self.unique_by, &columns)?;
+        let sort_by: Vec<_> = self.sort_by.iter().map(|key| key.column.clone()).collect();
+        check_columns("--sort-by", &sort_by, &columns)?;
+        if let Some(filter) = &self.filter {
+            filter
+                .check_columns(&columns)
+                .map_err(|e| anyhow!("--where: {}", e))?;
+        }
+
+        if let Some(filter) = self.filter.clone() {
+            rows = Box::new(rows.filter(move |record| match record {
+                Ok(record) => filter.matches(record),
+                Err(_) => true,
+            }));
+        }
+        if !self.unique_by.is_empty() {
+            let unique_by = self.unique_by.clone();
+            let mut seen = HashSet::new();
+            rows = Box::new(rows.filter(move |record| match record {
+                Ok(record) => {
+                    let key: Vec<&Value> = unique_by
+                        .iter()
+                        .map(|name| record.get(name).unwrap_or(&Value::Null))
+                        .collect();
+                    seen.insert(serde_json::to_string(&key).unwrap_or_default())
+                }
+                Err(_) => true,
+            }));
+        }
+        if !self.sort_by.is_empty() {
+            let mut records = rows.collect::<Result<Vec<_>>>()?;
+            sort_records(&mut records, &self.sort_by);
+            rows = Box::new(records.into_iter().map(Ok));
+        }
+        if let Some(head) = self.head {
+            rows = Box::new(rows.take(head));
+        }
+        if let Some(tail) = self.tail {
+            rows = last(rows, tail)?;
+        }
+
+        let columns = self.project(columns);
+        if self.select.is_empty() && self.exclude.is_empty() {
+            return Ok(RecordStream { columns, rows });
+        }
+        let names: Vec<String> = columns.iter().map(|c| c.name.clone()).collect();
+        let rows = rows.map(move |record| {
+            let mut record = record?;
+            Ok(names
+                .iter()
+                .map(|name| {
+                    let value = record.remove(name).unwrap_or(Value::Null);
+                    (name.clone(), value)
+                })
+                .collect())
+        });
+        Ok(RecordStream {
+            columns,
+            rows: Box::new(rows),
+        })
+    }
+
+    /// output columns in `--select` order, or input order without `--select`, a column selected
+    /// twice is only output once
+    fn project(&self, columns: Vec<Column>) -> Vec<Column> {
+        let selected = if self.select.is_empty() {
+            columns
+        } else {
+            let mut seen = HashSet::new();
+            self.select
+                .iter()
+                .filter(|name| seen.insert(name.as_str()))
+                .filter_map(|name| columns.iter().find(|c| &c.name == name).cloned())
+                .collect()
+        };
+        selected
+            .into_iter()
+            .filter(|c| !self.exclude.contains(&c.name))
+            .collect()
+    }
+}
+
+fn check_columns(option: &str, names: &[String], columns: &[Column]) -> Result<()> {
+    match names
+        .iter()
+        .find(|name| !columns.iter().any(|c| &c.name == *name))
+    {
+        Some(name) => Err(anyhow!(
+            "{}: unknown column {:?}, available: {:?}",
+            option,
+            name,
+            columns.iter().map(|c| &c.name).collect::<Vec<_>>()
+        )),
+        None => Ok(()),
+    }
+}
+
+/// the last `n` records, holding no more than `n` at a time
+fn last(rows: Rows, n: usize) -> Result<Rows> {
+    let mut records = VecDeque::with_capacity(n.min(1024));
+    for record in rows {
+        records.push_back(record?);
+        if records.len() > n {
+            records.pop_front();
+        }
+    }
+    Ok(Box::new(records.into_iter().map(Ok)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::csv_reader::{open_csv, CsvReaderOptions};
+
+    fn juventus(transform: &Transform) -> Result<(Vec<String>, Vec<Record>)> {
+        let stream = open_csv("assets/juventus.csv", &CsvReaderOptions::default())?;
+        let stream = transform.apply(stream)?;
+        let names = stream.columns.iter().map(|c| c.name.clone()).collect();
+        Ok((names, stream.rows.collect::<Result<Vec<_>>>()?))
+    }
+
+    fn names(records: &[Record]) -> Vec<&str> {
+        records
+            .iter()
+            .map(|r| r["Name"].as_str().unwrap_or_default())
+            .collect()
+    }
+
+    #[test]
+    fn test_where_sort_and_select() -> Result<()> {
+        let transform = Transform {
+            select: vec!["Name".to_string(), "Kit Number".to_string()],
+            filter: Some("Position == Goalkeeper".parse()?),
+            sort_by: vec!["Kit Number:desc".parse()?],
+            head: Some(3),
+            ..Default::default()
+        };
+        let (columns, records) = juventus(&transform)?;
+        assert_eq!(columns, vec!["Name", "Kit Number"]);
+        assert_eq!(
+            names(&records),
+            vec!["Gianluigi Buffon", "Mattia Perin", "Carlo Pinsoglio"]
+        );
+        assert_eq!(records[0].len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unique_by_tail_and_exclude() -> Result<()> {
+        let transform = Transform {
+            exclude: vec!["DOB".to_string(), "Kit Number".to_string()],
+            unique_by: vec!["Position".to_string()],
+            tail: Some(2),
+            ..Default::default()
+        };
+        let (columns, records) = juventus(&transform)?;
+        assert_eq!(columns, vec!["Name", "Position", "Nationality"]);
+        assert_eq!(names(&records), vec!["Paulo Dybala", "Gonzalo Higuaín"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_columns() -> Result<()> {
+        let transform = Transform {
+            filter: Some("Age > 30".parse()?),
+            ..Default::default()
+        };
+        let err = juventus(&transform).unwrap_err().to_string();
+        assert_eq!(err, "--where: unknown column \"Age\"");
+        let transform = Transform {
+            select: vec!["name".to_string()],
+            ..Default::default()
+        };
+        let err = juventus(&transform).unwrap_err().to_string();
+        assert!(
+            err.starts_with("--select: unknown column \"name\""),
+            "{}",
+            err
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_twice() -> Result<()> {
+        let transform = Transform {
+            select: vec![
+                "Kit Number".to_string(),
+                "Name".to_string(),
+                "Kit Number".to_string(),
+            ],
+            head: Some(1),
+            ..Default::default()
+        };
+        let (names, rows) = juventus(&transform)?;
+        assert_eq!(names, vec!["Kit Number", "Name"]);
+        assert_eq!(rows[0].len(), 2);
+        assert_eq!(rows[0]["Kit Number"], 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sort_key() -> Result<()> {
+        let key: SortKey = "Kit Number:DESC".parse()?;
+        assert_eq!((key.column.as_str(), key.descending), ("Kit Number", true));
+        let key: SortKey = "a:b".parse()?;
+        assert_eq!((key.column.as_str(), key.descending), ("a:b", false));
+        assert!(":desc".parse::<SortKey>().is_err());
+        Ok(())
+    }
+}
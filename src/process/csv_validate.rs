@@ -0,0 +1,604 @@
+use crate::opt::InputFormat;
+use crate::process::csv_convert::open_input;
+use crate::process::csv_encoding::decode_reader;
+use crate::process::csv_expr::compare;
+use crate::process::csv_reader::{ColumnType, CsvReaderOptions, Record};
+use crate::utils::get_decompressed_reader;
+use anyhow::{anyhow, Result};
+use chrono::NaiveDate;
+use csv::{Position, StringRecord};
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+use std::rc::Rc;
+
+/// the expected shape of a file, loaded from JSON or YAML
+///
+/// ```yaml
+/// strict: true            # reject columns the schema doesn't list
+/// columns:
+///   - name: Kit Number
+///     type: integer       # string, integer, float, bool or date (YYYY-MM-DD)
+///     required: true      # the column must be present, default true
+///     nullable: false     # empty cells are allowed, default true
+///     min: 1              # inclusive bounds for numbers and dates
+///     max: 99
+///     unique: true
+///   - name: Position
+///     enum: [Goalkeeper, Centre-Back]
+///   - name: Name
+///     pattern: "^[A-Z]"   # regex, unanchored
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Schema {
+    #[serde(default)]
+    pub strict: bool,
+    pub columns: Vec<ColumnSchema>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ColumnSchema {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: Option<String>,
+    #[serde(default = "default_true")]
+    pub required: bool,
+    #[serde(default = "default_true")]
+    pub nullable: bool,
+    pub pattern: Option<String>,
+    #[serde(rename = "enum")]
+    pub values: Option<Vec<Value>>,
+    pub min: Option<Value>,
+    pub max: Option<Value>,
+    #[serde(default)]
+    pub unique: bool,
+}
+
+/// a single violation, `line` is the physical line for CSV input and `None` for other inputs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    pub line: Option<u64>,
+    /// one-based record number, 0 for the header
+    pub record: u64,
+    pub column: Option<String>,
+    pub message: String,
+}
+
+/// a column schema with its pattern compiled and type resolved
+struct ColumnRule {
+    schema: ColumnSchema,
+    ty: ColumnType,
+    pattern: Option<Regex>,
+    /// text of every value seen so far and the record it was first seen in
+    seen: HashMap<String, (Option<u64>, u64)>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Schema {
+    /// load a schema, YAML unless the extension is .json
+    pub fn load(path: &str) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+        let is_json = Path::new(path)
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        let schema = if is_json {
+            serde_json::from_str(&content)?
+        } else {
+            serde_yaml::from_str(&content)?
+        };
+        Ok(schema)
+    }
+}
+
+pub fn process_csv_validate(
+    input: &str,
+    from: InputFormat,
+    opts: &CsvReaderOptions,
+    schema: &Schema,
+) -> Result<Vec<ValidationError>> {
+    let mut validator = Validator::new(schema)?;
+    if from == InputFormat::Csv {
+        // read raw cells so errors point at physical lines, the header is read as a record too
+        let seen = Rc::new(RefCell::new(SeenBytes::default()));
+        let tap = Tap {
+            inner: decode_reader(get_decompressed_reader(input)?, opts.encoding)?,
+            seen: seen.clone(),
+        };
+        let mut records = opts
+            .builder()
+            .has_headers(false)
+            .from_reader(tap)
+            .into_records();
+        let line_of = |record: &StringRecord| {
+            let position = record.position()?;
+            Some(seen.borrow_mut().line(position, opts.comment))
+        };
+        let mut headers: Vec<String> = Vec::new();
+        if opts.has_headers {
+            if let Some(header) = records.next() {
+                let header = header?;
+                headers = header.iter().map(String::from).collect();
+                validator.check_header(&headers, line_of(&header), 0);
+            }
+        }
+        for (i, record) in records.enumerate() {
+            let record = record?;
+            let line = line_of(&record);
+            // without a header every record brings its own columns
+            if !opts.has_headers {
+                headers = (0..record.len())
+                    .map(|i| format!("col_{}", i + 1))
+                    .collect();
+                validator.check_header(&headers, line, i as u64 + 1);
+            }
+            let row: Record = headers
+                .iter()
+                .zip(record.iter())
+                .map(|(name, cell)| (name.clone(), ColumnType::of(cell).parse(cell)))
+                .collect();
+            validator.check_record(line, i as u64 + 1, &row);
+        }
+    } else {
+        let stream = open_input(input, from, opts)?;
+        let headers: Vec<String> = stream.columns.iter().map(|c| c.name.clone()).collect();
+        validator.check_header(&headers, None, 0);
+        for (i, record) in stream.rows.enumerate() {
+            validator.check_record(None, i as u64 + 1, &record?);
+        }
+    }
+    Ok(validator.errors)
+}
+
+/// passes input through to the CSV reader, keeping what it read since the last record began
+struct Tap {
+    inner: Box<dyn Read>,
+    seen: Rc<RefCell<SeenBytes>>,
+}
+
+#[derive(Default)]
+struct SeenBytes {
+    /// offset of `bytes[0]` in the input
+    start: u64,
+    bytes: Vec<u8>,
+}
+
+impl Read for Tap {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.seen.borrow_mut().bytes.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+}
+
+impl SeenBytes {
+    /// the line a record starts on, the reader's position is where it started looking for the
+    /// record, before any blank or comment lines it skipped
+    fn line(&mut self, position: &Position, comment: Option<u8>) -> u64 {
+        let skip = usize::try_from(position.byte() - self.start).unwrap_or(usize::MAX);
+        self.bytes.drain(..skip.min(self.bytes.len()));
+        self.start = position.byte();
+        let mut line = position.line();
+        let mut rest = &self.bytes[..];
+        loop {
+            match rest.first() {
+                Some(b'\n') => line += 1,
+                Some(b'\r') => {}
+                Some(&b) if Some(b) == comment => match rest.iter().position(|&b| b == b'\n') {
+                    Some(end) => {
+                        rest = &rest[end..];
+                        continue;
+                    }
+                    None => break,
+                },
+                _ => break,
+            }
+            rest = &rest[1..];
+        }
+        line
+    }
+}
+
+struct Validator {
+    strict: bool,
+    rules: Vec<ColumnRule>,
+    /// columns of the input, a record without one of them has a null there
+    headers: Vec<String>,
+    errors: Vec<ValidationError>,
+}
+
+impl Validator {
+    fn new(schema: &Schema) -> Result<Self> {
+        let rules = schema
+            .columns
+            .iter()
+            .map(|column| {
+                let ty = match column.ty.as_deref() {
+                    None | Some("string") => ColumnType::String,
+                    Some("integer") => ColumnType::Integer,
+                    Some("float") => ColumnType::Float,
+                    Some("bool") => ColumnType::Bool,
+                    Some("date") => ColumnType::Date,
+                    Some(ty) => {
+                        return Err(anyhow!(
+                            "column {:?}: unknown type {:?}, expected string, integer, float, bool or date",
+                            column.name,
+                            ty
+                        ))
+                    }
+                };
+                let pattern = column
+                    .pattern
+                    .as_deref()
+                    .map(Regex::new)
+                    .transpose()
+                    .map_err(|e| anyhow!("column {:?}: invalid pattern: {}", column.name, e))?;
+                Ok(ColumnRule {
+                    schema: column.clone(),
+                    ty,
+                    pattern,
+                    seen: HashMap::new(),
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            strict: schema.strict,
+            rules,
+            headers: Vec::new(),
+            errors: Vec::new(),
+        })
+    }
+
+    fn error(&mut self, line: Option<u64>, record: u64, column: Option<&str>, message: String) {
+        self.errors.push(ValidationError {
+            line,
+            record,
+            column: column.map(String::from),
+            message,
+        });
+    }
+
+    /// check the columns of the header, or of a single record if the input has no header
+    fn check_header(&mut self, headers: &[String], line: Option<u64>, record: u64) {
+        self.headers = headers.to_vec();
+        let missing: Vec<String> = self
+            .rules
+            .iter()
+            .filter(|rule| rule.schema.required && !headers.contains(&rule.schema.name))
+            .map(|rule| rule.schema.name.clone())
+            .collect();
+        for name in missing {
+            self.error(
+                line,
+                record,
+                Some(&name),
+                "missing required column".to_string(),
+            );
+        }
+        if self.strict {
+            let unknown: Vec<String> = headers
+                .iter()
+                .filter(|name| !self.rules.iter().any(|rule| &rule.schema.name == *name))
+                .cloned()
+                .collect();
+            for name in unknown {
+                self.error(
+                    line,
+                    record,
+                    Some(&name),
+                    "column not in schema".to_string(),
+                );
+            }
+        }
+    }
+
+    fn check_record(&mut self, line: Option<u64>, record: u64, row: &Record) {
+        let mut errors = Vec::new();
+        for rule in &mut self.rules {
+            // a column missing from the header is reported once, by `check_header`
+            if !self.headers.contains(&rule.schema.name) {
+                continue;
+            }
+            // a short row under --flexible or a record without the key
+            let value = row.get(&rule.schema.name).unwrap_or(&Value::Null);
+            let name = rule.schema.name.clone();
+            for message in rule.check(value, line, record) {
+                errors.push((name.clone(), message));
+            }
+        }
+        for (name, message) in errors {
+            self.error(line, record, Some(&name), message);
+        }
+    }
+}
+
+impl ColumnRule {
+    fn check(&mut self, value: &Value, line: Option<u64>, record: u64) -> Vec<String> {
+        let mut errors = Vec::new();
+        if value.is_null() {
+            if !self.schema.nullable {
+                errors.push("value is required".to_string());
+            }
+            return errors;
+        }
+        let text = value_text(value);
+        if !matches_type(self.ty, &text) {
+            errors.push(format!("expected {}, found {:?}", self.ty, text));
+            // the remaining checks assume the type
+            return errors;
+        }
+        if let Some(values) = &self.schema.values {
+            let allowed = values.iter().any(|v| value_text(v) == text);
+            if !allowed {
+                errors.push(format!("{:?} is not one of {}", text, list(values)));
+            }
+        }
+        if let Some(pattern) = &self.pattern {
+            if !pattern.is_match(&text) {
+                errors.push(format!("{:?} doesn't match /{}/", text, pattern.as_str()));
+            }
+        }
+        if let Some(min) = &self.schema.min {
+            if compare(value, min) == Some(Ordering::Less) {
+                errors.push(format!("{} is less than the minimum {}", text, min));
+            }
+        }
+        if let Some(max) = &self.schema.max {
+            if compare(value, max) == Some(Ordering::Greater) {
+                errors.push(format!("{} is greater than the maximum {}", text, max));
+            }
+        }
+        if self.schema.unique {
+            match self.seen.get(&text) {
+                Some(&(first_line, first_record)) => errors.push(format!(
+                    "duplicate value {:?}, first seen in {}",
+                    text,
+                    location(first_line, first_record)
+                )),
+                None => {
+                    self.seen.insert(text, (line, record));
+                }
+            }
+        }
+        errors
+    }
+}
+
+fn matches_type(ty: ColumnType, text: &str) -> bool {
+    match ty {
+        ColumnType::Integer => text.parse::<i64>().is_ok(),
+        ColumnType::Float => text.parse::<f64>().is_ok_and(f64::is_finite),
+        ColumnType::Bool => text.eq_ignore_ascii_case("true") || text.eq_ignore_ascii_case("false"),
+        ColumnType::Date => NaiveDate::parse_from_str(text, "%Y-%m-%d").is_ok(),
+        ColumnType::Null | ColumnType::String => true,
+    }
+}
+
+fn list(values: &[Value]) -> String {
+    let values: Vec<String> = values.iter().map(value_text).collect();
+    format!("[{}]", values.join(", "))
+}
+
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+fn location(line: Option<u64>, record: u64) -> String {
+    match (line, record) {
+        (Some(line), _) => format!("line {}", line),
+        (None, 0) => "header".to_string(),
+        (None, record) => format!("record {}", record),
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", location(self.line, self.record))?;
+        if let Some(column) = &self.column {
+            write!(f, ", column {:?}", column)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::write_temp;
+
+    fn juventus_schema() -> Result<Schema> {
+        Ok(serde_yaml::from_str(
+            r#"
+strict: true
+columns:
+  - name: Name
+    nullable: false
+    unique: true
+    pattern: "^[A-Z]"
+  - name: Position
+    enum: [Goalkeeper, Centre-Back, Right-Back, Left-Back, Defensive Midfield,
+           Central Midfield, Right Winger, Left Winger, Second Striker, Centre-Forward]
+  - name: DOB
+  - name: Nationality
+  - name: Kit Number
+    type: integer
+    min: 1
+    max: 99
+    unique: true
+"#,
+        )?)
+    }
+
+    #[test]
+    fn test_juventus_is_valid() -> Result<()> {
+        let errors = process_csv_validate(
+            "assets/juventus.csv",
+            InputFormat::Csv,
+            &CsvReaderOptions::default(),
+            &juventus_schema()?,
+        )?;
+        assert_eq!(errors, vec![]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reports_lines_and_columns() -> Result<()> {
+        let input = write_temp(
+            "cli_validate.csv",
+            "Name,Position,DOB,Kit Number,Extra\n\
+             Alice,Goalkeeper,,1,x\n\
+             \"bob\nsmith\",Striker,,100,x\n\
+             Alice,Goalkeeper,,abc,x\n\
+             ,Goalkeeper,,1,x\n",
+        )?;
+        let errors = process_csv_validate(
+            &input,
+            InputFormat::Csv,
+            &CsvReaderOptions::default(),
+            &juventus_schema()?,
+        )?;
+        let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            errors,
+            vec![
+                "line 1, column \"Nationality\": missing required column",
+                "line 1, column \"Extra\": column not in schema",
+                "line 3, column \"Name\": \"bob\\nsmith\" doesn't match /^[A-Z]/",
+                "line 3, column \"Position\": \"Striker\" is not one of [Goalkeeper, Centre-Back, Right-Back, Left-Back, Defensive Midfield, Central Midfield, Right Winger, Left Winger, Second Striker, Centre-Forward]",
+                "line 3, column \"Kit Number\": 100 is greater than the maximum 99",
+                // the quoted newline moves the next record to line 5
+                "line 5, column \"Name\": duplicate value \"Alice\", first seen in line 2",
+                "line 5, column \"Kit Number\": expected integer, found \"abc\"",
+                "line 6, column \"Name\": value is required",
+                "line 6, column \"Kit Number\": duplicate value \"1\", first seen in line 2",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_short_rows_have_null_cells() -> Result<()> {
+        let input = write_temp(
+            "cli_validate_short.csv",
+            "Name,Nationality,Kit Number\n\
+             Alice,Italy,1\n\
+             Bob\n",
+        )?;
+        let schema: Schema = serde_yaml::from_str(
+            "columns: [{name: Name}, {name: Nationality, nullable: false}, \
+             {name: Kit Number, type: integer, nullable: false}]",
+        )?;
+        let opts = CsvReaderOptions {
+            flexible: true,
+            ..Default::default()
+        };
+        let errors = process_csv_validate(&input, InputFormat::Csv, &opts, &schema)?;
+        let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            errors,
+            vec![
+                "line 3, column \"Nationality\": value is required",
+                "line 3, column \"Kit Number\": value is required",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_header_line_after_comments() -> Result<()> {
+        let input = write_temp(
+            "cli_validate_comment.csv",
+            "# exported from the club database\r\nName,Kit Number\r\nAlice,NaN\r\n\r\n# loan\r\nBob,inf\r\n",
+        )?;
+        let schema: Schema = serde_yaml::from_str(
+            "columns: [{name: Name}, {name: Kit Number, type: float}, {name: DOB}]",
+        )?;
+        let opts = CsvReaderOptions {
+            comment: Some(b'#'),
+            ..Default::default()
+        };
+        let errors = process_csv_validate(&input, InputFormat::Csv, &opts, &schema)?;
+        let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            errors,
+            vec![
+                "line 2, column \"DOB\": missing required column",
+                "line 3, column \"Kit Number\": expected float, found \"NaN\"",
+                "line 6, column \"Kit Number\": expected float, found \"inf\"",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_headerless_records_are_checked_for_width() -> Result<()> {
+        let input = write_temp("cli_validate_headerless.csv", "a,1\nb\nc,3,x\n")?;
+        let schema: Schema =
+            serde_yaml::from_str("strict: true\ncolumns: [{name: col_1}, {name: col_2}]")?;
+        let opts = CsvReaderOptions {
+            has_headers: false,
+            flexible: true,
+            ..Default::default()
+        };
+        let errors = process_csv_validate(&input, InputFormat::Csv, &opts, &schema)?;
+        let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            errors,
+            vec![
+                "line 2, column \"col_2\": missing required column",
+                "line 3, column \"col_3\": column not in schema",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_structured_input_reports_records() -> Result<()> {
+        let input = write_temp(
+            "cli_validate.ndjson",
+            "{\"id\": 1, \"joined\": \"2019-07-01\"}\n{\"id\": \"x\", \"joined\": \"July\"}\n",
+        )?;
+        let schema: Schema = serde_json::from_str(
+            r#"{"columns": [{"name": "id", "type": "integer"}, {"name": "joined", "type": "date", "min": "2000-01-01"}]}"#,
+        )?;
+        let errors = process_csv_validate(
+            &input,
+            InputFormat::Ndjson,
+            &CsvReaderOptions::default(),
+            &schema,
+        )?;
+        let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            errors,
+            vec![
+                "record 2, column \"id\": expected integer, found \"x\"",
+                "record 2, column \"joined\": expected date, found \"July\"",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_schema() -> Result<()> {
+        let schema: Schema = serde_yaml::from_str("columns: [{name: a, type: number}]")?;
+        assert!(Validator::new(&schema).is_err());
+        let schema: Schema = serde_yaml::from_str("columns: [{name: a, pattern: '['}]")?;
+        assert!(Validator::new(&schema).is_err());
+        assert!(serde_yaml::from_str::<Schema>("columns: [{name: a, typo: 1}]").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,635 @@
+use crate::opt::InputFormat;
+use crate::process::csv_convert::open_input;
+use crate::process::csv_expr::sort_order;
+use crate::process::csv_reader::{Column, CsvReaderOptions, RecordStream};
+use crate::process::csv_transform::Transform;
+use anyhow::{anyhow, Result};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{cursor, execute, queue};
+use serde_json::Value;
+use std::io::{stdout, Write};
+use std::str::FromStr;
+use unicode_width::UnicodeWidthChar;
+
+/// a 1-based, inclusive range of records such as `10..20`, `10..` or `..20`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RowRange {
+    pub start: usize,
+    pub end: Option<usize>,
+}
+
+/// records loaded for display, cells keep their values so sorting stays numeric
+#[derive(Debug)]
+pub struct Table {
+    pub columns: Vec<Column>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+/// keys the pager reacts to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Left,
+    Right,
+    Enter,
+    Backspace,
+    Esc,
+    Char(char),
+}
+
+/// interactive state of `csv view --pager`, kept apart from the terminal so it can be tested
+pub struct Pager {
+    table: Table,
+    widths: Vec<usize>,
+    /// table rows in display order
+    order: Vec<usize>,
+    /// first row shown, an index into `order`
+    top: usize,
+    /// leftmost column shown, also the one `s` sorts by
+    column: usize,
+    sort: Option<(usize, bool)>,
+    search: String,
+    /// the search being typed after `/`
+    prompt: Option<String>,
+    /// the row found by the last search, an index into `order`
+    found: Option<usize>,
+    message: Option<String>,
+}
+
+impl Default for RowRange {
+    fn default() -> Self {
+        Self {
+            start: 1,
+            end: None,
+        }
+    }
+}
+
+impl FromStr for RowRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let number = |n: &str, default: Option<usize>| -> Result<Option<usize>> {
+            match (n.trim(), default) {
+                ("", default) => Ok(default),
+                (n, _) => match n.parse() {
+                    Ok(0) | Err(_) => {
+                        Err(anyhow!("expected a record number from 1, found {:?}", n))
+                    }
+                    Ok(n) => Ok(Some(n)),
+                },
+            }
+        };
+        let (start, end) = match s.split_once("..") {
+            Some((start, end)) => (number(start, Some(1))?, number(end, None)?),
+            None => {
+                let n = number(s, None)?;
+                (n, n)
+            }
+        };
+        let start = start.ok_or_else(|| anyhow!("expected START..END, START.. or ..END"))?;
+        if end.is_some_and(|end| end < start) {
+            return Err(anyhow!("the range {} ends before it starts", s));
+        }
+        Ok(Self { start, end })
+    }
+}
+
+pub fn process_csv_view(
+    input: &str,
+    from: InputFormat,
+    opts: &CsvReaderOptions,
+    transform: &Transform,
+    range: RowRange,
+) -> Result<Table> {
+    let stream = transform.apply(open_input(input, from, opts)?)?;
+    Table::load(stream, range)
+}
+
+impl Table {
+    pub fn load(stream: RecordStream, range: RowRange) -> Result<Self> {
+        let take = range.end.map_or(usize::MAX, |end| end + 1 - range.start);
+        let names: Vec<String> = stream.columns.iter().map(|c| c.name.clone()).collect();
+        let rows = stream
+            .rows
+            .skip(range.start - 1)
+            .take(take)
+            .map(|record| {
+                let mut record = record?;
+                Ok(names
+                    .iter()
+                    .map(|name| record.remove(name).unwrap_or(Value::Null))
+                    .collect())
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            columns: stream.columns,
+            rows,
+        })
+    }
+
+    /// the whole table, each column at most `max_width` characters wide
+    pub fn render(&self, max_width: usize) -> String {
+        let widths = self.widths(max_width);
+        let columns: Vec<usize> = (0..self.columns.len()).collect();
+        let order: Vec<usize> = (0..self.rows.len()).collect();
+        let mut out = String::new();
+        for line in self.lines(&order, &columns, &widths) {
+            out.push_str(&line);
+            out.push('\n');
+        }
+        out
+    }
+
+    fn widths(&self, max_width: usize) -> Vec<usize> {
+        self.columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                let cells = self.rows.iter().map(|row| width(&cell_text(&row[i])));
+                cells
+                    .chain(std::iter::once(width(&printable(&column.name))))
+                    .max()
+                    .unwrap_or(0)
+                    .clamp(1, max_width.max(1))
+            })
+            .collect()
+    }
+
+    /// box-drawn lines for the given rows and columns
+    fn lines(&self, rows: &[usize], columns: &[usize], widths: &[usize]) -> Vec<String> {
+        let widths: Vec<usize> = columns.iter().map(|&c| widths[c]).collect();
+        let header: Vec<(String, bool)> = columns
+            .iter()
+            .map(|&c| (printable(&self.columns[c].name), false))
+            .collect();
+        let mut lines = vec![
+            border(&widths, '┌', '┬', '┐'),
+            row_line(&header, &widths),
+            border(&widths, '├', '┼', '┤'),
+        ];
+        for &r in rows {
+            let cells: Vec<(String, bool)> = columns
+                .iter()
+                .map(|&c| {
+                    let value = &self.rows[r][c];
+                    (cell_text(value), value.is_number())
+                })
+                .collect();
+            lines.push(row_line(&cells, &widths));
+        }
+        lines.push(border(&widths, '└', '┴', '┘'));
+        lines
+    }
+}
+
+fn cell_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => printable(s),
+        v => v.to_string(),
+    }
+}
+
+/// text safe to send to the terminal, whitespace controls as spaces and other controls such as
+/// ESC as `�`
+fn printable(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '\n' | '\r' | '\t' => ' ',
+            c if c.is_control() => char::REPLACEMENT_CHARACTER,
+            c => c,
+        })
+        .collect()
+}
+
+/// columns taken by `text`, measured char by char like `truncate` cuts it
+fn width(text: &str) -> usize {
+    text.chars().map(|c| c.width().unwrap_or(0)).sum()
+}
+
+fn border(widths: &[usize], left: char, middle: char, right: char) -> String {
+    let mut line = String::from(left);
+    for (i, width) in widths.iter().enumerate() {
+        if i > 0 {
+            line.push(middle);
+        }
+        line.push_str(&"─".repeat(width + 2));
+    }
+    line.push(right);
+    line
+}
+
+/// `│ a │ b │`, numbers aligned right
+fn row_line(cells: &[(String, bool)], widths: &[usize]) -> String {
+    let mut line = String::from("│");
+    for ((text, right), width) in cells.iter().zip(widths) {
+        let text = truncate(text, *width);
+        let padding = " ".repeat(width.saturating_sub(self::width(&text)));
+        line.push(' ');
+        if *right {
+            line.push_str(&padding);
+            line.push_str(&text);
+        } else {
+            line.push_str(&text);
+            line.push_str(&padding);
+        }
+        line.push_str(" │");
+    }
+    line
+}
+
+/// cut `text` to `width` columns, ending in `…` when something was cut
+fn truncate(text: &str, width: usize) -> String {
+    if self::width(text) <= width {
+        return text.to_string();
+    }
+    let mut out = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w + 1 > width {
+            break;
+        }
+        out.push(c);
+        used += w;
+    }
+    out.push('…');
+    out
+}
+
+impl Pager {
+    pub fn new(table: Table, max_width: usize) -> Self {
+        let widths = table.widths(max_width);
+        let order = (0..table.rows.len()).collect();
+        Self {
+            table,
+            widths,
+            order,
+            top: 0,
+            column: 0,
+            sort: None,
+            search: String::new(),
+            prompt: None,
+            found: None,
+            message: None,
+        }
+    }
+
+    /// react to a key on a screen `height` lines tall, `false` means quit
+    pub fn handle(&mut self, key: Key, height: usize) -> bool {
+        let page = body_height(height).max(1);
+        let last = self.order.len().saturating_sub(page);
+        self.message = None;
+        if let Some(prompt) = &mut self.prompt {
+            match key {
+                Key::Enter => {
+                    self.search = self.prompt.take().unwrap_or_default();
+                    self.find(0);
+                }
+                Key::Esc => self.prompt = None,
+                Key::Backspace => {
+                    prompt.pop();
+                }
+                Key::Char(c) => prompt.push(c),
+                _ => {}
+            }
+            return true;
+        }
+        match key {
+            Key::Char('q') | Key::Esc => return false,
+            Key::Up | Key::Char('k') => self.top = self.top.saturating_sub(1),
+            Key::Down | Key::Char('j') => self.top = (self.top + 1).min(last),
+            Key::PageUp | Key::Char('b') => self.top = self.top.saturating_sub(page),
+            Key::PageDown | Key::Char(' ') => self.top = (self.top + page).min(last),
+            Key::Home | Key::Char('g') => self.top = 0,
+            Key::End | Key::Char('G') => self.top = last,
+            Key::Left | Key::Char('h') => self.column = self.column.saturating_sub(1),
+            Key::Right | Key::Char('l') => {
+                self.column = (self.column + 1).min(self.table.columns.len().saturating_sub(1))
+            }
+            Key::Char('s') => self.sort_by_column(),
+            Key::Char('/') => self.prompt = Some(String::new()),
+            Key::Char('n') => self.find(1),
+            _ => {}
+        }
+        true
+    }
+
+    /// ascending, then descending, then back to file order
+    fn sort_by_column(&mut self) {
+        self.sort = match self.sort {
+            Some((column, false)) if column == self.column => Some((column, true)),
+            Some((column, true)) if column == self.column => None,
+            _ => Some((self.column, false)),
+        };
+        self.order = (0..self.table.rows.len()).collect();
+        if let Some((column, descending)) = self.sort {
+            let rows = &self.table.rows;
+            self.order.sort_by(|&a, &b| {
+                let ordering = sort_order(&rows[a][column], &rows[b][column]);
+                if descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            });
+        }
+        self.top = 0;
+        self.found = None;
+    }
+
+    /// move to the first row matching the search, `skip` rows past the current one
+    fn find(&mut self, skip: usize) {
+        if self.search.is_empty() {
+            return;
+        }
+        let needle = self.search.to_lowercase();
+        let start = self.found.map_or(self.top, |found| found + skip);
+        let n = self.order.len();
+        let hit = (0..n).map(|i| (start + i) % n).find(|&i| {
+            self.table.rows[self.order[i]]
+                .iter()
+                .any(|value| cell_text(value).to_lowercase().contains(&needle))
+        });
+        match hit {
+            Some(i) => {
+                self.top = i;
+                self.found = Some(i);
+            }
+            None => {
+                self.found = None;
+                self.message = Some(format!("not found: {}", self.search));
+            }
+        }
+    }
+
+    /// the lines to draw and the line of the row found by search, if it is on screen
+    pub fn screen(&self, width: usize, height: usize) -> (Vec<String>, Option<usize>) {
+        let mut columns = Vec::new();
+        let mut used = 1;
+        for c in self.column..self.table.columns.len() {
+            let w = self.widths[c] + 3;
+            if !columns.is_empty() && used + w > width {
+                break;
+            }
+            columns.push(c);
+            used += w;
+        }
+        let end = (self.top + body_height(height)).min(self.order.len());
+        let rows = &self.order[self.top..end];
+        let mut lines = self.table.lines(rows, &columns, &self.widths);
+        lines.push(self.status(end));
+        let lines = lines
+            .into_iter()
+            .map(|line| truncate(&line, width))
+            .collect();
+        let found = self
+            .found
+            .filter(|found| (self.top..end).contains(found))
+            .map(|found| found - self.top + 3);
+        (lines, found)
+    }
+
+    fn status(&self, end: usize) -> String {
+        if let Some(prompt) = &self.prompt {
+            return format!("/{}", prompt);
+        }
+        let mut status = format!(
+            "rows {}-{} of {}",
+            (self.top + 1).min(end),
+            end,
+            self.order.len()
+        );
+        if let Some(column) = self.table.columns.get(self.column) {
+            status.push_str(&format!(" · column {}", column.name));
+        }
+        if let Some((column, descending)) = self.sort {
+            let direction = if descending { "desc" } else { "asc" };
+            let name = &self.table.columns[column].name;
+            status.push_str(&format!(" · sorted by {} {}", name, direction));
+        }
+        match &self.message {
+            Some(message) => status.push_str(&format!(" · {}", message)),
+            None => status.push_str(" · ↑↓ scroll ←→ column s sort / search n next q quit"),
+        }
+        status
+    }
+}
+
+/// rows that fit between the header and the status line
+fn body_height(height: usize) -> usize {
+    height.saturating_sub(5)
+}
+
+/// run the pager on the terminal until the user quits
+pub fn run_pager(pager: &mut Pager) -> Result<()> {
+    /// puts the terminal back even when drawing fails
+    struct Restore;
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let _ = execute!(stdout(), cursor::Show, LeaveAlternateScreen);
+            let _ = terminal::disable_raw_mode();
+        }
+    }
+
+    terminal::enable_raw_mode()?;
+    let _restore = Restore;
+    let mut out = stdout();
+    execute!(out, EnterAlternateScreen, cursor::Hide)?;
+    loop {
+        let (width, height) = terminal::size()?;
+        let (lines, found) = pager.screen(width as usize, height as usize);
+        queue!(out, Clear(ClearType::All))?;
+        for (i, line) in lines.iter().enumerate() {
+            queue!(out, cursor::MoveTo(0, i as u16))?;
+            if found == Some(i) {
+                queue!(
+                    out,
+                    SetAttribute(Attribute::Reverse),
+                    Print(line),
+                    SetAttribute(Attribute::Reset)
+                )?;
+            } else {
+                queue!(out, Print(line))?;
+            }
+        }
+        out.flush()?;
+
+        let Event::Key(event) = event::read()? else {
+            continue;
+        };
+        if event.kind != KeyEventKind::Press {
+            continue;
+        }
+        let key = match event.code {
+            KeyCode::Up => Key::Up,
+            KeyCode::Down => Key::Down,
+            KeyCode::PageUp => Key::PageUp,
+            KeyCode::PageDown => Key::PageDown,
+            KeyCode::Home => Key::Home,
+            KeyCode::End => Key::End,
+            KeyCode::Left => Key::Left,
+            KeyCode::Right => Key::Right,
+            KeyCode::Enter => Key::Enter,
+            KeyCode::Backspace => Key::Backspace,
+            KeyCode::Esc => Key::Esc,
+            KeyCode::Char(c) => Key::Char(c),
+            _ => continue,
+        };
+        if !pager.handle(key, height as usize) {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::csv_reader::open_csv;
+
+    fn juventus(range: RowRange) -> Result<Table> {
+        let transform = Transform {
+            select: vec!["Name".into(), "Nationality".into(), "Kit Number".into()],
+            ..Default::default()
+        };
+        let stream = transform.apply(open_csv(
+            "assets/juventus.csv",
+            &CsvReaderOptions::default(),
+        )?)?;
+        Table::load(stream, range)
+    }
+
+    #[test]
+    fn test_row_range() {
+        let range = |s: &str| s.parse::<RowRange>().map_err(|e| e.to_string());
+        assert_eq!(
+            range("10..20"),
+            Ok(RowRange {
+                start: 10,
+                end: Some(20)
+            })
+        );
+        assert_eq!(
+            range("10.."),
+            Ok(RowRange {
+                start: 10,
+                end: None
+            })
+        );
+        assert_eq!(
+            range("..5"),
+            Ok(RowRange {
+                start: 1,
+                end: Some(5)
+            })
+        );
+        assert_eq!(
+            range("3"),
+            Ok(RowRange {
+                start: 3,
+                end: Some(3)
+            })
+        );
+        assert!(range("0..3").is_err());
+        assert!(range("5..3").is_err());
+        assert!(range("a..").is_err());
+    }
+
+    #[test]
+    fn test_render() -> Result<()> {
+        let table = juventus("2..3".parse()?)?;
+        assert_eq!(
+            table.render(12),
+            "┌──────────────┬─────────────┬────────────┐\n\
+             │ Name         │ Nationality │ Kit Number │\n\
+             ├──────────────┼─────────────┼────────────┤\n\
+             │ Mattia Perin │ Italy       │         37 │\n\
+             │ Gianluigi B… │ Italy       │         77 │\n\
+             └──────────────┴─────────────┴────────────┘\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("Higuaín", 7), "Higuaín");
+        assert_eq!(truncate("Higuaín", 5), "Higu…");
+        // wide characters take two columns
+        assert_eq!(truncate("日本語", 4), "日…");
+        assert_eq!(truncate("abc", 1), "…");
+    }
+
+    #[test]
+    fn test_control_characters() {
+        let cell = format!("a\tb{}\x1b[31mred", "\x01".repeat(50));
+        let text = cell_text(&Value::String(cell));
+        assert!(!text.chars().any(|c| c.is_control()));
+        assert!(text.starts_with("a b��"));
+        let line = row_line(&[(text, false)], &[10]);
+        assert_eq!(line, "│ a b������… │");
+        assert_eq!(width(&line), 14);
+    }
+
+    #[test]
+    fn test_pager_sort_and_search() -> Result<()> {
+        let mut pager = Pager::new(juventus(RowRange::default())?, 20);
+        let first_name = |pager: &Pager| pager.table.rows[pager.order[pager.top]][0].clone();
+        assert_eq!(first_name(&pager), "Wojciech Szczesny");
+
+        // sort by Kit Number, ascending then descending
+        pager.handle(Key::Right, 20);
+        pager.handle(Key::Right, 20);
+        pager.handle(Key::Char('s'), 20);
+        assert_eq!(pager.table.rows[pager.order[0]][2], 1);
+        pager.handle(Key::Char('s'), 20);
+        assert!(pager.table.rows[pager.order[0]][2].as_i64() > Some(30));
+        assert!(pager.screen(80, 20).0[1].starts_with("│ Kit Number"));
+
+        for key in [Key::Char('/'), Key::Char('d'), Key::Char('Y'), Key::Enter] {
+            assert!(pager.handle(key, 20));
+        }
+        assert_eq!(first_name(&pager), "Paulo Dybala");
+        let (lines, found) = pager.screen(200, 20);
+        assert_eq!(found, Some(3));
+        assert!(lines.last().unwrap().contains("sorted by Kit Number desc"));
+
+        pager.handle(Key::Char('/'), 20);
+        "nobody".chars().for_each(|c| {
+            pager.handle(Key::Char(c), 20);
+        });
+        pager.handle(Key::Enter, 20);
+        assert!(pager
+            .screen(200, 20)
+            .0
+            .last()
+            .unwrap()
+            .contains("not found: nobody"));
+        assert!(!pager.handle(Key::Char('q'), 20));
+        Ok(())
+    }
+
+    #[test]
+    fn test_pager_scrolls_within_bounds() -> Result<()> {
+        let mut pager = Pager::new(juventus(RowRange::default())?, 20);
+        // 10 lines leave room for 5 rows of 27
+        pager.handle(Key::End, 10);
+        assert_eq!(pager.top, 22);
+        pager.handle(Key::PageDown, 10);
+        assert_eq!(pager.top, 22);
+        let (lines, _) = pager.screen(80, 10);
+        assert_eq!(lines.len(), 10);
+        assert!(lines[9].starts_with("rows 23-27 of 27"));
+        pager.handle(Key::Home, 10);
+        pager.handle(Key::Up, 10);
+        assert_eq!(pager.top, 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,636 @@
+use crate::opt::{Compression, OutputFormat};
+use crate::process::csv_columnar::{ArrowIpcWriter, ParquetWriter};
+use crate::process::csv_flatten::unflatten;
+use crate::process::csv_reader::{Column, ColumnType, Record, RecordStream};
+use crate::utils::{get_plain_writer, get_writer, is_compressed_path, OutputWriter};
+use anyhow::{anyhow, Result};
+use rust_xlsxwriter::{ExcelDateTime, Format, Workbook};
+use serde_json::Value;
+use std::io::{stdout, IsTerminal, Write};
+
+#[derive(Debug, Clone)]
+pub struct CsvWriterOptions {
+    /// delimiter of CSV output
+    pub delimiter: u8,
+    /// codec of parquet and arrow output, `None` picks the format's default
+    pub compression: Option<Compression>,
+    /// worksheet name of xlsx output
+    pub sheet_name: Option<String>,
+    /// nest `address.city` and `tags[0]` columns in json, yaml and ndjson output
+    pub unflatten: bool,
+}
+
+/// a sink that receives records one by one, so nothing but the current record is kept in memory
+pub trait RecordWriter {
+    fn write_record(&mut self, record: &Record) -> Result<()>;
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+struct JsonWriter {
+    writer: OutputWriter,
+    count: usize,
+}
+
+struct YamlWriter {
+    writer: OutputWriter,
+    count: usize,
+}
+
+struct NdjsonWriter {
+    writer: OutputWriter,
+}
+
+struct TomlWriter {
+    writer: OutputWriter,
+    count: usize,
+}
+
+struct MarkdownWriter {
+    writer: OutputWriter,
+    columns: Vec<Column>,
+}
+
+struct HtmlWriter {
+    writer: OutputWriter,
+    columns: Vec<Column>,
+}
+
+struct CsvWriter {
+    writer: csv::Writer<OutputWriter>,
+    columns: Vec<Column>,
+}
+
+struct XlsxWriter {
+    workbook: Workbook,
+    columns: Vec<Column>,
+    /// longest cell of each column in characters, including the header
+    widths: Vec<usize>,
+    date_format: Format,
+    path: String,
+    row: u32,
+}
+
+impl Default for CsvWriterOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            compression: None,
+            sheet_name: None,
+            unflatten: false,
+        }
+    }
+}
+
+pub fn create_writer(
+    format: OutputFormat,
+    output: &str,
+    columns: &[Column],
+    opts: &CsvWriterOptions,
+) -> Result<Box<dyn RecordWriter>> {
+    if is_compressed_path(output) && format.is_binary() {
+        return Err(anyhow!(
+            "{} output can't be compressed by extension: {}",
+            format,
+            output
+        ));
+    }
+    if output == "-" && format.is_binary() && stdout().is_terminal() {
+        return Err(anyhow!(
+            "refusing to write {} to a terminal, redirect stdout or pass a file to -o",
+            format
+        ));
+    }
+    let writer: Box<dyn RecordWriter> = match format {
+        OutputFormat::Json => Box::new(JsonWriter::new(get_writer(output)?)),
+        OutputFormat::Yaml => Box::new(YamlWriter::new(get_writer(output)?)),
+        OutputFormat::Xlsx => Box::new(XlsxWriter::new(output, columns, opts)?),
+        OutputFormat::Ndjson => Box::new(NdjsonWriter::new(get_writer(output)?)),
+        OutputFormat::Toml => Box::new(TomlWriter::new(get_writer(output)?)),
+        OutputFormat::Markdown => Box::new(MarkdownWriter::new(get_writer(output)?, columns)?),
+        OutputFormat::Html => Box::new(HtmlWriter::new(get_writer(output)?, columns)?),
+        OutputFormat::Csv => Box::new(CsvWriter::new(get_writer(output)?, columns, opts)?),
+        OutputFormat::Parquet => {
+            let compression = opts.compression.unwrap_or(Compression::Snappy);
+            Box::new(ParquetWriter::try_new(output, columns, compression)?)
+        }
+        OutputFormat::Arrow => {
+            let compression = opts.compression.unwrap_or(Compression::None);
+            Box::new(ArrowIpcWriter::try_new(output, columns, compression)?)
+        }
+    };
+    Ok(writer)
+}
+
+/// apply the writer options that reshape records rather than format them, i.e. `unflatten`
+pub(crate) fn prepare_stream(
+    stream: RecordStream,
+    format: OutputFormat,
+    opts: &CsvWriterOptions,
+) -> Result<RecordStream> {
+    if !opts.unflatten {
+        return Ok(stream);
+    }
+    if !matches!(
+        format,
+        OutputFormat::Json | OutputFormat::Yaml | OutputFormat::Ndjson
+    ) {
+        return Err(anyhow!(
+            "--unflatten needs json, yaml or ndjson output, not {}",
+            format
+        ));
+    }
+    unflatten(stream)
+}
+
+/// drain a record stream into the given output, returning the number of records written
+pub fn write_records(
+    stream: RecordStream,
+    format: OutputFormat,
+    output: &str,
+    opts: &CsvWriterOptions,
+) -> Result<usize> {
+    let stream = prepare_stream(stream, format, opts)?;
+    let mut writer = create_writer(format, output, &stream.columns, opts)?;
+    let mut count = 0;
+    for record in stream.rows {
+        writer.write_record(&record?)?;
+        count += 1;
+    }
+    writer.finish()?;
+    Ok(count)
+}
+
+impl JsonWriter {
+    fn new(writer: OutputWriter) -> Self {
+        Self { writer, count: 0 }
+    }
+}
+
+impl RecordWriter for JsonWriter {
+    fn write_record(&mut self, record: &Record) -> Result<()> {
+        let sep = if self.count == 0 { "[\n" } else { ",\n" };
+        self.writer.write_all(sep.as_bytes())?;
+        // same layout as serializing the whole array with `to_string_pretty`
+        let content = serde_json::to_string_pretty(record)?;
+        for (i, line) in content.lines().enumerate() {
+            if i > 0 {
+                self.writer.write_all(b"\n")?;
+            }
+            write!(self.writer, "  {}", line)?;
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        let end = if self.count == 0 { "[]" } else { "\n]" };
+        self.writer.write_all(end.as_bytes())?;
+        self.writer.finish()?;
+        Ok(())
+    }
+}
+
+impl YamlWriter {
+    fn new(writer: OutputWriter) -> Self {
+        Self { writer, count: 0 }
+    }
+}
+
+impl RecordWriter for YamlWriter {
+    fn write_record(&mut self, record: &Record) -> Result<()> {
+        // a one element sequence serializes as a single `- ` item of the whole sequence
+        let content = serde_yaml::to_string(&[record])?;
+        self.writer.write_all(content.as_bytes())?;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        if self.count == 0 {
+            self.writer.write_all(b"[]\n")?;
+        }
+        self.writer.finish()?;
+        Ok(())
+    }
+}
+
+impl NdjsonWriter {
+    fn new(writer: OutputWriter) -> Self {
+        Self { writer }
+    }
+}
+
+impl RecordWriter for NdjsonWriter {
+    fn write_record(&mut self, record: &Record) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, record)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        self.writer.finish()?;
+        Ok(())
+    }
+}
+
+impl TomlWriter {
+    fn new(writer: OutputWriter) -> Self {
+        Self { writer, count: 0 }
+    }
+}
+
+impl RecordWriter for TomlWriter {
+    fn write_record(&mut self, record: &Record) -> Result<()> {
+        // toml has no null, so empty cells are left out of the table
+        let table: Record = record
+            .iter()
+            .filter(|(_, v)| !v.is_null())
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        let doc = serde_json::json!({ "records": [table] });
+        if self.count > 0 {
+            self.writer.write_all(b"\n")?;
+        }
+        self.writer.write_all(toml::to_string(&doc)?.as_bytes())?;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        if self.count == 0 {
+            self.writer.write_all(b"records = []\n")?;
+        }
+        self.writer.finish()?;
+        Ok(())
+    }
+}
+
+impl MarkdownWriter {
+    fn new(mut writer: OutputWriter, columns: &[Column]) -> Result<Self> {
+        let names: Vec<_> = columns.iter().map(|c| markdown_escape(&c.name)).collect();
+        writeln!(writer, "| {} |", names.join(" | "))?;
+        let aligns: Vec<_> = columns
+            .iter()
+            .map(|c| match c.ty {
+                ColumnType::Integer | ColumnType::Float => "---:",
+                ColumnType::Bool => ":---:",
+                ColumnType::Null | ColumnType::Date | ColumnType::String => ":---",
+            })
+            .collect();
+        writeln!(writer, "| {} |", aligns.join(" | "))?;
+        Ok(Self {
+            writer,
+            columns: columns.to_vec(),
+        })
+    }
+}
+
+impl RecordWriter for MarkdownWriter {
+    fn write_record(&mut self, record: &Record) -> Result<()> {
+        let cells: Vec<_> = self
+            .columns
+            .iter()
+            .map(|c| markdown_escape(&cell_text(record.get(&c.name))))
+            .collect();
+        writeln!(self.writer, "| {} |", cells.join(" | "))?;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        self.writer.finish()?;
+        Ok(())
+    }
+}
+
+impl HtmlWriter {
+    fn new(mut writer: OutputWriter, columns: &[Column]) -> Result<Self> {
+        writeln!(writer, "<table>")?;
+        writeln!(writer, "  <thead>")?;
+        write!(writer, "    <tr>")?;
+        for column in columns {
+            write!(writer, "<th>{}</th>", html_escape(&column.name))?;
+        }
+        writeln!(writer, "</tr>")?;
+        writeln!(writer, "  </thead>")?;
+        writeln!(writer, "  <tbody>")?;
+        Ok(Self {
+            writer,
+            columns: columns.to_vec(),
+        })
+    }
+}
+
+impl RecordWriter for HtmlWriter {
+    fn write_record(&mut self, record: &Record) -> Result<()> {
+        write!(self.writer, "    <tr>")?;
+        for column in &self.columns {
+            let text = cell_text(record.get(&column.name));
+            write!(self.writer, "<td>{}</td>", html_escape(&text))?;
+        }
+        writeln!(self.writer, "</tr>")?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        writeln!(self.writer, "  </tbody>")?;
+        writeln!(self.writer, "</table>")?;
+        self.writer.finish()?;
+        Ok(())
+    }
+}
+
+impl CsvWriter {
+    fn new(writer: OutputWriter, columns: &[Column], opts: &CsvWriterOptions) -> Result<Self> {
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(opts.delimiter)
+            .from_writer(writer);
+        writer.write_record(columns.iter().map(|c| &c.name))?;
+        Ok(Self {
+            writer,
+            columns: columns.to_vec(),
+        })
+    }
+}
+
+impl RecordWriter for CsvWriter {
+    fn write_record(&mut self, record: &Record) -> Result<()> {
+        let cells = self.columns.iter().map(|c| cell_text(record.get(&c.name)));
+        self.writer.write_record(cells)?;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        let writer = self.writer.into_inner().map_err(|e| e.into_error())?;
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+impl XlsxWriter {
+    fn new(path: &str, columns: &[Column], opts: &CsvWriterOptions) -> Result<Self> {
+        let mut workbook = Workbook::new();
+        let sheet = workbook.add_worksheet_with_constant_memory();
+        if let Some(name) = &opts.sheet_name {
+            sheet.set_name(name)?;
+        }
+        let bold = Format::new().set_bold();
+        for (col, column) in columns.iter().enumerate() {
+            sheet.write_string_with_format(0, col as u16, &column.name, &bold)?;
+        }
+        sheet.set_freeze_panes(1, 0)?;
+
+        Ok(Self {
+            workbook,
+            columns: columns.to_vec(),
+            widths: columns.iter().map(|c| c.name.chars().count()).collect(),
+            date_format: Format::new().set_num_format("yyyy-mm-dd"),
+            path: path.to_string(),
+            row: 1,
+        })
+    }
+}
+
+impl RecordWriter for XlsxWriter {
+    fn write_record(&mut self, record: &Record) -> Result<()> {
+        let sheet = self.workbook.worksheet_from_index(0)?;
+        for (col, column) in self.columns.iter().enumerate() {
+            let value = match record.get(&column.name) {
+                Some(Value::Null) | None => continue,
+                Some(value) => value,
+            };
+            let c = col as u16;
+            match value {
+                Value::String(s) if column.ty == ColumnType::Date => {
+                    match ExcelDateTime::parse_from_str(s) {
+                        Ok(date) => sheet.write_datetime_with_format(
+                            self.row,
+                            c,
+                            &date,
+                            &self.date_format,
+                        )?,
+                        Err(_) => sheet.write_string(self.row, c, s)?,
+                    }
+                }
+                Value::String(s) => sheet.write_string(self.row, c, s)?,
+                Value::Number(n) => {
+                    sheet.write_number(self.row, c, n.as_f64().unwrap_or_default())?
+                }
+                Value::Bool(b) => sheet.write_boolean(self.row, c, *b)?,
+                v => sheet.write_string(self.row, c, v.to_string())?,
+            };
+            let len = cell_text(Some(value)).chars().count();
+            self.widths[col] = self.widths[col].max(len);
+        }
+        self.row += 1;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        let sheet = self.workbook.worksheet_from_index(0)?;
+        for (col, chars) in self.widths.iter().enumerate() {
+            sheet.set_column_width(col as u16, column_width(*chars))?;
+        }
+        if self.path == "-" {
+            let mut stdout = get_plain_writer("-")?;
+            stdout.write_all(&self.workbook.save_to_buffer()?)?;
+            stdout.flush()?;
+        } else {
+            self.workbook.save(&self.path)?;
+        }
+        Ok(())
+    }
+}
+
+/// excel column width for the longest cell, with some padding for the bold header
+fn column_width(chars: usize) -> f64 {
+    (chars as f64 * 1.1 + 2.0).clamp(8.0, 80.0)
+}
+
+/// plain text of a cell, strings without quotes and nulls as empty
+fn cell_text(value: Option<&Value>) -> String {
+    match value {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Null) | None => String::new(),
+        Some(v) => v.to_string(),
+    }
+}
+
+fn markdown_escape(text: &str) -> String {
+    text.replace('|', "\\|")
+        .replace("\r\n", "<br>")
+        .replace('\n', "<br>")
+}
+
+fn html_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opt::InputFormat;
+    use crate::process::csv_flatten::open_structured;
+    use crate::process::csv_reader::{open_csv, CsvReaderOptions};
+    use crate::utils::temp_path;
+    use std::fs;
+
+    #[test]
+    fn test_streaming_json_matches_pretty_array() -> Result<()> {
+        let stream = open_csv("assets/juventus.csv", &CsvReaderOptions::default())?;
+        let output = temp_path("cli_streaming_json.json");
+        let output = output.to_string_lossy();
+        let count = write_records(stream, OutputFormat::Json, &output, &Default::default())?;
+        assert_eq!(count, 27);
+
+        let content = fs::read_to_string(output.as_ref())?;
+        let value: Vec<Value> = serde_json::from_str(&content)?;
+        assert_eq!(content, serde_json::to_string_pretty(&value)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_streaming_yaml_matches_sequence() -> Result<()> {
+        let stream = open_csv("assets/juventus.csv", &CsvReaderOptions::default())?;
+        let output = temp_path("cli_streaming_yaml.yaml");
+        let output = output.to_string_lossy();
+        write_records(stream, OutputFormat::Yaml, &output, &Default::default())?;
+
+        let content = fs::read_to_string(output.as_ref())?;
+        let value: Vec<Value> = serde_yaml::from_str(&content)?;
+        assert_eq!(value.len(), 27);
+        assert_eq!(content, serde_yaml::to_string(&value)?);
+        Ok(())
+    }
+
+    fn convert_juventus(format: OutputFormat) -> Result<String> {
+        let stream = open_csv("assets/juventus.csv", &CsvReaderOptions::default())?;
+        let output = temp_path(&format!("cli_golden_juventus.{}", format));
+        let output = output.to_string_lossy();
+        write_records(stream, format, &output, &CsvWriterOptions::default())?;
+        Ok(fs::read_to_string(output.as_ref())?)
+    }
+
+    #[test]
+    fn test_ndjson_golden() -> Result<()> {
+        let expected = fs::read_to_string("fixtures/juventus.ndjson")?;
+        assert_eq!(convert_juventus(OutputFormat::Ndjson)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_toml_golden() -> Result<()> {
+        let expected = fs::read_to_string("fixtures/juventus.toml")?;
+        assert_eq!(convert_juventus(OutputFormat::Toml)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_markdown_golden() -> Result<()> {
+        let expected = fs::read_to_string("fixtures/juventus.md")?;
+        assert_eq!(convert_juventus(OutputFormat::Markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_html_golden() -> Result<()> {
+        let expected = fs::read_to_string("fixtures/juventus.html")?;
+        assert_eq!(convert_juventus(OutputFormat::Html)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_escapes() {
+        assert_eq!(markdown_escape("a|b\nc"), "a\\|b<br>c");
+        assert_eq!(
+            html_escape("<a href=\"x\">&'"),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&#39;"
+        );
+    }
+
+    #[test]
+    fn test_xlsx_preserves_columns_and_types() -> Result<()> {
+        use calamine::{open_workbook, Data, Reader, Xlsx};
+
+        let path = temp_path("cli_xlsx_columns.csv");
+        fs::write(
+            &path,
+            "Name,Kit Number,Joined,Active\nalice,7,2019-07-01,true\nbob,,,false\n",
+        )?;
+        let stream = open_csv(&path.to_string_lossy(), &CsvReaderOptions::default())?;
+        let output = temp_path("cli_xlsx_columns.xlsx");
+        let opts = CsvWriterOptions {
+            sheet_name: Some("Players".to_string()),
+            ..Default::default()
+        };
+        write_records(stream, OutputFormat::Xlsx, &output.to_string_lossy(), &opts)?;
+
+        let mut workbook: Xlsx<_> = open_workbook(&output)?;
+        assert_eq!(workbook.sheet_names(), vec!["Players"]);
+        let range = workbook.worksheet_range("Players")?;
+        let rows: Vec<_> = range.rows().collect();
+        let header: Vec<_> = rows[0].iter().map(|c| c.to_string()).collect();
+        assert_eq!(header, vec!["Name", "Kit Number", "Joined", "Active"]);
+        assert_eq!(rows[1][0], Data::String("alice".to_string()));
+        assert_eq!(rows[1][1], Data::Float(7.0));
+        assert!(matches!(rows[1][2], Data::DateTime(_)));
+        assert_eq!(rows[1][3], Data::Bool(true));
+        assert_eq!(rows[2][1], Data::Empty);
+        Ok(())
+    }
+
+    #[test]
+    fn test_column_width() {
+        assert_eq!(column_width(0), 8.0);
+        assert_eq!(column_width(20), 24.0);
+        assert_eq!(column_width(1000), 80.0);
+    }
+
+    #[test]
+    fn test_compressed_output_round_trip() -> Result<()> {
+        let expected: Vec<Record> = open_csv("assets/juventus.csv", &CsvReaderOptions::default())?
+            .rows
+            .collect::<Result<_>>()?;
+        for (format, ext) in [
+            (OutputFormat::Csv, "csv.gz"),
+            (OutputFormat::Ndjson, "ndjson.xz"),
+        ] {
+            let stream = open_csv("assets/juventus.csv", &CsvReaderOptions::default())?;
+            let output = temp_path(&format!("cli_compressed.{}", ext));
+            let output = output.to_string_lossy();
+            write_records(stream, format, &output, &Default::default())?;
+
+            let stream = match format {
+                OutputFormat::Csv => open_csv(&output, &CsvReaderOptions::default())?,
+                _ => open_structured(&output, InputFormat::from_path(&output))?,
+            };
+            let rows: Vec<Record> = stream.rows.collect::<Result<_>>()?;
+            assert_eq!(rows, expected, "{}", ext);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_compressed_binary_output_is_rejected() -> Result<()> {
+        let stream = open_csv("assets/juventus.csv", &CsvReaderOptions::default())?;
+        let output = temp_path("cli_compressed.xlsx.gz");
+        let result = write_records(
+            stream,
+            OutputFormat::Xlsx,
+            &output.to_string_lossy(),
+            &Default::default(),
+        );
+        assert!(result.is_err());
+        Ok(())
+    }
+}
@@ -1,12 +1,59 @@
 mod base64;
+mod csv_anonymize;
+mod csv_batch;
+mod csv_codegen;
+mod csv_columnar;
 mod csv_convert;
+mod csv_dates;
+mod csv_diff;
+mod csv_encoding;
+mod csv_expr;
+mod csv_flatten;
+mod csv_generate;
+mod csv_join;
+mod csv_pivot;
+mod csv_query;
+mod csv_reader;
+mod csv_sheet;
+mod csv_split;
+mod csv_stats;
+mod csv_transform;
+mod csv_validate;
+mod csv_view;
+mod csv_writer;
 mod gen_pass;
 mod http;
 mod jwt;
 mod text;
 
 pub use base64::{process_decode, process_encode};
-pub use csv_convert::process_csv;
+pub use csv_anonymize::{anonymize, process_csv_anonymize, ColumnRule, Rule};
+pub use csv_batch::{
+    batch_output, expand_glob, process_csv_batch, BatchJob, BatchOptions, BatchOutcome,
+};
+pub use csv_codegen::{codegen, infer_fields, process_csv_codegen, Field};
+pub use csv_convert::{open_input, process_csv};
+pub use csv_dates::{parse_dates, DateColumn, DateFunc, DateValue, DerivedColumn};
+pub use csv_diff::{diff, process_csv_diff, render_diff, CellChange, Diff, RowChange};
+pub use csv_encoding::{decode_reader, detect};
+pub use csv_expr::{CompareOp, Expr, SortKey};
+pub use csv_flatten::{flatten, open_structured, unflatten};
+pub use csv_generate::{
+    generate, process_csv_generate, GenerateSchema, GeneratedColumn, Generator,
+};
+pub use csv_join::{concat, join, process_csv_concat, process_csv_join, JoinOptions};
+pub use csv_pivot::{melt, pivot, process_csv_melt, process_csv_pivot, MeltOptions, PivotOptions};
+pub use csv_query::{process_csv_query, Aggregate, Query};
+pub use csv_reader::{open_csv, Column, ColumnType, CsvReaderOptions, Record, RecordStream};
+pub use csv_sheet::open_sheet;
+pub use csv_split::{process_csv_split, split, split_prefix, SplitMode, SplitOptions, SplitPiece};
+pub use csv_stats::{
+    process_csv_stats, profile, render_stats, ColumnStats, LengthBucket, StatsOptions, ValueCount,
+};
+pub use csv_transform::Transform;
+pub use csv_validate::{process_csv_validate, ColumnSchema, Schema, ValidationError};
+pub use csv_view::{process_csv_view, run_pager, Key, Pager, RowRange, Table};
+pub use csv_writer::{create_writer, write_records, CsvWriterOptions, RecordWriter};
 pub use gen_pass::process_gen_pass;
 pub use http::process_http_serve;
 pub use jwt::{process_jwt_sign, process_jwt_verify};
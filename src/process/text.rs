@@ -122,7 +122,7 @@ pub fn process_text_decrypt(input: &str, key: &str) -> Result<String> {
     Ok(decrypted.to_string())
 }
 
-/// Blake3 implementation for TextVerify, KeyLoader, KeyGenerate, and TextSign
+// Blake3 implementation for TextVerify, KeyLoader, KeyGenerate, and TextSign
 
 impl TextSign for Blake3 {
     fn sign(&self, reader: &mut dyn Read) -> Result<Vec<u8>> {
@@ -211,11 +211,18 @@ impl Blake3 {
     }
 
     pub fn try_new(key: &[u8]) -> Result<Self> {
-        let key = &key[..32];
+        let key = key
+            .get(..32)
+            .ok_or_else(|| anyhow::anyhow!("blake3 key must be at least 32 bytes"))?;
         let key = key.try_into()?;
         let singer = Blake3::new(key);
         Ok(singer)
     }
+
+    /// keyed hash of `data`, e.g. to pseudonymize values
+    pub fn hash(&self, data: &[u8]) -> blake3::Hash {
+        blake3::keyed_hash(&self.key, data)
+    }
 }
 
 impl Ed25519Signer {
@@ -240,7 +247,7 @@ impl Ed25519Verifier {
     }
 }
 
-/// test
+// test
 
 #[cfg(test)]
 mod tests {
@@ -1,6 +1,22 @@
 use anyhow::Result;
+use bzip2::read::MultiBzDecoder;
+use bzip2::write::BzEncoder;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
 use std::fs::File;
-use std::io::{stdin, Read};
+use std::io::{self, stdin, stdout, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use xz2::read::XzDecoder;
+use xz2::write::XzEncoder;
+
+/// stream compression recognized by file extension or magic bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Codec {
+    Gzip,
+    Zstd,
+    Bzip2,
+    Xz,
+}
 
 pub fn get_reader(input: &str) -> Result<Box<dyn Read>> {
     let reader: Box<dyn Read> = if input == "-" {
@@ -10,3 +26,269 @@ pub fn get_reader(input: &str) -> Result<Box<dyn Read>> {
     };
     Ok(reader)
 }
+
+/// like `get_reader`, but transparently decompresses gzip, zstd, bzip2 and xz input
+///
+/// The codec comes from the extension (`.gz`, `.zst`, `.bz2`, `.xz`) or else the magic bytes,
+/// so compressed stdin works too. Decompression is streamed.
+pub fn get_decompressed_reader(input: &str) -> Result<Box<dyn Read>> {
+    let mut reader = BufReader::new(get_reader(input)?);
+    let codec = match Codec::from_path(input) {
+        Some(codec) => Some(codec),
+        None => Codec::from_magic(reader.fill_buf()?),
+    };
+    let reader: Box<dyn Read> = match codec {
+        Some(Codec::Gzip) => Box::new(MultiGzDecoder::new(reader)),
+        Some(Codec::Zstd) => Box::new(zstd::Decoder::with_buffer(reader)?),
+        Some(Codec::Bzip2) => Box::new(MultiBzDecoder::new(reader)),
+        Some(Codec::Xz) => Box::new(XzDecoder::new_multi_decoder(reader)),
+        None => Box::new(reader),
+    };
+    Ok(reader)
+}
+
+type PlainWriter = Box<dyn Write + Send>;
+
+/// a buffered, possibly compressed writer that has to be finished
+///
+/// `finish` writes the end of the compressed stream and flushes, returning any error that
+/// dropping the writer would swallow, such as a full disk.
+pub enum OutputWriter {
+    Plain(PlainWriter),
+    Gzip(GzEncoder<PlainWriter>),
+    Zstd(zstd::Encoder<'static, PlainWriter>),
+    Bzip2(BzEncoder<PlainWriter>),
+    Xz(XzEncoder<PlainWriter>),
+}
+
+/// a buffered writer to a file or stdout (`-`), compressed according to the extension of `output`
+pub fn get_writer(output: &str) -> Result<OutputWriter> {
+    let file = get_plain_writer(output)?;
+    let writer = match Codec::from_path(output) {
+        Some(Codec::Gzip) => {
+            OutputWriter::Gzip(GzEncoder::new(file, flate2::Compression::default()))
+        }
+        Some(Codec::Zstd) => OutputWriter::Zstd(zstd::Encoder::new(file, 0)?),
+        Some(Codec::Bzip2) => {
+            OutputWriter::Bzip2(BzEncoder::new(file, bzip2::Compression::default()))
+        }
+        Some(Codec::Xz) => OutputWriter::Xz(XzEncoder::new(file, 6)),
+        None => OutputWriter::Plain(file),
+    };
+    Ok(writer)
+}
+
+/// a buffered writer to a file or stdout (`-`) without compression, for binary formats
+pub fn get_plain_writer(output: &str) -> Result<Box<dyn Write + Send>> {
+    let writer: Box<dyn Write + Send> = if output == "-" {
+        Box::new(BufWriter::new(stdout()))
+    } else {
+        Box::new(BufWriter::new(File::create(output)?))
+    };
+    Ok(writer)
+}
+
+impl OutputWriter {
+    pub fn finish(self) -> io::Result<()> {
+        let mut file = match self {
+            OutputWriter::Plain(file) => file,
+            OutputWriter::Gzip(encoder) => encoder.finish()?,
+            OutputWriter::Zstd(encoder) => encoder.finish()?,
+            OutputWriter::Bzip2(encoder) => encoder.finish()?,
+            OutputWriter::Xz(encoder) => encoder.finish()?,
+        };
+        file.flush()
+    }
+
+    fn inner(&mut self) -> &mut dyn Write {
+        match self {
+            OutputWriter::Plain(file) => file,
+            OutputWriter::Gzip(encoder) => encoder,
+            OutputWriter::Zstd(encoder) => encoder,
+            OutputWriter::Bzip2(encoder) => encoder,
+            OutputWriter::Xz(encoder) => encoder,
+        }
+    }
+}
+
+impl Write for OutputWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner().flush()
+    }
+}
+
+/// whether `err` comes from writing to a pipe whose reader has gone away, e.g. `| head`
+///
+/// csv and serde_json wrap the io error without exposing it as a source, so they are unwrapped
+/// by hand.
+pub fn is_broken_pipe(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        let kind = if let Some(e) = cause.downcast_ref::<io::Error>() {
+            Some(e.kind())
+        } else if let Some(e) = cause.downcast_ref::<serde_json::Error>() {
+            e.io_error_kind()
+        } else if let Some(e) = cause.downcast_ref::<csv::Error>() {
+            match e.kind() {
+                csv::ErrorKind::Io(e) => Some(e.kind()),
+                _ => None,
+            }
+        } else {
+            None
+        };
+        kind == Some(io::ErrorKind::BrokenPipe)
+    })
+}
+
+/// whether the extension of `path` selects a compression codec
+pub fn is_compressed_path(path: &str) -> bool {
+    Codec::from_path(path).is_some()
+}
+
+/// `path` without a trailing compression extension, e.g. `data.csv.gz` becomes `data.csv`
+pub fn strip_compression_ext(path: &str) -> &str {
+    match Codec::from_path(path) {
+        Some(_) => path.rsplit_once('.').map_or(path, |(stem, _)| stem),
+        None => path,
+    }
+}
+
+impl Codec {
+    fn from_path(path: &str) -> Option<Self> {
+        let ext = Path::new(path)
+            .extension()?
+            .to_string_lossy()
+            .to_ascii_lowercase();
+        match ext.as_str() {
+            "gz" | "gzip" => Some(Codec::Gzip),
+            "zst" | "zstd" => Some(Codec::Zstd),
+            "bz2" => Some(Codec::Bzip2),
+            "xz" => Some(Codec::Xz),
+            _ => None,
+        }
+    }
+
+    fn from_magic(head: &[u8]) -> Option<Self> {
+        if head.starts_with(&[0x1f, 0x8b]) {
+            Some(Codec::Gzip)
+        } else if head.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Codec::Zstd)
+        } else if head.starts_with(b"BZh") {
+            Some(Codec::Bzip2)
+        } else if head.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(Codec::Xz)
+        } else {
+            None
+        }
+    }
+}
+
+/// a path in a temp directory of its own for the running test, so tests running in parallel, or
+/// in another process, never share a file
+#[cfg(test)]
+pub(crate) fn temp_path(name: &str) -> std::path::PathBuf {
+    let thread = std::thread::current();
+    let test = thread.name().unwrap_or("main").replace("::", "-");
+    let dir = std::env::temp_dir()
+        .join(format!("cli-tests-{}", std::process::id()))
+        .join(test);
+    std::fs::create_dir_all(&dir).expect("the temp dir is writable");
+    dir.join(name)
+}
+
+/// write `content` to `temp_path(name)` and return the path
+#[cfg(test)]
+pub(crate) fn write_temp(name: &str, content: &str) -> Result<String> {
+    let path = temp_path(name);
+    std::fs::write(&path, content)?;
+    Ok(path.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENT: &str = "Name,Kit Number\nGonzalo Higuaín,21\n";
+
+    fn tmp(name: &str) -> String {
+        temp_path(name).to_string_lossy().into_owned()
+    }
+
+    fn read(path: &str) -> Result<String> {
+        let mut content = String::new();
+        get_decompressed_reader(path)?.read_to_string(&mut content)?;
+        Ok(content)
+    }
+
+    #[test]
+    fn test_compressed_round_trip() -> Result<()> {
+        for ext in ["csv", "csv.gz", "csv.zst", "csv.bz2", "csv.xz"] {
+            let path = tmp(&format!("cli_codec.{}", ext));
+            let mut writer = get_writer(&path)?;
+            writer.write_all(CONTENT.as_bytes())?;
+            writer.finish()?;
+            assert_eq!(read(&path)?, CONTENT, "{}", ext);
+            if ext != "csv" {
+                assert_ne!(std::fs::read(&path)?, CONTENT.as_bytes(), "{}", ext);
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_finish_reports_write_errors() -> Result<()> {
+        // every write to /dev/full fails with "No space left on device"
+        for ext in ["csv", "csv.gz", "csv.zst", "csv.bz2", "csv.xz"] {
+            let path = tmp(&format!("cli_codec_full.{}", ext));
+            let _ = std::fs::remove_file(&path);
+            std::os::unix::fs::symlink("/dev/full", &path)?;
+            let mut writer = get_writer(&path)?;
+            writer.write_all(CONTENT.as_bytes())?;
+            let err = writer.finish().unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::StorageFull, "{}", ext);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_magic_without_extension() -> Result<()> {
+        let compressed = tmp("cli_codec_magic.zst");
+        let mut writer = get_writer(&compressed)?;
+        writer.write_all(CONTENT.as_bytes())?;
+        writer.finish()?;
+        let renamed = tmp("cli_codec_magic.csv");
+        std::fs::rename(&compressed, &renamed)?;
+        assert_eq!(read(&renamed)?, CONTENT);
+        Ok(())
+    }
+
+    #[test]
+    fn test_strip_compression_ext() {
+        assert_eq!(strip_compression_ext("data.csv.gz"), "data.csv");
+        assert_eq!(strip_compression_ext("data.JSON.XZ"), "data.JSON");
+        assert_eq!(strip_compression_ext("data.csv"), "data.csv");
+        assert!(is_compressed_path("a.ndjson.zst"));
+        assert!(!is_compressed_path("-"));
+    }
+
+    #[test]
+    fn test_is_broken_pipe() {
+        let pipe = || io::Error::from(io::ErrorKind::BrokenPipe);
+        assert!(is_broken_pipe(&anyhow::Error::from(pipe())));
+        assert!(is_broken_pipe(&anyhow::Error::from(serde_json::Error::io(
+            pipe()
+        ))));
+        assert!(is_broken_pipe(&anyhow::Error::from(csv::Error::from(
+            pipe()
+        ))));
+        assert!(is_broken_pipe(
+            &anyhow::Error::from(pipe()).context("writing output")
+        ));
+        let other = io::Error::from(io::ErrorKind::PermissionDenied);
+        assert!(!is_broken_pipe(&anyhow::Error::from(other)));
+    }
+}
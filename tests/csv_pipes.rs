@@ -0,0 +1,127 @@
+use anyhow::Result;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Command, Output, Stdio};
+use std::thread;
+
+const JUVENTUS: &str = include_str!("../assets/juventus.csv");
+
+fn cli() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_cli"))
+}
+
+/// run the binary with `input` on stdin, collecting stdout and stderr
+fn run(args: &[&str], input: &[u8]) -> Result<Output> {
+    let mut child = cli()
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut stdin = child.stdin.take().unwrap();
+    let input = input.to_vec();
+    // written from another thread so a full stdout pipe can't deadlock us
+    let writer = thread::spawn(move || stdin.write_all(&input));
+    let output = child.wait_with_output()?;
+    writer.join().unwrap()?;
+    Ok(output)
+}
+
+fn stdout(args: &[&str], input: &[u8]) -> Result<Vec<u8>> {
+    let output = run(args, input)?;
+    assert!(
+        output.status.success(),
+        "{:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Ok(output.stdout)
+}
+
+#[test]
+fn test_stdin_to_stdout() -> Result<()> {
+    let yaml = stdout(&["csv", "--format", "yaml", "-o", "-"], JUVENTUS.as_bytes())?;
+    let yaml = String::from_utf8(yaml)?;
+    assert!(yaml.starts_with("- Name: Wojciech Szczesny\n  Position: Goalkeeper\n"));
+    assert_eq!(yaml.matches("- Name: ").count(), 27);
+    Ok(())
+}
+
+#[test]
+fn test_pipeline_round_trip() -> Result<()> {
+    // csv | json | csv gives back the original file
+    let json = stdout(
+        &["csv", "-i", "-", "--format", "json", "-o", "-"],
+        JUVENTUS.as_bytes(),
+    )?;
+    let csv = stdout(
+        &["csv", "--from", "json", "--format", "csv", "-o", "-"],
+        &json,
+    )?;
+    assert_eq!(String::from_utf8(csv)?, JUVENTUS);
+    Ok(())
+}
+
+#[test]
+fn test_binary_formats_through_pipes() -> Result<()> {
+    let xlsx = stdout(&["csv", "--format", "xlsx", "-o", "-"], JUVENTUS.as_bytes())?;
+    assert!(xlsx.starts_with(b"PK"));
+    let csv = stdout(
+        &["csv", "--from", "xlsx", "--format", "csv", "-o", "-"],
+        &xlsx,
+    )?;
+    let csv = String::from_utf8(csv)?;
+    assert_eq!(csv.lines().count(), JUVENTUS.lines().count());
+
+    for (format, magic) in [("parquet", &b"PAR1"[..]), ("arrow", &b"ARROW1"[..])] {
+        let out = stdout(&["csv", "--format", format, "-o", "-"], JUVENTUS.as_bytes())?;
+        assert!(out.starts_with(magic), "{}", format);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_compressed_stdin() -> Result<()> {
+    let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    gz.write_all(JUVENTUS.as_bytes())?;
+    let ndjson = stdout(&["csv", "--format", "ndjson", "-o", "-"], &gz.finish()?)?;
+    assert_eq!(String::from_utf8(ndjson)?.lines().count(), 27);
+    Ok(())
+}
+
+#[test]
+fn test_closed_stdout_is_not_an_error() -> Result<()> {
+    let schema = std::env::temp_dir().join(format!("cli_pipes_schema_{}.yaml", std::process::id()));
+    std::fs::write(
+        &schema,
+        "columns:\n  - name: id\n    generator: sequence\n  - name: name\n    generator: name\n",
+    )?;
+    for format in ["csv", "json", "ndjson"] {
+        let mut child = cli()
+            .args([
+                "csv", "generate", "-r", "1000000", "--format", format, "-o", "-", "-s",
+            ])
+            .arg(&schema)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        // like `| head -1`: read a line, then hang up
+        let mut line = String::new();
+        BufReader::new(child.stdout.take().unwrap()).read_line(&mut line)?;
+        assert!(!line.is_empty());
+        let status = child.wait()?;
+        let mut stderr = String::new();
+        child.stderr.take().unwrap().read_to_string(&mut stderr)?;
+        assert!(status.success(), "{}: {}", format, stderr);
+        assert_eq!(stderr, "", "{}", format);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_errors_go_to_stderr() -> Result<()> {
+    let output = run(&["csv", "--from", "json", "-o", "-"], b"[1, 2]")?;
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8(output.stderr)?.contains("record 1 is not an object"));
+    Ok(())
+}
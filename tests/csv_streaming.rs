@@ -0,0 +1,95 @@
+use anyhow::Result;
+use cli::{process_csv, CsvReaderOptions, CsvWriterOptions, InputFormat, OutputFormat, Transform};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// tracks live and peak heap usage so the test can tell whether memory grows with the input
+struct PeakAlloc;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for PeakAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let current = CURRENT.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+            PEAK.fetch_max(current, Ordering::SeqCst);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        CURRENT.fetch_sub(layout.size(), Ordering::SeqCst);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: PeakAlloc = PeakAlloc;
+
+fn generate_csv(rows: usize) -> Result<PathBuf> {
+    let path =
+        std::env::temp_dir().join(format!("cli_streaming_{}_{}.csv", rows, std::process::id()));
+    let mut writer = BufWriter::new(File::create(&path)?);
+    writeln!(writer, "id,name,score,active,comment")?;
+    for i in 0..rows {
+        writeln!(
+            writer,
+            "{},player {},{}.5,{},\"line {}, with a quoted delimiter\"",
+            i,
+            i,
+            i % 100,
+            i % 2 == 0,
+            i
+        )?;
+    }
+    writer.flush()?;
+    Ok(path)
+}
+
+/// peak heap usage above the baseline while converting `input`
+fn peak_usage(input: &Path, format: OutputFormat) -> Result<usize> {
+    let output = input.with_extension(format.to_string());
+    let baseline = CURRENT.load(Ordering::SeqCst);
+    PEAK.store(baseline, Ordering::SeqCst);
+    process_csv(
+        &input.to_string_lossy(),
+        output.to_string_lossy().into_owned(),
+        format,
+        InputFormat::Csv,
+        &CsvReaderOptions::default(),
+        &CsvWriterOptions::default(),
+        &Transform::default(),
+    )?;
+    let peak = PEAK.load(Ordering::SeqCst) - baseline;
+    std::fs::remove_file(output)?;
+    Ok(peak)
+}
+
+// a single test, so no other test allocates concurrently and skews the peak
+#[test]
+fn test_peak_memory_stays_flat() -> Result<()> {
+    let small = generate_csv(5_000)?;
+    let large = generate_csv(50_000)?;
+
+    for format in [OutputFormat::Json, OutputFormat::Yaml, OutputFormat::Xlsx] {
+        let small_peak = peak_usage(&small, format)?;
+        let large_peak = peak_usage(&large, format)?;
+        // 10x the rows must not need noticeably more memory
+        assert!(
+            large_peak < small_peak * 2 + 1024 * 1024,
+            "{} peak grew from {} to {} bytes",
+            format,
+            small_peak,
+            large_peak
+        );
+    }
+
+    std::fs::remove_file(small)?;
+    std::fs::remove_file(large)?;
+    Ok(())
+}